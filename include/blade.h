@@ -0,0 +1,97 @@
+#ifndef BLADE_H
+#define BLADE_H
+
+/* Generated by cbindgen from src/ffi.rs. Don't edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define BLADE_OK 0
+
+#define BLADE_NOT_FOUND 1
+
+#define BLADE_USAGE 2
+
+#define BLADE_LOCKED 3
+
+#define BLADE_ERROR 7
+
+// An open database, from `blade_open`
+typedef struct Blade Blade;
+
+// Opens the database at `path`, creating and migrating it if needed.
+// Returns null on error. Close it with `blade_close`.
+//
+// # Safety
+// `path` must be null or a nul-terminated string
+Blade *blade_open(const char *path);
+
+// Closes a database from `blade_open`. Does nothing if `db` is null.
+//
+// # Safety
+// `db` must be null or from `blade_open`, and not already closed
+void blade_close(Blade *db);
+
+// Gets a key, setting `value` and `len` to a copy of its value.
+// Free the value with `blade_free_value`.
+// Returns `BLADE_NOT_FOUND` if the key doesn't exist.
+//
+// Returns `BLADE_USAGE` if any of the pointers are null.
+//
+// # Safety
+// `db` must be null or from `blade_open`, `namespace` and `key` must be null or
+// nul-terminated strings, and `value` and `len` must be null or valid to write to
+int blade_get(const Blade *db,
+              const char *namespace_,
+              const char *key,
+              uint8_t **value,
+              size_t *len);
+
+// Frees a value from `blade_get`. Does nothing if `value` is null.
+//
+// # Safety
+// `value` and `len` must be from `blade_get`, and the value not already freed
+void blade_free_value(uint8_t *value, size_t len);
+
+// Sets a key to the `len` bytes at `value`. Existing keys keep their expiry.
+// Returns `BLADE_USAGE` if `db`, `namespace`, or `key` are null.
+//
+// # Safety
+// `db` must be null or from `blade_open`, `namespace` and `key` must be null or
+// nul-terminated strings, and `value` must point to `len` bytes, or be null if `len` is 0
+int blade_set(const Blade *db,
+              const char *namespace_,
+              const char *key,
+              const uint8_t *value,
+              size_t len);
+
+// Deletes a key. Returns `BLADE_NOT_FOUND` if it didn't exist,
+// and `BLADE_USAGE` if any of the pointers are null.
+//
+// # Safety
+// `db` must be null or from `blade_open`,
+// and `namespace` and `key` must be null or nul-terminated strings
+int blade_delete(const Blade *db, const char *namespace_, const char *key);
+
+// Lists the keys in a namespace, in order, setting `keys` to an array of `len` strings.
+// Free them with `blade_free_list`. Returns `BLADE_USAGE` if any of the pointers are null.
+//
+// # Safety
+// `db` must be null or from `blade_open`, `namespace` must be null or
+// a nul-terminated string, and `keys` and `len` must be null or valid to write to
+int blade_list(const Blade *db, const char *namespace_, char ***keys, size_t *len);
+
+// Frees keys from `blade_list`. Does nothing if `keys` is null.
+//
+// # Safety
+// `keys` and `len` must be from `blade_list`, and the keys not already freed
+void blade_free_list(char **keys, size_t len);
+
+// The message of the last error on this thread, or null if there hasn't been one.
+// It's valid until the next error on this thread.
+const char *blade_last_error(void);
+
+#endif  /* BLADE_H */
@@ -0,0 +1,320 @@
+//! A C API over a local database, so scripts in other languages can use it
+//! without running blade once per key.
+//! `include/blade.h` is generated from this file with
+//! `cbindgen --config cbindgen.toml --output include/blade.h`.
+//!
+//! Functions return `BLADE_OK` or one of the other codes, which match blade's exit codes.
+//! `blade_last_error` has the message of the last error on the calling thread.
+//! A `Blade*` can be shared between threads, which take turns using its connection.
+//! This doesn't run hooks, schemas, or quotas.
+
+use crate::{Backend, migrate_db};
+use rusqlite::{Connection, ErrorCode};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::fmt::Display;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+pub const BLADE_OK: c_int = 0;
+pub const BLADE_NOT_FOUND: c_int = 1;
+pub const BLADE_USAGE: c_int = 2;
+pub const BLADE_LOCKED: c_int = 3;
+pub const BLADE_ERROR: c_int = 7;
+
+/// An open database, from `blade_open`
+pub struct Blade {
+    conn: Mutex<Connection>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn fail(code: c_int, message: impl Display) -> c_int {
+    let message =
+        CString::new(message.to_string().replace('\0', "")).expect("nul bytes were removed");
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+    code
+}
+
+fn fail_sqlite(e: rusqlite::Error) -> c_int {
+    let code = match e.sqlite_error_code() {
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => BLADE_LOCKED,
+        _ => BLADE_ERROR,
+    };
+    fail(code, e)
+}
+
+/// # Safety
+/// `s` must be null or a nul-terminated string that outlives `'a`
+unsafe fn str_arg<'a>(s: *const c_char, name: &str) -> Result<&'a str, c_int> {
+    if s.is_null() {
+        return Err(fail(BLADE_USAGE, format!("{} is null", name)));
+    }
+
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .map_err(|_| fail(BLADE_USAGE, format!("{} is not valid UTF-8", name)))
+}
+
+/// # Safety
+/// `db` must be null or from `blade_open`, and not closed before `'a`
+unsafe fn db_arg<'a>(db: *const Blade) -> Result<MutexGuard<'a, Connection>, c_int> {
+    if db.is_null() {
+        return Err(fail(BLADE_USAGE, "db is null"));
+    }
+
+    let conn = unsafe { &(*db).conn };
+    Ok(conn.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// # Safety
+/// `namespace` and `key` must be null or nul-terminated strings that outlive `'a`
+unsafe fn key_args<'a>(
+    namespace: *const c_char,
+    key: *const c_char,
+) -> Result<(&'a str, &'a str), c_int> {
+    unsafe { Ok((str_arg(namespace, "namespace")?, str_arg(key, "key")?)) }
+}
+
+fn open(path: &str) -> anyhow::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "journal_mode", "wal")?;
+    // the same as the default `sqlite_busy_timeout_ms`
+    conn.pragma_update(None, "busy_timeout", 5_000)?;
+    migrate_db(conn)
+}
+
+/// Opens the database at `path`, creating and migrating it if needed.
+/// Returns null on error. Close it with `blade_close`.
+///
+/// # Safety
+/// `path` must be null or a nul-terminated string
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blade_open(path: *const c_char) -> *mut Blade {
+    let Ok(path) = (unsafe { str_arg(path, "path") }) else {
+        return ptr::null_mut();
+    };
+
+    match open(path) {
+        Ok(conn) => Box::into_raw(Box::new(Blade {
+            conn: Mutex::new(conn),
+        })),
+        Err(e) => {
+            fail(BLADE_ERROR, format!("{:#}", e));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Closes a database from `blade_open`. Does nothing if `db` is null.
+///
+/// # Safety
+/// `db` must be null or from `blade_open`, and not already closed
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blade_close(db: *mut Blade) {
+    if !db.is_null() {
+        drop(unsafe { Box::from_raw(db) });
+    }
+}
+
+/// Gets a key, setting `value` and `len` to a copy of its value.
+/// Free the value with `blade_free_value`.
+/// Returns `BLADE_NOT_FOUND` if the key doesn't exist.
+///
+/// Returns `BLADE_USAGE` if any of the pointers are null.
+///
+/// # Safety
+/// `db` must be null or from `blade_open`, `namespace` and `key` must be null or
+/// nul-terminated strings, and `value` and `len` must be null or valid to write to
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blade_get(
+    db: *const Blade,
+    namespace: *const c_char,
+    key: *const c_char,
+    value: *mut *mut u8,
+    len: *mut usize,
+) -> c_int {
+    let conn = match unsafe { db_arg(db) } {
+        Ok(conn) => conn,
+        Err(code) => return code,
+    };
+
+    let (namespace, key) = match unsafe { key_args(namespace, key) } {
+        Ok(args) => args,
+        Err(code) => return code,
+    };
+
+    if value.is_null() || len.is_null() {
+        return fail(BLADE_USAGE, "value and len can't be null");
+    }
+
+    match conn.get(namespace, key) {
+        Ok(Some(found)) => {
+            unsafe {
+                *len = found.len();
+                *value = Box::into_raw(found.into_boxed_slice()).cast();
+            }
+            BLADE_OK
+        }
+        Ok(None) => fail(BLADE_NOT_FOUND, format!("key not found: {}", key)),
+        Err(e) => fail_sqlite(e),
+    }
+}
+
+/// Frees a value from `blade_get`. Does nothing if `value` is null.
+///
+/// # Safety
+/// `value` and `len` must be from `blade_get`, and the value not already freed
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blade_free_value(value: *mut u8, len: usize) {
+    if !value.is_null() {
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(value, len)) });
+    }
+}
+
+/// Sets a key to the `len` bytes at `value`. Existing keys keep their expiry.
+/// Returns `BLADE_USAGE` if `db`, `namespace`, or `key` are null.
+///
+/// # Safety
+/// `db` must be null or from `blade_open`, `namespace` and `key` must be null or
+/// nul-terminated strings, and `value` must point to `len` bytes, or be null if `len` is 0
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blade_set(
+    db: *const Blade,
+    namespace: *const c_char,
+    key: *const c_char,
+    value: *const u8,
+    len: usize,
+) -> c_int {
+    let conn = match unsafe { db_arg(db) } {
+        Ok(conn) => conn,
+        Err(code) => return code,
+    };
+
+    let (namespace, key) = match unsafe { key_args(namespace, key) } {
+        Ok(args) => args,
+        Err(code) => return code,
+    };
+
+    let value = match (value.is_null(), len) {
+        (true, 0) => &[],
+        (true, _) => return fail(BLADE_USAGE, "value is null"),
+        (false, _) => unsafe { std::slice::from_raw_parts(value, len) },
+    };
+
+    match conn.set(namespace, key, value) {
+        Ok(()) => BLADE_OK,
+        Err(e) => fail_sqlite(e),
+    }
+}
+
+/// Deletes a key. Returns `BLADE_NOT_FOUND` if it didn't exist,
+/// and `BLADE_USAGE` if any of the pointers are null.
+///
+/// # Safety
+/// `db` must be null or from `blade_open`,
+/// and `namespace` and `key` must be null or nul-terminated strings
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blade_delete(
+    db: *const Blade,
+    namespace: *const c_char,
+    key: *const c_char,
+) -> c_int {
+    let conn = match unsafe { db_arg(db) } {
+        Ok(conn) => conn,
+        Err(code) => return code,
+    };
+
+    let (namespace, key) = match unsafe { key_args(namespace, key) } {
+        Ok(args) => args,
+        Err(code) => return code,
+    };
+
+    match conn.delete(namespace, key) {
+        Ok(true) => BLADE_OK,
+        Ok(false) => fail(BLADE_NOT_FOUND, format!("key not found: {}", key)),
+        Err(e) => fail_sqlite(e),
+    }
+}
+
+/// Lists the keys in a namespace, in order, setting `keys` to an array of `len` strings.
+/// Free them with `blade_free_list`. Returns `BLADE_USAGE` if any of the pointers are null.
+///
+/// # Safety
+/// `db` must be null or from `blade_open`, `namespace` must be null or
+/// a nul-terminated string, and `keys` and `len` must be null or valid to write to
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blade_list(
+    db: *const Blade,
+    namespace: *const c_char,
+    keys: *mut *mut *mut c_char,
+    len: *mut usize,
+) -> c_int {
+    let conn = match unsafe { db_arg(db) } {
+        Ok(conn) => conn,
+        Err(code) => return code,
+    };
+
+    let namespace = match unsafe { str_arg(namespace, "namespace") } {
+        Ok(namespace) => namespace,
+        Err(code) => return code,
+    };
+
+    if keys.is_null() || len.is_null() {
+        return fail(BLADE_USAGE, "keys and len can't be null");
+    }
+
+    let listed = match conn.list(namespace) {
+        Ok(listed) => listed,
+        Err(e) => return fail_sqlite(e),
+    };
+
+    let listed = match listed
+        .into_iter()
+        .map(CString::new)
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(listed) => listed,
+        Err(e) => return fail(BLADE_ERROR, format!("a key has a nul byte: {}", e)),
+    };
+
+    let listed: Box<[*mut c_char]> = listed.into_iter().map(CString::into_raw).collect();
+
+    unsafe {
+        *len = listed.len();
+        *keys = Box::into_raw(listed).cast();
+    }
+
+    BLADE_OK
+}
+
+/// Frees keys from `blade_list`. Does nothing if `keys` is null.
+///
+/// # Safety
+/// `keys` and `len` must be from `blade_list`, and the keys not already freed
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn blade_free_list(keys: *mut *mut c_char, len: usize) {
+    if keys.is_null() {
+        return;
+    }
+
+    let keys = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(keys, len)) };
+
+    for key in keys {
+        drop(unsafe { CString::from_raw(key) });
+    }
+}
+
+/// The message of the last error on this thread, or null if there hasn't been one.
+/// It's valid until the next error on this thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn blade_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
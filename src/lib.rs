@@ -0,0 +1,391 @@
+//! blade's storage: the database's schema, and the [`Backend`] trait that
+//! the basic operations on keys go through
+
+use rusqlite::{Connection, OptionalExtension, params};
+use tracing::debug;
+
+pub mod ffi;
+#[cfg(feature = "libsql")]
+pub mod remote;
+
+/// Params are namespace, key, value, and an optional ttl in seconds.
+/// Without a ttl, new keys get their namespace's default ttl, if it has one,
+/// and existing keys keep their expiry
+pub const SET_QUERY: &str = "
+    insert into entries (namespace, key, value, expires_at)
+    values (
+        ?1,
+        ?2,
+        ?3,
+        strftime(
+            '%Y-%m-%d %H:%M:%f',
+            'NOW',
+            '+' || coalesce(
+                ?4,
+                (select default_ttl_seconds from namespaces where namespace = ?1)
+            ) || ' seconds'
+        )
+    )
+    on conflict do update
+    set value = excluded.value,
+    expires_at = case
+        when ?4 is not null or entries.expires_at <= strftime('%Y-%m-%d %H:%M:%f', 'NOW')
+        then excluded.expires_at
+        else entries.expires_at
+    end
+    where namespace = excluded.namespace
+    and key = excluded.key;
+    ";
+
+/// Schema migrations, run in order.
+/// `pragma user_version` records how many have been applied,
+/// so only ever append to this list
+pub const MIGRATIONS: &[&str] = &[
+    "
+    create table if not exists entries (
+        namespace text not null,
+        key text not null,
+        value blob not null,
+        inserted_at datetime not null default(strftime('%Y-%m-%d %H:%M:%f', 'NOW')),
+        updated_at datetime not null default(strftime('%Y-%m-%d %H:%M:%f', 'NOW')),
+        primary key (namespace, key)
+    ) without rowid;
+
+    create trigger if not exists entries_updated_at
+    after update on entries for each row
+    begin
+        update entries
+        set updated_at = current_timestamp
+        where namespace = old.namespace
+        and key = old.key;
+    end;
+    ",
+    // recording reads must not count as updates
+    "
+    alter table entries add column last_accessed_at datetime;
+
+    drop trigger entries_updated_at;
+
+    create trigger entries_updated_at
+    after update of namespace, key, value, inserted_at, updated_at on entries for each row
+    begin
+        update entries
+        set updated_at = current_timestamp
+        where namespace = old.namespace
+        and key = old.key;
+    end;
+    ",
+    "
+    create table namespaces (
+        namespace text not null primary key,
+        description text,
+        created_at datetime not null default(strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+    ) without rowid;
+    ",
+    "
+    alter table entries add column expires_at datetime;
+
+    alter table namespaces add column default_ttl_seconds integer;
+    ",
+    "
+    create table aliases (
+        namespace text not null,
+        alias text not null,
+        target_namespace text not null,
+        target_key text not null,
+        primary key (namespace, alias)
+    ) without rowid;
+    ",
+    "
+    alter table entries add column content_type text;
+
+    alter table entries add column source_url text;
+
+    alter table entries add column source_headers text;
+    ",
+    "
+    create table schemas (
+        namespace text not null,
+        prefix text not null,
+        schema text not null,
+        created_at datetime not null default(strftime('%Y-%m-%d %H:%M:%f', 'NOW')),
+        primary key (namespace, prefix)
+    ) without rowid;
+    ",
+    "
+    alter table entries add column value_type text;
+    ",
+    "
+    create table json_indexes (
+        id integer primary key,
+        namespace text not null,
+        path text not null,
+        unique (namespace, path)
+    );
+    ",
+    "
+    create index entries_expires_at on entries (expires_at) where expires_at is not null;
+    ",
+    "
+    create table attachments (
+        namespace text not null primary key,
+        db_location text not null,
+        created_at datetime not null default(strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+    ) without rowid;
+    ",
+    "
+    create index entries_namespace_inserted_at on entries (namespace, inserted_at);
+    create index entries_updated_at on entries (updated_at);
+    ",
+    "
+    create index entries_namespace_key_expires_at on entries (namespace, key, expires_at);
+    ",
+    // `current_timestamp` has no fractional seconds, so times written by the trigger
+    // sorted before times written in the same second with `strftime`.
+    // the trigger is dropped first so that fixing `updated_at` doesn't fire it
+    "
+    drop trigger entries_updated_at;
+
+    update entries
+    set inserted_at = strftime('%Y-%m-%d %H:%M:%f', inserted_at)
+    where inserted_at != strftime('%Y-%m-%d %H:%M:%f', inserted_at);
+
+    update entries
+    set updated_at = strftime('%Y-%m-%d %H:%M:%f', updated_at)
+    where updated_at != strftime('%Y-%m-%d %H:%M:%f', updated_at);
+
+    update entries
+    set last_accessed_at = strftime('%Y-%m-%d %H:%M:%f', last_accessed_at)
+    where last_accessed_at != strftime('%Y-%m-%d %H:%M:%f', last_accessed_at);
+
+    update entries
+    set expires_at = strftime('%Y-%m-%d %H:%M:%f', expires_at)
+    where expires_at != strftime('%Y-%m-%d %H:%M:%f', expires_at);
+
+    create trigger entries_updated_at
+    after update of namespace, key, value, inserted_at, updated_at on entries for each row
+    begin
+        update entries
+        set updated_at = strftime('%Y-%m-%d %H:%M:%f', 'NOW')
+        where namespace = old.namespace
+        and key = old.key;
+    end;
+    ",
+    "
+    alter table entries add column note text;
+    ",
+    // a deleted version has no value, and a rollback records the version it restored.
+    // renaming a key takes its history with it. Keys set before this migration get
+    // their first version when they're next changed, rather than all being copied here
+    "
+    create table entry_history (
+        namespace text not null,
+        key text not null,
+        version integer not null,
+        value blob,
+        recorded_at datetime not null default(strftime('%Y-%m-%d %H:%M:%f', 'NOW')),
+        restored_version integer,
+        primary key (namespace, key, version)
+    ) without rowid;
+
+    create trigger entries_history_insert
+    after insert on entries for each row
+    begin
+        insert into entry_history (namespace, key, version, value)
+        select new.namespace, new.key, coalesce(max(version), 0) + 1, new.value
+        from entry_history
+        where namespace = new.namespace
+        and key = new.key;
+    end;
+
+    create trigger entries_history_update
+    after update of value on entries for each row
+    begin
+        insert into entry_history (namespace, key, version, value, recorded_at)
+        select old.namespace, old.key, 1, old.value, old.updated_at
+        where not exists (
+            select 1
+            from entry_history
+            where namespace = old.namespace
+            and key = old.key
+        );
+
+        insert into entry_history (namespace, key, version, value)
+        select new.namespace, new.key, coalesce(max(version), 0) + 1, new.value
+        from entry_history
+        where namespace = new.namespace
+        and key = new.key;
+    end;
+
+    create trigger entries_history_delete
+    after delete on entries for each row
+    begin
+        insert into entry_history (namespace, key, version, value, recorded_at)
+        select old.namespace, old.key, 1, old.value, old.updated_at
+        where not exists (
+            select 1
+            from entry_history
+            where namespace = old.namespace
+            and key = old.key
+        );
+
+        insert into entry_history (namespace, key, version, value)
+        select old.namespace, old.key, coalesce(max(version), 0) + 1, null
+        from entry_history
+        where namespace = old.namespace
+        and key = old.key;
+    end;
+
+    create trigger entries_history_rename
+    after update of namespace, key on entries for each row
+    when new.namespace != old.namespace or new.key != old.key
+    begin
+        delete from entry_history
+        where namespace = new.namespace
+        and key = new.key;
+
+        update entry_history
+        set namespace = new.namespace,
+        key = new.key
+        where namespace = old.namespace
+        and key = old.key;
+    end;
+    ",
+];
+
+pub fn migrate_db(mut conn: Connection) -> anyhow::Result<Connection> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    // don't take the write lock unless there is something to do
+    if version as usize >= MIGRATIONS.len() {
+        return Ok(conn);
+    }
+
+    let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+
+    let version: i64 = tx.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let version = i as i64 + 1;
+        debug!(version, "running migration");
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", version)?;
+    }
+
+    tx.commit()?;
+
+    Ok(conn)
+}
+
+/// A `glob` pattern matching keys that start with `prefix`.
+/// `glob` is case sensitive and can use the primary key, unlike `like`
+fn prefix_glob(prefix: &str) -> String {
+    format!(
+        "{}*",
+        prefix
+            .replace('[', "[[]")
+            .replace('*', "[*]")
+            .replace('?', "[?]")
+    )
+}
+
+/// Where keys are stored. Keys are in namespaces, and expired keys are as good as gone.
+/// The CLI reads and deletes local keys through this, and `blade mount` lists them,
+/// but `set` and `list` need quotas, types, and filters that it doesn't have,
+/// so they only go through it for a remote database
+pub trait Backend {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// The value of a key, if it exists
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Self::Error>;
+
+    /// Sets a key. Existing keys keep their expiry
+    fn set(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Self::Error>;
+
+    /// Deletes a key, returning whether it existed
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool, Self::Error>;
+
+    /// The keys in a namespace, in order
+    fn list(&self, namespace: &str) -> Result<Vec<String>, Self::Error>;
+
+    /// The keys in a namespace starting with `prefix`, and their values, in order
+    fn scan(&self, namespace: &str, prefix: &str) -> Result<Vec<(String, Vec<u8>)>, Self::Error>;
+}
+
+impl Backend for Connection {
+    type Error = rusqlite::Error;
+
+    fn get(&self, namespace: &str, key: &str) -> rusqlite::Result<Option<Vec<u8>>> {
+        let mut q = self.prepare_cached(
+            "
+        select
+            value
+        from entries
+        where namespace = ?
+        and key = ?
+        and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        limit 1
+        ",
+        )?;
+
+        q.query_one([namespace, key], |row| row.get(0)).optional()
+    }
+
+    fn set(&self, namespace: &str, key: &str, value: &[u8]) -> rusqlite::Result<()> {
+        self.prepare_cached(SET_QUERY)?
+            .execute(params![namespace, key, value, None::<f64>])?;
+
+        Ok(())
+    }
+
+    fn delete(&self, namespace: &str, key: &str) -> rusqlite::Result<bool> {
+        // an expired row is deleted too, but it didn't exist as far as `get` is concerned
+        let existed = self
+            .prepare_cached(
+                "
+            delete from entries
+            where namespace = ?
+            and key = ?
+            returning coalesce(expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'), true)
+            ",
+            )?
+            .query_one([namespace, key], |row| row.get(0))
+            .optional()?;
+
+        Ok(existed.unwrap_or(false))
+    }
+
+    fn list(&self, namespace: &str) -> rusqlite::Result<Vec<String>> {
+        self.prepare_cached(
+            "
+        select
+            key
+        from entries
+        where namespace = ?
+        and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        order by key
+        ",
+        )?
+        .query_map([namespace], |row| row.get(0))?
+        .collect()
+    }
+
+    fn scan(&self, namespace: &str, prefix: &str) -> rusqlite::Result<Vec<(String, Vec<u8>)>> {
+        self.prepare_cached(
+            "
+        select
+            key,
+            value
+        from entries
+        where namespace = ?
+        and key glob ?
+        and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        order by key
+        ",
+        )?
+        .query_map([namespace, &prefix_glob(prefix)], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect()
+    }
+}
@@ -1,13 +1,53 @@
-use anyhow::anyhow;
-use clap::{Parser, Subcommand};
-use rusqlite::{Connection, OptionalExtension, params};
+use age::secrecy::SecretString;
+use anyhow::{Context, anyhow};
+use base64::prelude::*;
+use blade::{Backend, MIGRATIONS, SET_QUERY, migrate_db};
+use clap::{Parser, Subcommand, ValueEnum};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use hmac::{Hmac, Mac};
+use indicatif::{ProgressBar, ProgressStyle};
+use rusqlite::trace::{TraceEvent, TraceEventCodes};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::fmt::Display;
-use std::io::{IsTerminal, Read, Write};
+use std::io::{BufRead, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{Level, debug, warn};
+
+#[cfg(feature = "fuser")]
+mod mount;
 
 const DEFAULT_NAMESPACE: &str = "default";
 
+/// `default_namespace` from the config file, set once it's loaded
+static CONFIGURED_DEFAULT_NAMESPACE: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+/// The namespace of keys that don't name one
+fn default_namespace() -> &'static str {
+    CONFIGURED_DEFAULT_NAMESPACE
+        .get()
+        .map_or(DEFAULT_NAMESPACE, String::as_str)
+}
+
+fn check_default_namespace(namespace: &str) -> anyhow::Result<()> {
+    if namespace.trim().is_empty() || namespace.contains('@') {
+        return Err(BladeError::Usage(format!(
+            "default_namespace must be a namespace, not: {:?}",
+            namespace
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
 #[derive(Parser)]
 struct Options {
     /// Optional. Setting this environment variable overrides
@@ -16,6 +56,32 @@ struct Options {
     /// ~/.config/blade/config.toml
     #[arg(env)]
     db_location: Option<PathBuf>,
+    /// Log timings to stderr. `-v` for debug, `-vv` for trace.
+    /// The `BLADE_LOG` environment variable takes precedence, like `BLADE_LOG=blade=debug`
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+    #[arg(long, value_enum, env = "BLADE_LOG_FORMAT", default_value_t = OutputFormat::Text, global = true)]
+    log_format: OutputFormat,
+    /// How to print errors to stderr. `json` prints one object with the error's kind, exit code, message, and causes
+    #[arg(long, value_enum, env = "BLADE_ERRORS", default_value_t = OutputFormat::Text, global = true)]
+    errors: OutputFormat,
+    /// Don't run hooks or send webhooks for this command's changes
+    #[arg(long, global = true)]
+    no_hooks: bool,
+    /// Fail with exit code 10 if the command takes longer than this, like `2s`,
+    /// including waiting for a busy database. Changes not yet committed are rolled back,
+    /// and files being written, like backups, are removed
+    #[arg(long, value_parser = parse_duration, global = true)]
+    timeout: Option<std::time::Duration>,
+    /// How to print times, like `query --select created`
+    #[arg(long, value_enum, env = "BLADE_TIME_FORMAT", default_value_t = TimeFormat::Iso8601, global = true)]
+    time_format: TimeFormat,
+    /// Print `iso8601` times in UTC, which is the default
+    #[arg(long, overrides_with = "local", global = true)]
+    utc: bool,
+    /// Print `iso8601` times in the local timezone, with its offset
+    #[arg(long, overrides_with = "utc", global = true)]
+    local: bool,
     #[command(subcommand)]
     command: Command,
 }
@@ -23,349 +89,10715 @@ struct Options {
 #[derive(Subcommand, Clone)]
 enum Command {
     /// Get a key. `key[@namespace]`
-    Get { namespaced_key: String },
+    Get {
+        #[arg(required_unless_present = "stdin", conflicts_with = "stdin")]
+        namespaced_key: Option<String>,
+        /// Read keys from stdin, one per line, and print each of their values
+        /// followed by `--delimiter`. Keys that don't exist are reported once the rest have been printed
+        #[arg(long, conflicts_with_all = ["render", "qr"])]
+        stdin: bool,
+        /// With `--stdin`, keys are delimited by NUL rather than newlines
+        #[arg(short = '0', long, requires = "stdin")]
+        null: bool,
+        /// With `--stdin`, written after each value. `\n`, `\t`, `\0`, and `\\` are unescaped
+        #[arg(long, default_value = "\\n", requires = "stdin")]
+        delimiter: String,
+        /// Print this instead of failing if the key doesn't exist
+        #[arg(long, conflicts_with = "default_file")]
+        default: Option<String>,
+        /// Print the contents of this file instead of failing if the key doesn't exist
+        #[arg(long)]
+        default_file: Option<PathBuf>,
+        /// Fail unless the value can be read as this type, and print it in its canonical form,
+        /// like `true` for a `bool` set as `yes`
+        #[arg(long = "type", value_enum)]
+        value_type: Option<ValueType>,
+        /// When stdout is a terminal, pretty-print JSON, TOML, and markdown values
+        /// with colors, wrapping text to fit. Values are written as-is otherwise
+        #[arg(long)]
+        render: bool,
+        /// Print the value as a QR code, to scan with a phone
+        #[arg(long, conflicts_with = "render")]
+        qr: bool,
+        /// Print the value base64-encoded
+        #[arg(long, conflicts_with_all = ["render", "hex"])]
+        base64: bool,
+        /// Print the value hex-encoded
+        #[arg(long, conflicts_with = "render")]
+        hex: bool,
+        /// Print the value as a shell assignment to this variable, like `VAR='value'`, for `eval`.
+        /// With `--stdin`, this is a prefix for variables named after each key, like `DB_HOST`
+        #[arg(long, value_name = "VAR", conflicts_with_all = ["render", "qr"])]
+        shell: Option<String>,
+        /// Print binary values to a terminal, rather than failing
+        #[arg(long)]
+        binary_ok: bool,
+        /// Write a newline after the value even when stdout isn't a terminal,
+        /// where the value is otherwise written exactly as it was set
+        #[arg(long, conflicts_with = "stdin")]
+        newline: bool,
+        /// Print the key's size, times, type, and note instead of its value, one per line
+        #[arg(long, conflicts_with = "stdin")]
+        meta: bool,
+    },
+    /// Write the raw values of keys to stdout, in order. `key[@namespace]`.
+    /// Keys that don't exist are reported once the rest have been written
+    Cat {
+        #[arg(required = true)]
+        namespaced_keys: Vec<String>,
+        /// Written between values. `\n`, `\t`, `\0`, and `\\` are unescaped
+        #[arg(long, default_value = "")]
+        delimiter: String,
+    },
     /// Set a key. `key[@namespace]`.
     /// Value can be either a string, or a file read from stdin,
-    /// like `blade set key < file.txt`
+    /// like `blade set key < file.txt`.
+    /// Several keys can be set in one transaction with `key=value` arguments,
+    /// like `blade set host=localhost port=5432`
     Set {
         namespaced_key: String,
+        #[arg(conflicts_with_all = ["from_url", "refresh"])]
         value: Option<String>,
+        /// More `key=value` arguments
+        #[arg(value_name = "KEY=VALUE", conflicts_with_all = ["from_url", "refresh"])]
+        more: Vec<String>,
+        /// Read arguments as `key=value`, setting keys in this namespace
+        /// unless they name another
+        #[arg(long, conflicts_with_all = ["from_url", "refresh"])]
+        namespace: Option<String>,
+        /// Expire the key after this long, like `24h` or `7d`
+        #[arg(long, value_parser = parse_ttl)]
+        ttl: Option<std::time::Duration>,
+        /// Download the value from this URL, storing its content type too
+        #[arg(long, conflicts_with = "refresh")]
+        from_url: Option<String>,
+        /// A header to send with `--from-url` or `--refresh`, like `Authorization: Bearer abc`
+        #[arg(long)]
+        header: Vec<String>,
+        /// Download the value again from the URL it was set from.
+        /// Uses the same headers, unless `--header` is given. Credentials, like `Authorization`,
+        /// aren't stored, and are sent from `BLADE_HEADER_<NAME>`, like `BLADE_HEADER_AUTHORIZATION`
+        #[arg(long)]
+        refresh: bool,
+        /// Require this value, and every later value of the key, to be of this type
+        #[arg(long = "type", value_enum)]
+        value_type: Option<ValueType>,
+        /// Decode the value from base64 before storing it
+        #[arg(long, conflicts_with_all = ["from_url", "refresh", "hex"])]
+        base64: bool,
+        /// Decode the value from hex before storing it
+        #[arg(long, conflicts_with_all = ["from_url", "refresh"])]
+        hex: bool,
+        /// Also write stdin to stdout as it's read, to set a key from the middle of a pipeline
+        #[arg(long, conflicts_with_all = ["value", "more", "namespace", "from_url", "refresh"])]
+        tee: bool,
     },
-    /// Delete a key. `key[@namespace]`
-    Delete { namespaced_key: String },
-    /// List all keys. Optionally with namespace and delimiter (default: `\t`)
+    /// Set a key to the value of an environment variable. `key[@namespace]`
+    SetFromEnv {
+        namespaced_key: String,
+        variable: String,
+        /// Set the key in this namespace, unless it names another
+        #[arg(long)]
+        namespace: Option<String>,
+        /// Expire the key after this long, like `24h` or `7d`
+        #[arg(long, value_parser = parse_ttl)]
+        ttl: Option<std::time::Duration>,
+    },
+    /// Set a key for each environment variable starting with a prefix, in one transaction,
+    /// the inverse of `direnv` and `get --shell`. The prefix is removed and the rest lowercased,
+    /// so `MYAPP_DB_HOST` with `--prefix MYAPP_` is set as `db_host`
+    CaptureEnv {
+        #[arg(long)]
+        prefix: String,
+        /// (default: `default`)
+        #[arg(long)]
+        namespace: Option<String>,
+        /// Expire the keys after this long, like `24h` or `7d`
+        #[arg(long, value_parser = parse_ttl)]
+        ttl: Option<std::time::Duration>,
+    },
+    /// Append values to a key holding a JSON array, like a queue or a log, creating it if needed.
+    /// `key[@namespace]`. Prints the array's new length
+    Push {
+        namespaced_key: String,
+        #[arg(required = true)]
+        values: Vec<String>,
+        /// Append the values as JSON, like `3` or `{"a":1}`, rather than as strings
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print a range of the elements of a key holding a JSON array, one per line.
+    /// Both ends are included, and negative indexes count from the end, so `0 -1` is everything.
+    /// Strings are printed as-is, and other elements as JSON
+    Lrange {
+        namespaced_key: String,
+        #[arg(allow_negative_numbers = true, default_value_t = 0)]
+        start: i64,
+        #[arg(allow_negative_numbers = true, default_value_t = -1)]
+        stop: i64,
+    },
+    /// Delete keys, in one transaction. `key[@namespace]`.
+    /// With more than one key, reports how many existed.
+    /// Their values are kept in their history, unless `--purge-history` is given
+    Delete {
+        #[arg(required_unless_present = "interactive")]
+        namespaced_keys: Vec<String>,
+        /// Pick keys to delete from a list, where the argument is a namespace (default: `default`).
+        /// Type to filter, tab to mark keys, and enter to delete them, after confirming
+        #[arg(long)]
+        interactive: bool,
+        /// Delete the keys' history too, so none of their values are kept
+        #[arg(long)]
+        purge_history: bool,
+    },
+    /// Fuzzy-find a key in a namespace (default: `default`), with a preview of its value,
+    /// and print its value or do something else with it.
+    /// `*` in the namespace matches anything, like `work.*`
+    Pick {
+        namespace: Option<String>,
+        #[arg(long, value_enum, default_value_t = PickAction::Print)]
+        then: PickAction,
+    },
+    /// Print the keys, as `key@namespace`, whose values contain a string.
+    /// Only text values are searched, unless `--binary` is given
+    #[command(visible_alias = "search")]
+    FindValue {
+        #[arg(required_unless_present = "regex", conflicts_with = "regex")]
+        substring: Option<String>,
+        /// Match values against this regular expression instead
+        #[arg(long)]
+        regex: Option<String>,
+        /// Only search this namespace. `*` matches anything, like `work.*`
+        #[arg(long, default_value = "*")]
+        namespace: String,
+        /// Search the bytes of values that aren't UTF-8 too
+        #[arg(long, conflicts_with = "notes")]
+        binary: bool,
+        /// Search keys' notes instead of their values
+        #[arg(long)]
+        notes: bool,
+    },
+    /// Print the keys, as `key@namespace`, whose values are exactly a given value
+    #[command(group(
+        clap::ArgGroup::new("value")
+            .args(["hash", "value_file"])
+            .required(true)
+    ))]
+    Which {
+        /// The SHA-256 of the value, in hex
+        #[arg(long)]
+        hash: Option<String>,
+        /// A file containing the value
+        #[arg(long)]
+        value_file: Option<PathBuf>,
+        /// Only search this namespace. `*` matches anything, like `work.*`
+        #[arg(long, default_value = "*")]
+        namespace: String,
+    },
+    /// Rename every key in a namespace matching a glob, all at once.
+    /// Nothing is renamed if a new name is taken, or two keys would get the same one
+    Rename {
+        /// Keys to rename, where `*` matches anything, like `old-prefix/*`
+        #[arg(long)]
+        glob: String,
+        /// The new names. `*`s are filled in with what the glob's `*`s matched, in order.
+        /// Without any, what they matched is appended, so `new-prefix/` replaces the prefix
+        #[arg(long)]
+        replace: String,
+        #[arg(long)]
+        namespace: Option<String>,
+        /// Print each key and its new name, rather than renaming
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Move every key in a namespace matching a glob to another namespace, all at once
+    Move {
+        /// Keys to move, where `*` matches anything, like `proj-*`
+        #[arg(long)]
+        glob: String,
+        #[arg(long)]
+        from: Option<String>,
+        #[arg(long)]
+        to: String,
+        /// What to do when a key already exists in `--to`
+        #[arg(long, value_enum, default_value_t = ConflictStrategy::Fail)]
+        on_conflict: ConflictStrategy,
+    },
+    /// Bump a key's `updated_at` without changing its value. `key[@namespace]`
+    Touch {
+        namespaced_key: String,
+        /// Also reset `inserted_at`, as if the key was just created
+        #[arg(long)]
+        created: bool,
+    },
+    /// Expire a key after this long, like `24h` or `7d`. `key[@namespace]`
+    Expire {
+        namespaced_key: String,
+        #[arg(value_parser = parse_ttl)]
+        ttl: std::time::Duration,
+    },
+    /// Print how long until a key expires, or `none`. `key[@namespace]`
+    Ttl { namespaced_key: String },
+    /// Stop a key from expiring. `key[@namespace]`
+    Persist { namespaced_key: String },
+    /// Mount the database as a filesystem until it's unmounted, with a directory for each
+    /// namespace, and a file for each key in it. Writing a file sets its key.
+    /// `/`s in keys are `%2F` in file names
+    #[cfg(feature = "fuser")]
+    Mount { mountpoint: PathBuf },
+    /// Print every version of a key, oldest first, as its version, when it was recorded,
+    /// and whether it was a `set`, a `delete`, or a `rollback to` an earlier version.
+    /// `key[@namespace]`. Deleted values are kept until `history purge`,
+    /// or `gc` with `[history]` retention in the config
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    History {
+        #[arg(required = true)]
+        namespaced_key: Option<String>,
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+    },
+    /// Set a key to the value it had at an earlier version, as a new version.
+    /// `key[@namespace]`
+    #[command(group(
+        clap::ArgGroup::new("version")
+            .args(["to_version", "to"])
+            .required(true)
+    ))]
+    Rollback {
+        namespaced_key: String,
+        /// The version to restore, as printed by `history`
+        #[arg(long)]
+        to_version: Option<i64>,
+        /// Restore the value the key had at this time, like `2024-05-01` or `2024-05-01 12:00:00`,
+        /// or this long ago, like `2d`
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Delete keys matching all of the given filters, in one transaction,
+    /// and print how many bytes of values were deleted.
+    /// The values stay in the keys' history until `gc` trims it
+    #[command(group(
+        clap::ArgGroup::new("filter")
+            .args(["not_used_in", "older_than", "larger_than"])
+            .required(true)
+            .multiple(true)
+    ))]
+    Prune {
+        /// Only prune keys in this namespace. `*` matches anything, like `work.*`
+        #[arg(long)]
+        namespace: Option<String>,
+        /// Keys that have not been read or written in this long, like `90d` or `12h`.
+        /// Reads are only recorded with `track_last_accessed` in the config
+        #[arg(long, value_parser = parse_duration)]
+        not_used_in: Option<std::time::Duration>,
+        /// Keys that have not been written in this long, like `180d`
+        #[arg(long, value_parser = parse_duration)]
+        older_than: Option<std::time::Duration>,
+        /// Keys with values larger than this, like `10mb`
+        #[arg(long, value_parser = parse_size)]
+        larger_than: Option<usize>,
+        /// Only print how many keys would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Delete expired keys, which are otherwise kept until they're set again,
+    /// and trim history to the `[history]` retention in the config.
+    /// See `expiry_sweep_limit` in the config to delete expired keys as you go
+    Gc {
+        /// Delete at most this many, oldest first in each database
+        #[arg(long)]
+        max: Option<u64>,
+    },
+    /// List all keys. Optionally with namespace and delimiter (default: `\t`).
+    /// `*` in the namespace matches anything, like `work.*`,
+    /// and keys are printed as `key@namespace`
     List {
         namespace: Option<String>,
         #[arg(default_value = "\t")]
         delimiter: String,
+        /// Only list keys that have been read, most recently read first.
+        /// Requires `track_last_accessed` in the config
+        #[arg(long)]
+        recently_used: bool,
+        /// Only list keys starting with this, like `deploy/`
+        #[arg(long, default_value = "")]
+        prefix: String,
+        /// Only list JSON values where a path compares to a value, like `$.env = prod`.
+        /// Operators are `=`, `!=`, `<`, `<=`, `>`, and `>=`. Can be given more than once.
+        /// See `index create` to make these fast
+        #[arg(long = "where", value_parser = parse_where)]
+        wheres: Vec<Where>,
+        /// Only list keys updated within this long, like `7d` or `6mo`
+        #[arg(long, value_parser = parse_duration)]
+        updated_within: Option<std::time::Duration>,
+        /// Only print keys, as `key@namespace`, like for `get --stdin`
+        #[arg(long)]
+        keys_only: bool,
+        /// Also print each key's note, after another delimiter
+        #[arg(long)]
+        long: bool,
+        /// List at most this many entries, in key order. If there are more,
+        /// a token for `--cursor` to list the next ones is printed on stderr
+        #[arg(long, conflicts_with = "recently_used")]
+        limit: Option<u64>,
+        /// Continue listing after where a `--limit` listing stopped
+        #[arg(long, conflicts_with = "recently_used")]
+        cursor: Option<String>,
+    },
+    /// Print only key names, in key order, like for shell completion or `fzf`.
+    /// `*` in the namespace matches anything, and keys are then printed as `key@namespace`
+    Keys {
+        namespace: Option<String>,
+        /// Only print keys starting with this
+        #[arg(long, default_value = "")]
+        prefix: String,
+    },
+    /// Print a key chosen uniformly at random from a namespace
+    Random {
+        namespace: Option<String>,
+        /// Print the key's value instead
+        #[arg(long)]
+        value: bool,
+    },
+    /// Show keys as a tree, treating `/` in keys as hierarchy.
+    /// Optionally with namespace and key prefix
+    Tree {
+        namespace: Option<String>,
+        #[arg(default_value = "")]
+        prefix: String,
     },
     /// List all namespaces
-    ListNamespaces,
+    ListNamespaces {
+        /// Show namespaces as a tree, treating `.` as hierarchy
+        #[arg(long, conflicts_with = "long")]
+        tree: bool,
+        /// Also show each namespace's number of keys, creation time, and description,
+        /// delimited by `\t`
+        #[arg(long)]
+        long: bool,
+    },
+    /// Print a namespace's keys as `export` lines for an `.envrc`, like `eval "$(blade direnv work)"`,
+    /// with variable names like `get --shell`. Under direnv, the database is watched for changes too
+    Direnv {
+        #[arg(required_unless_present = "stdlib")]
+        namespace: Option<String>,
+        /// Print a `use_blade` function for `~/.config/direnv/direnvrc` instead,
+        /// so that `use blade <namespace>` in an `.envrc` does the same
+        #[arg(long, conflicts_with = "namespace")]
+        stdlib: bool,
+    },
+    /// Manage namespaces
+    Namespace {
+        #[command(subcommand)]
+        command: NamespaceCommand,
+    },
+    /// Keep a namespace in another database file, like `blade attach /mnt/big/media.db as media`.
+    /// Commands on keys in the namespace open that file instead.
+    /// Commands that span namespaces, like `list 'work.*'`, are refused if they'd include it,
+    /// except `import`, `merge`, `get --stdin`, and `gc`, which use each namespace's file.
+    /// A namespace with keys or aliases can't be attached until they're moved.
+    /// Lists attached namespaces without arguments
+    #[command(override_usage = "blade attach [<DB_LOCATION> as <NAMESPACE>]")]
+    Attach {
+        /// Created if it doesn't exist
+        #[arg(requires = "namespace")]
+        db_location: Option<PathBuf>,
+        #[arg(value_parser = ["as"], hide = true)]
+        r#as: Option<String>,
+        #[arg(requires = "as")]
+        namespace: Option<String>,
+    },
+    /// Stop keeping a namespace in another database file.
+    /// The file and its keys are left alone
+    Detach { namespace: String },
+    /// Manage notes on keys, for remembering what they hold
+    Note {
+        #[command(subcommand)]
+        command: NoteCommand,
+    },
+    /// Manage aliases, which `get` resolves to the keys they point to, and `set` writes through
+    Alias {
+        #[command(subcommand)]
+        command: AliasCommand,
+    },
+    /// Index JSON paths in values, for `list --where`
+    Index {
+        #[command(subcommand)]
+        command: IndexCommand,
+    },
+    /// Validate JSON values set in a namespace against a JSON Schema
+    Schema {
+        #[command(subcommand)]
+        command: SchemaCommand,
+    },
+    /// List groups of keys with byte-identical values.
+    /// Optionally with namespace and delimiter (default: `\t`)
+    Dupes {
+        namespace: Option<String>,
+        #[arg(default_value = "\t")]
+        delimiter: String,
+    },
+    /// Show a unified diff between the values of two keys. `key[@namespace]`
+    Diff {
+        namespaced_key1: String,
+        namespaced_key2: String,
+    },
+    /// Print a template with `{{ key[@namespace] }}` placeholders replaced by their values.
+    /// The template is read from a file, a key, or stdin
+    Render {
+        template_file: Option<PathBuf>,
+        /// Read the template from this key. `key[@namespace]`
+        #[arg(long, conflicts_with = "template_file")]
+        key: Option<String>,
+    },
+    /// Show keys added, removed, or changed in another blade database,
+    /// relative to this one
+    DbDiff {
+        other_db_location: PathBuf,
+        /// Only compare keys in this namespace. `*` matches anything, like `work.*`
+        #[arg(long)]
+        namespace: Option<String>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Merge entries from another blade database into this one.
+    /// Keys that exist in both databases with different values are conflicts,
+    /// resolved by `--strategy`
+    Merge {
+        other_db_location: PathBuf,
+        #[arg(long, value_enum, default_value_t = MergeStrategy::Newest)]
+        strategy: MergeStrategy,
+    },
+    /// Write a consistent snapshot of the database to a file, or off the machine.
+    /// `--to` is a path, or a `file://`, `http(s)://`, or `s3://bucket/key` URL,
+    /// and must not already exist. Remote backups are streamed in one `PUT` with
+    /// `If-None-Match: *`, so S3 backups can be up to 5GB
+    Backup {
+        #[arg(long)]
+        to: String,
+    },
+    /// Replace the database with a backup made by `blade backup`, including expiry, notes,
+    /// aliases, schemas, and namespace settings.
+    /// `--from` is anything `backup --to` takes
+    Restore {
+        #[arg(long)]
+        from: String,
+    },
+    /// Export entries as JSON lines to stdout, or to a compressed archive with `--archive`
+    Export {
+        /// Only export keys in this namespace. `*` matches anything, like `work.*`
+        #[arg(long)]
+        namespace: Option<String>,
+        /// Only export entries updated at or after this time, like `2024-01-01` or `2024-01-01 12:00:00`,
+        /// or within this long, like `2w`
+        #[arg(long)]
+        since: Option<String>,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Jsonl)]
+        format: ExportFormat,
+        /// Write a zstd-compressed archive to this path instead of stdout
+        #[arg(long)]
+        archive: Option<PathBuf>,
+        /// Encrypt the archive with this passphrase
+        #[arg(
+            long,
+            env = "BLADE_ARCHIVE_PASSPHRASE",
+            hide_env_values = true,
+            requires = "archive"
+        )]
+        passphrase: Option<String>,
+        /// Sign the archive with the secret key at this path, writing the signature to `<archive>.sig`
+        #[arg(long, requires = "archive")]
+        sign: Option<PathBuf>,
+        /// Write a browsable, read-only site to this directory instead of stdout,
+        /// with an index page and one page per key. Text values are rendered as markdown
+        #[arg(long, conflicts_with_all = ["archive", "format"])]
+        html: Option<PathBuf>,
+        /// Copy entries to Consul instead of stdout, like `http://localhost:8500/v1/kv/myapp`.
+        /// Keys are written under `<namespace>/<key>`, or `<key>` when exporting one namespace
+        #[arg(long, value_name = "URL", conflicts_with_all = ["archive", "format", "html", "etcd"])]
+        consul: Option<String>,
+        /// Copy entries to etcd instead of stdout, like `http://localhost:2379/myapp`,
+        /// with keys written like `--consul`
+        #[arg(long, value_name = "URL", conflicts_with_all = ["archive", "format", "html"])]
+        etcd: Option<String>,
+        /// Print a Kubernetes Secret with this name instead, holding the keys in `--namespace`
+        #[arg(
+            long,
+            value_name = "NAME",
+            requires = "namespace",
+            group = "k8s",
+            conflicts_with_all = ["archive", "format", "html", "consul", "etcd"]
+        )]
+        k8s_secret: Option<String>,
+        /// Print a Kubernetes ConfigMap with this name instead, like `--k8s-secret`
+        #[arg(
+            long,
+            value_name = "NAME",
+            requires = "namespace",
+            group = "k8s",
+            conflicts_with_all = ["archive", "format", "html", "consul", "etcd"]
+        )]
+        k8s_config_map: Option<String>,
+        /// The Kubernetes namespace to put the Secret or ConfigMap in
+        #[arg(long, value_name = "NAMESPACE", requires = "k8s")]
+        k8s_namespace: Option<String>,
+        /// Write each value to its own file in this directory instead of stdout,
+        /// as `<namespace>/<key>`, listed in `manifest.jsonl`. Made to be committed to git:
+        /// the output only changes when values do, and files of deleted keys are removed.
+        /// With `--namespace` or `--since`, the rest of an earlier export is kept
+        #[arg(
+            long,
+            value_name = "DIR",
+            conflicts_with_all = ["archive", "format", "html", "consul", "etcd", "k8s"]
+        )]
+        git_dir: Option<PathBuf>,
+    },
+    /// Import entries from stdin, or from an archive made by `blade export --archive`.
+    /// Existing keys are overwritten
+    Import {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Jsonl)]
+        format: ExportFormat,
+        #[command(flatten)]
+        csv_options: CsvImportOptions,
+        /// What to do when an imported key already exists with a different value
+        #[arg(long, value_enum, default_value_t = ConflictStrategy::Overwrite)]
+        on_conflict: ConflictStrategy,
+        /// Report what would be imported without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Read a compressed archive from this path instead of stdin
+        #[arg(long)]
+        archive: Option<PathBuf>,
+        /// Passphrase for an encrypted archive
+        #[arg(
+            long,
+            env = "BLADE_ARCHIVE_PASSPHRASE",
+            hide_env_values = true,
+            requires = "archive"
+        )]
+        passphrase: Option<String>,
+        /// Refuse to import unless `<archive>.sig` is a valid signature from this public key
+        #[arg(long, requires = "archive")]
+        verify: Option<String>,
+        /// Copy the string keys from a Redis database, like `redis://localhost/0`,
+        /// instead of reading stdin. Keys keep their TTLs
+        #[arg(long, value_name = "URL", conflicts_with = "archive")]
+        redis: Option<String>,
+        /// Only copy the Redis keys matching this pattern, like `prefix:*`
+        #[arg(long = "match", value_name = "PATTERN", requires = "redis")]
+        redis_match: Option<String>,
+        /// Copy the keys under a Consul prefix, like `http://localhost:8500/v1/kv/myapp`,
+        /// instead of reading stdin. The first part of each key's path is its namespace,
+        /// unless `--namespace` is given
+        #[arg(long, value_name = "URL", conflicts_with_all = ["archive", "redis", "etcd"])]
+        consul: Option<String>,
+        /// Copy the keys under an etcd prefix, like `http://localhost:2379/myapp`,
+        /// instead of reading stdin, like `--consul`
+        #[arg(long, value_name = "URL", conflicts_with_all = ["archive", "redis"])]
+        etcd: Option<String>,
+        /// Read a directory made by `blade export --git-dir` instead of stdin
+        #[arg(
+            long,
+            value_name = "DIR",
+            conflicts_with_all = ["archive", "redis", "consul", "etcd"]
+        )]
+        git_dir: Option<PathBuf>,
+    },
+    /// Generate a secret key for signing archives, writing it to the given path
+    /// and printing the public key
+    GenerateSigningKey { secret_key_path: PathBuf },
+    /// Measure set, get, and delete throughput and latency against a temporary database,
+    /// using the SQLite settings from the config file
+    Bench {
+        /// Number of operations to run for each of set, get, and delete
+        #[arg(long, default_value_t = 100_000)]
+        ops: usize,
+        /// Size of each value, like `512`, `1k`, or `1m`
+        #[arg(long, default_value = "1k", value_parser = parse_size)]
+        value_size: usize,
+        /// Number of threads, each with its own connection
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+    },
+    /// Find entries by their metadata, printing the selected fields separated by `--delimiter`.
+    /// A friendlier alternative to `sql`, like
+    /// `blade query --namespace work --where 'size > 1MB' --where 'updated > 7d ago'`
+    Query {
+        /// `*` matches anything, like `work.*`. All namespaces if not given
+        #[arg(long)]
+        namespace: Option<String>,
+        /// `<field> <operator> <value>`, where operators are `=`, `!=`, `<`, `<=`, `>`, `>=`,
+        /// and `~` for `*` patterns. Sizes can be like `1MB`, and times like `7d ago`.
+        /// Can be given more than once
+        #[arg(long = "where", value_parser = parse_query_where)]
+        wheres: Vec<QueryWhere>,
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_value = "namespace,key,size,updated"
+        )]
+        select: Vec<QueryField>,
+        #[arg(long, default_value = "\t")]
+        delimiter: String,
+    },
+    /// Run a SQL query against the database, printing rows with the given delimiter (default: `\t`).
+    /// Read-only unless `--write` is passed
+    Sql {
+        query: String,
+        #[arg(default_value = "\t")]
+        delimiter: String,
+        /// Allow the query to modify the database
+        #[arg(long)]
+        write: bool,
+    },
     /// Print the current config
     DumpConfig,
+    /// Write a config file, asking for the main settings, and create the database
+    Init {
+        /// Replace an existing config file, starting from its settings
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check the config file and database for common problems, and print how to fix them.
+    /// Exits with an error if any are found
+    Doctor,
 }
 
-#[derive(Serialize, Deserialize)]
+impl Command {
+    /// Whether this is a `set` of `key=value` arguments rather than of one key
+    fn sets_pairs(&self) -> bool {
+        match self {
+            Command::Set {
+                namespaced_key,
+                value,
+                more,
+                namespace,
+                ..
+            } => {
+                namespace.is_some()
+                    || !more.is_empty()
+                    || value
+                        .as_ref()
+                        .is_some_and(|value| namespaced_key.contains('=') && value.contains('='))
+            }
+            _ => false,
+        }
+    }
+
+    /// The namespaces of the keys this command reads or changes,
+    /// for commands that work on particular keys or a namespace
+    fn namespaces(&self) -> Vec<String> {
+        fn of(namespaced_key: &str) -> Option<String> {
+            split_maybe_qualified_key(namespaced_key)
+                .ok()
+                .map(|key| key.namespace.to_string())
+        }
+
+        fn or_default(namespace: Option<&String>) -> String {
+            namespace
+                .cloned()
+                .unwrap_or_else(|| default_namespace().to_string())
+        }
+
+        match self {
+            Command::Get {
+                namespaced_key: Some(namespaced_key),
+                ..
+            }
+            | Command::Touch { namespaced_key, .. }
+            | Command::Expire { namespaced_key, .. }
+            | Command::Ttl { namespaced_key }
+            | Command::Persist { namespaced_key }
+            | Command::History {
+                namespaced_key: Some(namespaced_key),
+                command: None,
+            }
+            | Command::History {
+                command: Some(HistoryCommand::Purge { namespaced_key }),
+                ..
+            }
+            | Command::Alias {
+                command:
+                    AliasCommand::Delete {
+                        namespaced_alias: namespaced_key,
+                    },
+            }
+            | Command::Rollback { namespaced_key, .. }
+            | Command::Push { namespaced_key, .. }
+            | Command::Lrange { namespaced_key, .. }
+            | Command::Note {
+                command:
+                    NoteCommand::Set { namespaced_key, .. }
+                    | NoteCommand::Get { namespaced_key }
+                    | NoteCommand::Delete { namespaced_key },
+            } => of(namespaced_key).into_iter().collect(),
+            Command::Cat {
+                namespaced_keys, ..
+            }
+            | Command::Delete {
+                namespaced_keys,
+                interactive: false,
+                ..
+            } => namespaced_keys.iter().filter_map(|key| of(key)).collect(),
+            Command::Delete {
+                namespaced_keys,
+                interactive: true,
+                ..
+            } => vec![or_default(namespaced_keys.first())],
+            Command::Set {
+                namespaced_key,
+                value,
+                more,
+                namespace,
+                ..
+            } if self.sets_pairs() => std::iter::once(namespaced_key)
+                .chain(value)
+                .chain(more)
+                .filter_map(|pair| {
+                    let (namespaced_key, _) = pair.split_once('=')?;
+                    if namespaced_key.contains('@') {
+                        of(namespaced_key)
+                    } else {
+                        Some(or_default(namespace.as_ref()))
+                    }
+                })
+                .collect(),
+            Command::Set { namespaced_key, .. } => of(namespaced_key).into_iter().collect(),
+            Command::SetFromEnv {
+                namespaced_key,
+                namespace,
+                ..
+            } => {
+                if namespaced_key.contains('@') {
+                    of(namespaced_key).into_iter().collect()
+                } else {
+                    vec![or_default(namespace.as_ref())]
+                }
+            }
+            // aliases are resolved in their namespace's database, so they can't point out of it
+            Command::Alias {
+                command:
+                    AliasCommand::Create {
+                        namespaced_alias: namespaced_key1,
+                        namespaced_target: namespaced_key2,
+                    },
+            }
+            | Command::Diff {
+                namespaced_key1,
+                namespaced_key2,
+            } => [namespaced_key1, namespaced_key2]
+                .into_iter()
+                .filter_map(|key| of(key))
+                .collect(),
+            // `spanned_namespaces` has the ones with patterns
+            Command::List { namespace, .. }
+            | Command::CaptureEnv { namespace, .. }
+            | Command::Keys { namespace, .. }
+            | Command::Rename { namespace, .. }
+            | Command::Random { namespace, .. }
+            | Command::Tree { namespace, .. }
+            | Command::Pick { namespace, .. }
+            | Command::Dupes { namespace, .. } => Some(or_default(namespace.as_ref()))
+                .filter(|namespace| !namespace.contains('*'))
+                .into_iter()
+                .collect(),
+            Command::Export { namespace, .. }
+            | Command::Prune { namespace, .. }
+            | Command::Query { namespace, .. }
+            | Command::DbDiff { namespace, .. } => namespace
+                .iter()
+                .filter(|namespace| !namespace.contains('*'))
+                .cloned()
+                .collect(),
+            Command::FindValue { namespace, .. } | Command::Which { namespace, .. }
+                if !namespace.contains('*') =>
+            {
+                vec![namespace.clone()]
+            }
+            Command::Direnv {
+                namespace: Some(namespace),
+                ..
+            } => vec![namespace.clone()],
+            Command::Alias {
+                command: AliasCommand::List { namespace },
+            } => vec![or_default(namespace.as_ref())],
+            Command::Move { from, to, .. } => vec![or_default(from.as_ref()), to.clone()],
+            Command::Namespace {
+                command:
+                    NamespaceCommand::Copy {
+                        source,
+                        destination,
+                        ..
+                    },
+            } => vec![source.clone(), destination.clone()],
+            // namespace settings, schemas, and indexes live with the namespace's keys
+            Command::Namespace {
+                command:
+                    NamespaceCommand::Describe { namespace, .. }
+                    | NamespaceCommand::SetDefault { namespace, .. },
+            }
+            | Command::Schema {
+                command:
+                    SchemaCommand::Set { namespace, .. }
+                    | SchemaCommand::Delete { namespace, .. }
+                    | SchemaCommand::Get { namespace, .. },
+            }
+            | Command::Index {
+                command:
+                    IndexCommand::Create { namespace, .. } | IndexCommand::Drop { namespace, .. },
+            } => vec![namespace.clone()],
+            _ => vec![],
+        }
+    }
+
+    /// The namespaces a command reads or changes when it isn't limited to one of them,
+    /// as a pattern, like `*` for all of them.
+    /// `get --stdin`, `import`, `merge`, and `gc` find their databases as they go instead
+    fn spanned_namespaces(&self) -> Option<String> {
+        match self {
+            Command::List {
+                namespace: Some(namespace),
+                ..
+            }
+            | Command::CaptureEnv {
+                namespace: Some(namespace),
+                ..
+            }
+            | Command::Keys {
+                namespace: Some(namespace),
+                ..
+            }
+            | Command::Rename {
+                namespace: Some(namespace),
+                ..
+            }
+            | Command::Random {
+                namespace: Some(namespace),
+                ..
+            }
+            | Command::Tree {
+                namespace: Some(namespace),
+                ..
+            }
+            | Command::Pick {
+                namespace: Some(namespace),
+                ..
+            }
+            | Command::Dupes {
+                namespace: Some(namespace),
+                ..
+            }
+            | Command::FindValue { namespace, .. }
+            | Command::Which { namespace, .. }
+                if namespace.contains('*') =>
+            {
+                Some(namespace.clone())
+            }
+            Command::Export { namespace, .. }
+            | Command::Prune { namespace, .. }
+            | Command::Query { namespace, .. }
+            | Command::DbDiff { namespace, .. } => match namespace {
+                Some(namespace) if !namespace.contains('*') => None,
+                namespace => Some(namespace.clone().unwrap_or_else(|| "*".to_string())),
+            },
+            Command::ListNamespaces { .. } | Command::Backup { .. } | Command::Restore { .. } => {
+                Some("*".to_string())
+            }
+            #[cfg(feature = "fuser")]
+            Command::Mount { .. } => Some("*".to_string()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Subcommand, Clone)]
+enum NamespaceCommand {
+    /// Set a namespace's description, or print it if no description is given
+    Describe {
+        namespace: String,
+        description: Option<String>,
+    },
+    /// Set defaults for new keys in a namespace, like `ttl=7d`,
+    /// or print them if no settings are given. `ttl=none` removes the default
+    SetDefault {
+        namespace: String,
+        settings: Vec<String>,
+    },
+    /// Copy a namespace's keys into another namespace, all at once.
+    /// Fails without copying anything if a copied key already exists, unless `--overwrite` is given
+    Copy {
+        source: String,
+        destination: String,
+        /// Put this before each copied key, like `v2/`
+        #[arg(long, default_value = "")]
+        key_prefix: String,
+        /// Only copy keys matching this, where `*` matches anything
+        #[arg(long, default_value = "*")]
+        filter: String,
+        /// Replace keys that already exist in the destination
+        #[arg(long)]
+        overwrite: bool,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum NoteCommand {
+    /// Set a key's note. `key[@namespace]`
+    Set {
+        namespaced_key: String,
+        note: String,
+    },
+    /// Print a key's note
+    Get { namespaced_key: String },
+    /// Delete a key's note
+    Delete { namespaced_key: String },
+}
+
+#[derive(Subcommand, Clone)]
+enum HistoryCommand {
+    /// Delete a key's earlier versions, keeping only its current value,
+    /// or all of its history if it's been deleted. `key[@namespace]`
+    Purge { namespaced_key: String },
+}
+
+#[derive(Subcommand, Clone)]
+enum AliasCommand {
+    /// Point an alias at a key, or at another alias. Both are `key[@namespace]`,
+    /// and have to be kept in the same database
+    Create {
+        namespaced_alias: String,
+        namespaced_target: String,
+    },
+    /// Delete an alias. `alias[@namespace]`
+    Delete { namespaced_alias: String },
+    /// List aliases in a namespace (default: `default`) and their targets
+    List { namespace: Option<String> },
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum QueryField {
+    Namespace,
+    Key,
+    Value,
+    /// The value's length in bytes
+    Size,
+    Created,
+    Updated,
+    /// When the key was last read, if `track_last_accessed` is on
+    Accessed,
+    Expires,
+    /// The type set with `set --type`
+    Type,
+    /// The note set with `note set`
+    Note,
+}
+
+impl QueryField {
+    fn column(&self) -> &'static str {
+        match self {
+            QueryField::Namespace => "namespace",
+            QueryField::Key => "key",
+            QueryField::Value => "value",
+            QueryField::Size => "length(value)",
+            QueryField::Created => "inserted_at",
+            QueryField::Updated => "updated_at",
+            QueryField::Accessed => "last_accessed_at",
+            QueryField::Expires => "expires_at",
+            QueryField::Type => "value_type",
+            QueryField::Note => "note",
+        }
+    }
+
+    fn is_time(&self) -> bool {
+        matches!(
+            self,
+            QueryField::Created | QueryField::Updated | QueryField::Accessed | QueryField::Expires
+        )
+    }
+}
+
+/// A `query --where` filter, like `size > 1MB`
+#[derive(Clone)]
+struct QueryWhere {
+    field: QueryField,
+    operator: &'static str,
+    value: QueryValue,
+}
+
+#[derive(Clone)]
+enum QueryValue {
+    Literal(rusqlite::types::Value),
+    /// This long before now
+    Ago(std::time::Duration),
+}
+
+#[derive(Subcommand, Clone)]
+enum IndexCommand {
+    /// Index a JSON path in a namespace's values, like `$.env`
+    Create { namespace: String, path: String },
+    /// Drop an index made with `index create`
+    Drop { namespace: String, path: String },
+    /// List indexed namespaces and paths
+    List,
+}
+
+#[derive(Subcommand, Clone)]
+enum SchemaCommand {
+    /// Require values set in a namespace to be JSON matching a schema.
+    /// When several prefixes match a key, the longest one's schema is used
+    Set {
+        namespace: String,
+        schema_file: PathBuf,
+        /// Only keys starting with this
+        #[arg(long, default_value = "")]
+        prefix: String,
+    },
+    /// Stop validating values in a namespace
+    Delete {
+        namespace: String,
+        #[arg(long, default_value = "")]
+        prefix: String,
+    },
+    /// Print a namespace's schema
+    Get {
+        namespace: String,
+        #[arg(long, default_value = "")]
+        prefix: String,
+    },
+    /// List the namespaces and prefixes that have schemas
+    List,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TimeFormat {
+    /// Like `2024-05-01T12:00:00.000Z`
+    Iso8601,
+    /// Seconds since 1970-01-01 UTC
+    Unix,
+    /// Like `3 days ago` or `in 2 hours`
+    Relative,
+}
+
+/// How to print the times in the database, which are stored in UTC
+/// as `YYYY-MM-DD HH:MM:SS.SSS`
+#[derive(Clone, Copy)]
+struct TimeDisplay {
+    format: TimeFormat,
+    local: bool,
+}
+
+impl TimeDisplay {
+    /// A SQL expression for how to print the time in `column`, `null` when it is.
+    /// Relative times are seconds from now, which `write` describes
+    fn sql(&self, column: &str) -> String {
+        match self.format {
+            TimeFormat::Iso8601 if self.local => {
+                let offset_minutes = format!(
+                    "cast(round((julianday({0}, 'localtime') - julianday({0})) * 1440) as integer)",
+                    column
+                );
+                format!(
+                    "strftime('%Y-%m-%dT%H:%M:%f', {0}, 'localtime') || printf('%+03d:%02d', {1} / 60, abs({1}) % 60)",
+                    column, offset_minutes
+                )
+            }
+            TimeFormat::Iso8601 => format!("strftime('%Y-%m-%dT%H:%M:%fZ', {})", column),
+            TimeFormat::Unix => format!("unixepoch({})", column),
+            TimeFormat::Relative => format!("(julianday({}) - julianday('NOW')) * 86400", column),
+        }
+    }
+
+    /// Writes a time selected with `sql`
+    fn write(&self, out: &mut impl Write, value: ValueRef) -> std::io::Result<()> {
+        match (self.format, value) {
+            (TimeFormat::Relative, ValueRef::Real(seconds)) => {
+                write!(out, "{}", relative_time(seconds))
+            }
+            (_, value) => write_sql_value(out, value, false),
+        }
+    }
+}
+
+/// Like `3 days ago` for negative seconds, and `in 3 days` for positive ones
+fn relative_time(seconds: f64) -> String {
+    let magnitude = seconds.abs();
+
+    if magnitude < 1.0 {
+        return "now".to_string();
+    }
+
+    let (count, unit) = [
+        (365.0 * 86400.0, "year"),
+        (30.0 * 86400.0, "month"),
+        (86400.0, "day"),
+        (3600.0, "hour"),
+        (60.0, "minute"),
+        (1.0, "second"),
+    ]
+    .into_iter()
+    .find(|(unit_seconds, _)| magnitude >= *unit_seconds)
+    .map(|(unit_seconds, unit)| ((magnitude / unit_seconds) as u64, unit))
+    .unwrap_or((0, "second"));
+
+    let plural = if count == 1 { "" } else { "s" };
+
+    if seconds < 0.0 {
+        format!("{} {}{} ago", count, unit, plural)
+    } else {
+        format!("in {} {}{}", count, unit, plural)
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ValueType {
+    Int,
+    /// `true` or `false`. `1`, `0`, `yes`, `no`, `on`, and `off` are accepted too
+    Bool,
+    Float,
+    Json,
+    /// Any UTF-8
+    String,
+}
+
+impl ValueType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ValueType::Int => "int",
+            ValueType::Bool => "bool",
+            ValueType::Float => "float",
+            ValueType::Json => "json",
+            ValueType::String => "string",
+        }
+    }
+
+    /// Reads `value` as this type, returning it in its canonical form,
+    /// or `None` if it isn't one
+    fn coerce(&self, value: &[u8]) -> Option<Vec<u8>> {
+        let s = std::str::from_utf8(value).ok()?;
+
+        let coerced = match self {
+            ValueType::Int => s.trim().parse::<i64>().ok()?.to_string(),
+            ValueType::Bool => match s.trim().to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => "true".to_string(),
+                "false" | "0" | "no" | "off" => "false".to_string(),
+                _ => return None,
+            },
+            ValueType::Float => s
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|f| f.is_finite())?
+                .to_string(),
+            ValueType::Json => {
+                serde_json::from_str::<serde::de::IgnoredAny>(s).ok()?;
+                s.to_string()
+            }
+            ValueType::String => s.to_string(),
+        };
+
+        Some(coerced.into_bytes())
+    }
+
+    /// Fails with `Rejected` if `value` isn't of this type
+    fn check(&self, namespaced_key: &str, value: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.coerce(value).ok_or_else(|| {
+            BladeError::Rejected(format!(
+                "{} must be {} {}, not: {}",
+                namespaced_key,
+                match self {
+                    ValueType::Int => "an",
+                    _ => "a",
+                },
+                self.as_str(),
+                describe_value(value)
+            ))
+            .into()
+        })
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PickAction {
+    /// Print the value
+    Print,
+    Delete,
+    /// Copy the value to the clipboard
+    Clip,
+    /// Edit the value in `$VISUAL` or `$EDITOR`
+    Edit,
+}
+
+/// A text encoding for binary values, from `--base64` or `--hex`
+#[derive(Clone, Copy)]
+enum Encoding {
+    Base64,
+    Hex,
+}
+
+impl Encoding {
+    fn from_flags(base64: bool, hex: bool) -> Option<Self> {
+        match (base64, hex) {
+            (true, _) => Some(Encoding::Base64),
+            (_, true) => Some(Encoding::Hex),
+            _ => None,
+        }
+    }
+
+    fn encode(&self, value: &[u8]) -> Vec<u8> {
+        match self {
+            Encoding::Base64 => BASE64_STANDARD.encode(value).into_bytes(),
+            Encoding::Hex => value
+                .iter()
+                .flat_map(|byte| format!("{:02x}", byte).into_bytes())
+                .collect(),
+        }
+    }
+
+    /// Whitespace is ignored, so wrapped or newline-terminated input decodes
+    fn decode(&self, value: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let value: Vec<u8> = value
+            .iter()
+            .copied()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .collect();
+
+        match self {
+            Encoding::Base64 => BASE64_STANDARD
+                .decode(&value)
+                .map_err(|e| BladeError::Usage(format!("value is not valid base64: {}", e)).into()),
+            Encoding::Hex => {
+                let invalid = || BladeError::Usage("value is not valid hex".to_string());
+
+                if !value.len().is_multiple_of(2) {
+                    return Err(invalid().into());
+                }
+
+                value
+                    .chunks(2)
+                    .map(|pair| {
+                        std::str::from_utf8(pair)
+                            .ok()
+                            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                            .ok_or_else(|| invalid().into())
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Jsonl,
+    Csv,
+    Tsv,
+}
+
+impl ExportFormat {
+    fn csv_delimiter(&self) -> u8 {
+        match self {
+            ExportFormat::Tsv => b'\t',
+            _ => b',',
+        }
+    }
+}
+
+#[derive(clap::Args, Clone)]
+struct CsvImportOptions {
+    /// Column containing the key (csv/tsv only)
+    #[arg(long, default_value_t = 0)]
+    key_column: usize,
+    /// Column containing the value (csv/tsv only)
+    #[arg(long, default_value_t = 1)]
+    value_column: usize,
+    /// Column containing the namespace (csv/tsv only)
+    #[arg(long)]
+    namespace_column: Option<usize>,
+    /// Namespace for rows without a namespace column (csv/tsv only), or for keys from Redis
+    #[arg(long)]
+    namespace: Option<String>,
+    /// The first row is data, not a header (csv/tsv only)
+    #[arg(long)]
+    no_header: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ConflictStrategy {
+    /// Keep the existing value
+    Skip,
+    /// Replace the existing value
+    Overwrite,
+    /// Replace the existing value if the incoming entry was updated more recently
+    Newest,
+    /// Fail without changing anything
+    Fail,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MergeStrategy {
+    /// Keep whichever value was updated most recently
+    Newest,
+    /// Keep the value in this database
+    Ours,
+    /// Take the value from the other database
+    Theirs,
+    /// Ask for each conflict
+    Interactive,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 struct Config {
     db_location: PathBuf,
+    /// The namespace of keys that don't name one
+    default_namespace: String,
     sqlite_synchronous_mode: SqliteSynchronousMode,
     sqlite_busy_timeout_ms: i32,
+    // The following are applied as pragmas when opening the database,
+    // and left at SQLite's defaults when not set.
+    // `page_size` and `auto_vacuum` only take effect for new databases,
+    // or existing ones after a `vacuum`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sqlite_page_size: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sqlite_cache_size: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sqlite_mmap_size: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sqlite_wal_autocheckpoint: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sqlite_temp_store: Option<SqliteTempStore>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sqlite_auto_vacuum: Option<SqliteAutoVacuum>,
+    /// Retry writes that fail because the database is busy,
+    /// on top of waiting for `sqlite_busy_timeout_ms`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    busy_retry: Option<BusyRetry>,
+    /// Record when each key was last read by `get`.
+    /// Off by default, because it turns reads into writes
+    track_last_accessed: bool,
+    /// Keep each namespace in its own database file, in a directory next to `db_location`,
+    /// so that commands only open the files they use.
+    /// Commands that span namespaces, like `list 'work.*'` or `export`, are refused,
+    /// except `import`, `merge`, `get --stdin`, and `gc`, which use each namespace's file
+    shard_by_namespace: bool,
+    /// Delete up to this many expired keys on every command, like `gc --max`.
+    /// Off by default, for the same reason
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expiry_sweep_limit: Option<u64>,
+    /// Per-namespace settings, like `[namespaces.cache]`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    namespaces: BTreeMap<String, NamespaceConfig>,
+    /// Shell commands to run around changes, like `pre-set = "./validate.sh"`
+    #[serde(skip_serializing_if = "Hooks::is_empty")]
+    hooks: Hooks,
+    /// How much of each key's history `gc` keeps, like `max_versions = 10`
+    #[serde(skip_serializing_if = "HistoryRetention::is_empty")]
+    history: HistoryRetention,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        let mut db_location = directories::ProjectDirs::from("", "", "blade")
-            .ok_or(anyhow!("could not retrieve home directory"))
-            .unwrap()
-            .data_local_dir()
-            .to_path_buf();
+/// Every version of every key is kept, deleted ones included, unless these are set
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct HistoryRetention {
+    /// Keep at most this many versions of each key, counting the current one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_versions: Option<u64>,
+    /// Drop versions recorded longer ago than this, like `90d`,
+    /// except the one holding a key's current value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_age: Option<String>,
+}
+
+impl HistoryRetention {
+    fn is_empty(&self) -> bool {
+        self.max_versions.is_none() && self.max_age.is_none()
+    }
+}
+
+/// Each hook is run with `sh -c`, with `BLADE_HOOK`, `BLADE_NAMESPACE`, and `BLADE_KEY`
+/// set in its environment, and the value on its stdin
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct Hooks {
+    /// Runs before a `set`. If it fails, the `set` is aborted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pre_set: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_set: Option<String>,
+    /// Gets no value on its stdin
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_delete: Option<String>,
+}
+
+impl Hooks {
+    fn is_empty(&self) -> bool {
+        self.pre_set.is_none() && self.post_set.is_none() && self.post_delete.is_none()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct BusyRetry {
+    /// Total attempts, including the first
+    max_attempts: u32,
+    initial_backoff_ms: u64,
+    max_backoff_ms: u64,
+}
+
+impl Default for BusyRetry {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff_ms: 10,
+            max_backoff_ms: 1_000,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct NamespaceConfig {
+    mode: NamespaceMode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_entries: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_bytes: Option<u64>,
+    /// What `set` does when it would go over `max_entries` or `max_bytes`.
+    /// Defaults to `lru` in cache mode, and `none` otherwise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    eviction: Option<Eviction>,
+    /// URLs to POST a JSON event to after each change to a key in the namespace
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    webhooks: Vec<String>,
+}
+
+impl NamespaceConfig {
+    fn eviction(&self) -> Eviction {
+        self.eviction.unwrap_or(match self.mode {
+            NamespaceMode::Normal => Eviction::None,
+            NamespaceMode::Cache => Eviction::Lru,
+        })
+    }
+
+    fn tracks_last_accessed(&self) -> bool {
+        matches!(self.mode, NamespaceMode::Cache)
+    }
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum NamespaceMode {
+    #[default]
+    Normal,
+    /// Evict on write, and record reads for `lru` eviction
+    /// even without `track_last_accessed`
+    Cache,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Eviction {
+    /// Fail the `set`
+    None,
+    /// Delete the least recently used entries until the namespace fits.
+    /// Reads only count with `track_last_accessed`, or in cache mode
+    Lru,
+    /// Delete the least recently inserted entries until the namespace fits
+    Fifo,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let mut db_location = directories::ProjectDirs::from("", "", "blade")
+            .ok_or(anyhow!("could not retrieve home directory"))
+            .unwrap()
+            .data_local_dir()
+            .to_path_buf();
+
+        db_location.push("blade.db");
+
+        Self {
+            db_location,
+            default_namespace: DEFAULT_NAMESPACE.to_string(),
+            sqlite_synchronous_mode: SqliteSynchronousMode::default(),
+            sqlite_busy_timeout_ms: 5_000,
+            sqlite_page_size: None,
+            sqlite_cache_size: None,
+            sqlite_mmap_size: None,
+            sqlite_wal_autocheckpoint: None,
+            sqlite_temp_store: None,
+            sqlite_auto_vacuum: None,
+            busy_retry: None,
+            track_last_accessed: false,
+            shard_by_namespace: false,
+            expiry_sweep_limit: None,
+            namespaces: BTreeMap::new(),
+            hooks: Hooks::default(),
+            history: HistoryRetention::default(),
+        }
+    }
+}
+
+impl Config {
+    /// This config, with any unset SQLite tuning options filled in
+    /// with the values actually in effect on `conn`
+    fn effective(&self, conn: &Connection) -> rusqlite::Result<Config> {
+        let pragma = |name: &str| conn.pragma_query_value(None, name, |row| row.get::<_, i64>(0));
+
+        Ok(Config {
+            sqlite_page_size: Some(pragma("page_size")?),
+            sqlite_cache_size: Some(pragma("cache_size")?),
+            sqlite_mmap_size: Some(pragma("mmap_size")?),
+            sqlite_wal_autocheckpoint: Some(pragma("wal_autocheckpoint")?),
+            sqlite_temp_store: SqliteTempStore::from_pragma(pragma("temp_store")?),
+            sqlite_auto_vacuum: SqliteAutoVacuum::from_pragma(pragma("auto_vacuum")?),
+            ..self.clone()
+        })
+    }
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SqliteSynchronousMode {
+    Extra,
+    Full,
+    #[default]
+    Normal,
+    Off,
+}
+
+impl Display for SqliteSynchronousMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SqliteSynchronousMode::Extra => "extra",
+            SqliteSynchronousMode::Full => "full",
+            SqliteSynchronousMode::Normal => "normal",
+            SqliteSynchronousMode::Off => "off",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SqliteTempStore {
+    Default,
+    File,
+    Memory,
+}
+
+impl SqliteTempStore {
+    fn from_pragma(value: i64) -> Option<Self> {
+        match value {
+            0 => Some(SqliteTempStore::Default),
+            1 => Some(SqliteTempStore::File),
+            2 => Some(SqliteTempStore::Memory),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SqliteTempStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SqliteTempStore::Default => "default",
+            SqliteTempStore::File => "file",
+            SqliteTempStore::Memory => "memory",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SqliteAutoVacuum {
+    None,
+    Full,
+    Incremental,
+}
+
+impl SqliteAutoVacuum {
+    fn from_pragma(value: i64) -> Option<Self> {
+        match value {
+            0 => Some(SqliteAutoVacuum::None),
+            1 => Some(SqliteAutoVacuum::Full),
+            2 => Some(SqliteAutoVacuum::Incremental),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SqliteAutoVacuum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SqliteAutoVacuum::None => "none",
+            SqliteAutoVacuum::Full => "full",
+            SqliteAutoVacuum::Incremental => "incremental",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// ~/.config/blade/config.toml
+fn config_file_path() -> anyhow::Result<PathBuf> {
+    let mut config_path = directories::UserDirs::new()
+        .ok_or(anyhow!("could not retrieve home directory"))?
+        .home_dir()
+        .to_path_buf();
+    config_path.push(".config");
+    config_path.push("blade");
+    config_path.push("config.toml");
+
+    Ok(config_path)
+}
+
+fn get_or_create_config_file() -> anyhow::Result<Config> {
+    let config_path = config_file_path()?;
+
+    if let Some(config_dir) = config_path.parent() {
+        std::fs::create_dir_all(config_dir)?;
+    }
+
+    let config: Config = match std::fs::read_to_string(&config_path) {
+        Ok(f) => toml::from_str(&f)?,
+        Err(_) => {
+            let mut f = std::fs::File::create_new(&config_path)?;
+
+            let config = Config::default();
+
+            let s = toml::to_string(&config)?;
+
+            f.write_all(s.as_bytes())?;
+
+            if std::io::stderr().is_terminal() {
+                eprintln!(
+                    "wrote the default config to {}, `blade init --force` asks for each setting",
+                    config_path.display()
+                );
+            }
+
+            config
+        }
+    };
+
+    Ok(config)
+}
+
+fn open_or_create_db(db_location: &Path, config: &Config) -> anyhow::Result<rusqlite::Connection> {
+    match open_db_connection(db_location, config) {
+        Ok(c) => Ok(c),
+        Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error {
+                code: rusqlite::ErrorCode::CannotOpen,
+                ..
+            },
+            _,
+        )) => {
+            let db_dir = db_location.parent().unwrap();
+            std::fs::create_dir_all(db_dir)?;
+            let conn = open_db_connection(db_location, config)?;
+            Ok(conn)
+        }
+        Err(e) => Err(e)?,
+    }
+}
+
+fn open_db_connection(path: &Path, config: &Config) -> rusqlite::Result<rusqlite::Connection> {
+    let conn = rusqlite::Connection::open(path)?;
+
+    // these have to be set before the database is in wal mode
+    if let Some(page_size) = config.sqlite_page_size {
+        conn.pragma_update(None, "page_size", page_size)?;
+    }
+    if let Some(auto_vacuum) = config.sqlite_auto_vacuum {
+        conn.pragma_update(None, "auto_vacuum", auto_vacuum.to_string())?;
+    }
+
+    conn.pragma_update(None, "journal_mode", "wal")?;
+    conn.pragma_update(
+        None,
+        "synchronous",
+        config.sqlite_synchronous_mode.to_string(),
+    )?;
+
+    if let Some(wal_autocheckpoint) = config.sqlite_wal_autocheckpoint {
+        conn.pragma_update(None, "wal_autocheckpoint", wal_autocheckpoint)?;
+    }
+
+    apply_connection_pragmas(&conn, config)?;
+
+    if tracing::enabled!(Level::DEBUG) {
+        conn.trace_v2(TraceEventCodes::SQLITE_TRACE_PROFILE, Some(log_statement));
+    }
+
+    #[cfg(target_os = "macos")]
+    conn.pragma_update(None, "fullfsync", true)?;
+
+    Ok(conn)
+}
+
+/// Pragmas that apply to both read-write and read-only connections
+fn apply_connection_pragmas(conn: &Connection, config: &Config) -> rusqlite::Result<()> {
+    conn.pragma_update(None, "busy_timeout", config.sqlite_busy_timeout_ms)?;
+
+    if TIMEOUT.get().is_some() {
+        BUSY_TIMEOUT_MS.store(config.sqlite_busy_timeout_ms, Ordering::Relaxed);
+        conn.busy_handler(Some(wait_while_busy))?;
+    }
+
+    lock_ignoring_poison(&INTERRUPT_HANDLES).push(conn.get_interrupt_handle());
+
+    if let Some(cache_size) = config.sqlite_cache_size {
+        conn.pragma_update(None, "cache_size", cache_size)?;
+    }
+    if let Some(mmap_size) = config.sqlite_mmap_size {
+        conn.pragma_update(None, "mmap_size", mmap_size)?;
+    }
+    if let Some(temp_store) = config.sqlite_temp_store {
+        conn.pragma_update(None, "temp_store", temp_store.to_string())?;
+    }
+
+    Ok(())
+}
+
+fn is_busy(e: &rusqlite::Error) -> bool {
+    matches!(
+        e.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+    )
+}
+
+/// Runs `f`, retrying with exponential backoff and jitter while the database is busy,
+/// if `busy_retry` is configured
+fn with_busy_retry<T>(
+    config: &Config,
+    mut f: impl FnMut() -> rusqlite::Result<T>,
+) -> rusqlite::Result<T> {
+    let Some(busy_retry) = &config.busy_retry else {
+        return f();
+    };
+
+    let mut backoff_ms = busy_retry.initial_backoff_ms;
+    let mut attempt = 1;
+
+    loop {
+        match f() {
+            Err(e)
+                if is_busy(&e)
+                    && attempt < busy_retry.max_attempts
+                    && !TIMED_OUT.load(Ordering::Relaxed) =>
+            {
+                // sleep somewhere between half and all of the backoff,
+                // so concurrent writers don't retry in lockstep
+                let jitter = getrandom::u64().unwrap_or_default() % (backoff_ms / 2 + 1);
+                let sleep_ms = backoff_ms - jitter;
+
+                warn!(
+                    "database is busy, retrying in {}ms (attempt {} of {})",
+                    sleep_ms, attempt, busy_retry.max_attempts
+                );
+
+                std::thread::sleep(Duration::from_millis(sleep_ms));
+
+                backoff_ms = (backoff_ms * 2).min(busy_retry.max_backoff_ms);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn open_read_only_db_connection(
+    path: &Path,
+    config: &Config,
+) -> rusqlite::Result<rusqlite::Connection> {
+    let conn = rusqlite::Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+
+    apply_connection_pragmas(&conn, config)?;
+
+    if tracing::enabled!(Level::DEBUG) {
+        conn.trace_v2(TraceEventCodes::SQLITE_TRACE_PROFILE, Some(log_statement));
+    }
+
+    Ok(conn)
+}
+
+/// Asks on stderr and reads the answers from stdin, so it can be scripted
+fn init(force: bool) -> anyhow::Result<()> {
+    let config_path = config_file_path()?;
+
+    let mut config = match std::fs::read_to_string(&config_path) {
+        Ok(_) if !force => {
+            return Err(BladeError::Conflict(format!(
+                "config file already exists: {}, edit it or pass --force to replace it",
+                config_path.display()
+            ))
+            .into());
+        }
+        // start from what's there, unless it's what needs replacing
+        Ok(s) => toml::from_str(&s).unwrap_or_default(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Config::default(),
+        Err(e) => {
+            return Err(e).with_context(|| format!("could not read {}", config_path.display()));
+        }
+    };
+
+    let mut err = std::io::stderr().lock();
+    let mut stdin = std::io::stdin().lock();
+
+    let mut ask = |question: String| -> anyhow::Result<String> {
+        write!(err, "{} ", question)?;
+        err.flush()?;
+
+        let mut answer = String::new();
+        stdin.read_line(&mut answer)?;
+
+        Ok(answer.trim().to_string())
+    };
+
+    let db_location = ask(format!(
+        "database location? [{}]",
+        config.db_location.display()
+    ))?;
+
+    if let Some(path) = db_location.strip_prefix("~/") {
+        config.db_location = directories::UserDirs::new()
+            .ok_or(anyhow!("could not retrieve home directory"))?
+            .home_dir()
+            .join(path);
+    } else if !db_location.is_empty() {
+        config.db_location = PathBuf::from(db_location);
+    }
+
+    let default_namespace = ask(format!(
+        "namespace for keys that don't name one? [{}]",
+        config.default_namespace
+    ))?;
+
+    if !default_namespace.is_empty() {
+        check_default_namespace(&default_namespace)?;
+        config.default_namespace = default_namespace;
+    }
+
+    let durable = matches!(
+        config.sqlite_synchronous_mode,
+        SqliteSynchronousMode::Full | SqliteSynchronousMode::Extra
+    );
+
+    let answer = ask(format!(
+        "keep every write through a power failure, at the cost of slower writes? {}",
+        if durable { "[Y/n]" } else { "[y/N]" }
+    ))?;
+
+    match answer.to_lowercase().as_str() {
+        "" => {}
+        "y" | "yes" if !durable => config.sqlite_synchronous_mode = SqliteSynchronousMode::Full,
+        "n" | "no" if durable => config.sqlite_synchronous_mode = SqliteSynchronousMode::Normal,
+        "y" | "yes" | "n" | "no" => {}
+        _ => {
+            return Err(BladeError::Usage(format!("expected y or n, not: {}", answer)).into());
+        }
+    }
+
+    if let Some(config_dir) = config_path.parent() {
+        std::fs::create_dir_all(config_dir)?;
+    }
+
+    std::fs::write(&config_path, toml::to_string(&config)?)
+        .with_context(|| format!("could not write {}", config_path.display()))?;
+
+    let existed = config.db_location.exists();
+
+    migrate_db(open_or_create_db(&config.db_location, &config)?)?;
+
+    let mut out = std::io::stdout().lock();
+
+    writeln!(out, "wrote {}", config_path.display())?;
+
+    if existed {
+        writeln!(
+            out,
+            "using the existing database at {}",
+            config.db_location.display()
+        )?;
+    } else {
+        writeln!(out, "created {}", config.db_location.display())?;
+    }
+
+    Ok(())
+}
+
+/// Findings from `blade doctor`, printed as they're found
+struct Diagnosis<W> {
+    out: W,
+    errors: usize,
+}
+
+impl<W: Write> Diagnosis<W> {
+    fn ok(&mut self, message: impl Display) -> std::io::Result<()> {
+        writeln!(self.out, "ok       {}", message)
+    }
+
+    fn warning(&mut self, message: impl Display, fix: impl Display) -> std::io::Result<()> {
+        writeln!(self.out, "warning  {}\n         fix: {}", message, fix)
+    }
+
+    fn error(&mut self, message: impl Display, fix: impl Display) -> std::io::Result<()> {
+        self.errors += 1;
+        writeln!(self.out, "error    {}\n         fix: {}", message, fix)
+    }
+
+    fn finish(mut self) -> anyhow::Result<()> {
+        self.out.flush()?;
+
+        match self.errors {
+            0 => Ok(()),
+            1 => Err(anyhow!("found 1 problem")),
+            n => Err(anyhow!("found {} problems", n)),
+        }
+    }
+}
+
+/// Unlike every other command, this has to work without a valid config file,
+/// and without creating or changing the database
+fn doctor(db_location: Option<&Path>) -> anyhow::Result<()> {
+    let mut d = Diagnosis {
+        out: std::io::stdout().lock(),
+        errors: 0,
+    };
+
+    let config_path = config_file_path()?;
+
+    let config = match std::fs::read_to_string(&config_path) {
+        Ok(s) => match toml::from_str::<Config>(&s) {
+            Ok(config) => {
+                match check_default_namespace(&config.default_namespace) {
+                    Ok(()) => d.ok(format!("config file {} is valid", config_path.display()))?,
+                    Err(e) => d.error(
+                        format!("config file {} is invalid: {}", config_path.display(), e),
+                        "set it to a namespace, or remove it to use `default`",
+                    )?,
+                }
+                config
+            }
+            Err(e) => {
+                d.error(
+                    format!(
+                        "config file {} is invalid: {}",
+                        config_path.display(),
+                        e.message()
+                    ),
+                    "correct it, or move it aside and blade will write one with the defaults",
+                )?;
+                Config::default()
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            d.ok(format!(
+                "no config file yet, one with the defaults will be written to {}",
+                config_path.display()
+            ))?;
+            Config::default()
+        }
+        Err(e) => {
+            d.error(
+                format!(
+                    "could not read config file {}: {}",
+                    config_path.display(),
+                    e
+                ),
+                format!(
+                    "check its permissions, like `chmod u+rw {}`",
+                    config_path.display()
+                ),
+            )?;
+            Config::default()
+        }
+    };
+
+    let db_location = db_location
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| config.db_location.clone());
+
+    let db_dir = match db_location.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    // the nearest directory that exists, for the permission and disk space checks
+    let existing_dir = db_dir.ancestors().find(|dir| dir.is_dir());
+
+    // SQLite creates the -wal and -shm files next to the database
+    match existing_dir.map(tempfile::tempfile_in) {
+        Some(Ok(_)) => d.ok(format!("can create files in {}", db_dir.display()))?,
+        Some(Err(e)) => d.error(
+            format!("cannot create files in {}: {}", db_dir.display(), e),
+            format!(
+                "check its permissions, like `chmod u+rwx {}`",
+                db_dir.display()
+            ),
+        )?,
+        None => d.error(
+            format!("no part of {} exists", db_dir.display()),
+            "set `db_location` in the config file to a path on this machine",
+        )?,
+    }
+
+    let db_size = std::fs::metadata(&db_location).map(|m| m.len()).ok();
+
+    #[cfg(unix)]
+    if let Some(dir) = existing_dir {
+        match rustix::fs::statvfs(dir) {
+            Ok(stat) => {
+                let free = stat.f_bavail * stat.f_frsize;
+                // `vacuum` and `backup` write a whole copy of the database
+                let needed = db_size.unwrap_or(0).max(100 * 1024 * 1024);
+                if free < needed {
+                    d.warning(
+                        format!(
+                            "only {} free on the disk with {}",
+                            indicatif::HumanBytes(free),
+                            db_dir.display()
+                        ),
+                        "free up space, writes fail with `database or disk is full` when it runs out",
+                    )?;
+                } else {
+                    d.ok(format!("{} free on disk", indicatif::HumanBytes(free)))?;
+                }
+            }
+            Err(e) => warn!("could not check free disk space: {}", e),
+        }
+    }
+
+    let Some(db_size) = db_size else {
+        d.ok(format!(
+            "no database yet, it will be created at {}",
+            db_location.display()
+        ))?;
+        return d.finish();
+    };
+
+    if let Err(e) = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&db_location)
+    {
+        d.error(
+            format!("cannot write database {}: {}", db_location.display(), e),
+            format!(
+                "check its permissions, like `chmod u+rw {}`",
+                db_location.display()
+            ),
+        )?;
+        return d.finish();
+    }
+
+    d.ok(format!(
+        "database {} is {}",
+        db_location.display(),
+        indicatif::HumanBytes(db_size)
+    ))?;
+
+    let sidecar = |suffix: &str| {
+        let mut path = db_location.clone().into_os_string();
+        path.push(suffix);
+        PathBuf::from(path)
+    };
+
+    if sidecar("-journal").exists() {
+        d.warning(
+            format!(
+                "a rollback journal was left behind at {}",
+                sidecar("-journal").display()
+            ),
+            "run any blade command to let SQLite roll back the interrupted write. \
+            Don't delete the journal, the database needs it to be consistent",
+        )?;
+    }
+
+    let conn = match rusqlite::Connection::open_with_flags(
+        &db_location,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    ) {
+        Ok(conn) => conn,
+        Err(e) => {
+            d.error(
+                format!("cannot open database: {}", e),
+                "check that it is a SQLite database, and restore it from a backup if not",
+            )?;
+            return d.finish();
+        }
+    };
+
+    let check: String = match conn.query_one("pragma quick_check", [], |row| row.get(0)) {
+        Ok(check) => check,
+        Err(e) => e.to_string(),
+    };
+
+    if check == "ok" {
+        d.ok("database passes `pragma quick_check`")?;
+    } else {
+        d.error(
+            format!("database is corrupt: {}", check),
+            format!(
+                "restore it with `blade restore`, or try `sqlite3 {} .recover`",
+                db_location.display()
+            ),
+        )?;
+        return d.finish();
+    }
+
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    match (version as usize).cmp(&MIGRATIONS.len()) {
+        std::cmp::Ordering::Equal => d.ok(format!("schema is up to date (version {})", version))?,
+        std::cmp::Ordering::Less => d.ok(format!(
+            "schema is at version {}, the next command will migrate it to {}",
+            version,
+            MIGRATIONS.len()
+        ))?,
+        std::cmp::Ordering::Greater => d.error(
+            format!(
+                "schema is at version {}, but this blade only knows version {}",
+                version,
+                MIGRATIONS.len()
+            ),
+            "upgrade blade to the version that last wrote to the database",
+        )?,
+    }
+
+    let journal_mode: String = conn.pragma_query_value(None, "journal_mode", |row| row.get(0))?;
+
+    if journal_mode == "wal" {
+        d.ok("database is in wal mode")?;
+    } else {
+        d.warning(
+            format!("database is in {} mode rather than wal", journal_mode),
+            "run any blade command, which puts it back in wal mode",
+        )?;
+    }
+
+    let wal_size = std::fs::metadata(sidecar("-wal"))
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    // the WAL only grows past the autocheckpoint (4MB by default)
+    // when checkpoints can't finish, usually because of a long-running reader
+    if wal_size > db_size.max(64 * 1024 * 1024) {
+        d.warning(
+            format!(
+                "the WAL is {}, larger than the database",
+                indicatif::HumanBytes(wal_size)
+            ),
+            "stop any long-running readers, then run \
+            `blade sql 'pragma wal_checkpoint(truncate)' --write`",
+        )?;
+    } else {
+        d.ok(format!("the WAL is {}", indicatif::HumanBytes(wal_size)))?;
+    }
+
+    // a new connection doesn't wait on a busy database
+    match conn.execute_batch("begin immediate; rollback;") {
+        Ok(()) => d.ok("no other process is writing to the database")?,
+        Err(e)
+            if matches!(
+                e.sqlite_error_code(),
+                Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+            ) =>
+        {
+            d.warning(
+                "another process holds the write lock",
+                format!(
+                    "wait for it to finish, or stop it. Writes wait up to \
+                    `sqlite_busy_timeout_ms` ({}ms) for the lock, \
+                    and `busy_retry` in the config file retries them after that",
+                    config.sqlite_busy_timeout_ms
+                ),
+            )?;
+        }
+        Err(e) => Err(e)?,
+    }
+
+    d.finish()
+}
+
+fn attach_db(conn: &Connection, db_location: &Path, alias: &str) -> anyhow::Result<()> {
+    if !db_location.exists() {
+        return Err(BladeError::Usage(format!(
+            "database does not exist: {}",
+            db_location.display()
+        ))
+        .into());
+    }
+
+    conn.execute(
+        "attach database ? as ?",
+        params![db_location.to_string_lossy(), alias],
+    )?;
+
+    Ok(())
+}
+
+/// The database file the keys in `namespaces` are kept in,
+/// if they're attached or sharded rather than in `db_location`.
+/// They can't be split between databases
+fn namespace_db_location(
+    conn: &Connection,
+    config: &Config,
+    db_location: &Path,
+    namespaces: &[String],
+) -> anyhow::Result<Option<PathBuf>> {
+    let mut q = conn.prepare(
+        "
+    select db_location
+    from attachments
+    where namespace = ?
+    ",
+    )?;
+
+    let mut db_locations = vec![];
+
+    for namespace in namespaces {
+        let attached: Option<String> = q.query_one([namespace], |row| row.get(0)).optional()?;
+
+        let namespace_db_location = match attached {
+            Some(attached) => Some(PathBuf::from(attached)),
+            None if config.shard_by_namespace => {
+                Some(shards_dir(db_location).join(safe_file_name(namespace) + ".db"))
+            }
+            None => None,
+        };
+
+        db_locations.push(namespace_db_location);
+    }
+
+    db_locations.sort();
+    db_locations.dedup();
+
+    match db_locations.as_slice() {
+        [] | [None] => Ok(None),
+        [Some(db_location)] => Ok(Some(db_location.clone())),
+        _ => Err(BladeError::Usage(
+            "keys in attached or sharded namespaces can't be used with keys in other databases"
+                .to_string(),
+        )
+        .into()),
+    }
+}
+
+/// The directory `shard_by_namespace` keeps namespaces' databases in
+fn shards_dir(db_location: &Path) -> PathBuf {
+    let mut shards = db_location.as_os_str().to_owned();
+    shards.push(".shards");
+    PathBuf::from(shards)
+}
+
+/// Opens the database a namespace is kept in, creating it if needed
+fn open_namespace_db(db_location: &Path, config: &Config) -> anyhow::Result<Connection> {
+    if let Some(dir) = db_location.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    migrate_db(open_or_create_db(db_location, config)?)
+}
+
+/// The databases other than `db_location` that namespaces are kept in:
+/// attached ones, and with `shard_by_namespace`, every namespace's
+fn other_db_locations(
+    conn: &Connection,
+    config: &Config,
+    db_location: &Path,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut locations = conn
+        .prepare(
+            "
+    select db_location
+    from attachments
+    ",
+        )?
+        .query_map([], |row| Ok(PathBuf::from(row.get::<_, String>(0)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    if config.shard_by_namespace
+        && let Ok(shards) = std::fs::read_dir(shards_dir(db_location))
+    {
+        for shard in shards {
+            let shard = shard?.path();
+
+            if shard.extension().is_some_and(|extension| extension == "db") {
+                locations.push(shard);
+            }
+        }
+    }
+
+    locations.sort();
+    locations.dedup();
+
+    Ok(locations)
+}
+
+/// Whether a namespace has keys, or aliases in or pointing into it
+fn namespace_in_use(conn: &Connection, namespace: &str) -> rusqlite::Result<bool> {
+    conn.query_one(
+        "
+    select
+        exists(select 1 from entries where namespace = ?1)
+        or exists(
+            select 1
+            from aliases
+            where namespace = ?1 or target_namespace = ?1
+        )
+    ",
+        [namespace],
+        |row| row.get(0),
+    )
+}
+
+/// Refuses a command that spans the namespaces matching `pattern`
+/// when some of them are kept in other databases, which it would miss
+fn check_spans_one_db(conn: &Connection, config: &Config, pattern: &str) -> anyhow::Result<()> {
+    if config.shard_by_namespace {
+        return Err(BladeError::Usage(format!(
+            "shard_by_namespace keeps each namespace in its own database, \
+            so this command can't use every namespace matching {} at once",
+            pattern
+        ))
+        .into());
+    }
+
+    let attached: Option<String> = conn
+        .query_one(
+            "
+        select namespace
+        from attachments
+        where namespace glob ?
+        order by namespace
+        limit 1
+        ",
+            [namespace_glob(pattern)],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    if let Some(attached) = attached {
+        return Err(BladeError::Usage(format!(
+            "{} is attached to another database, \
+            so this command can't use every namespace matching {} at once",
+            attached, pattern
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Where `backup` writes a snapshot to and `restore` reads one from
+enum BackupLocation {
+    File(PathBuf),
+    /// Written with `PUT` and read with `GET`
+    Http(String),
+    S3 {
+        bucket: String,
+        key: String,
+    },
+}
+
+fn backup_location(url: &str) -> anyhow::Result<BackupLocation> {
+    match url.split_once("://") {
+        None => Ok(BackupLocation::File(PathBuf::from(url))),
+        Some(("file", path)) => Ok(BackupLocation::File(PathBuf::from(path))),
+        Some(("http" | "https", _)) => Ok(BackupLocation::Http(url.to_string())),
+        Some(("s3", path)) => {
+            let (bucket, key) = path
+                .split_once('/')
+                .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+                .ok_or_else(|| {
+                    BladeError::Usage(format!(
+                        "s3 locations are like `s3://bucket/path/to/blade.db`: {}",
+                        url
+                    ))
+                })?;
+
+            Ok(BackupLocation::S3 {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            })
+        }
+        Some((scheme, _)) => {
+            Err(BladeError::Usage(format!("unsupported backup location scheme: {}", scheme)).into())
+        }
+    }
+}
+
+impl BackupLocation {
+    /// Streams the snapshot at `snapshot` here. Remote uploads are sent with
+    /// `If-None-Match: *`, so a backup that's already there is never replaced
+    fn upload(&self, conn: &Connection, snapshot: &Path) -> anyhow::Result<()> {
+        let response = match self {
+            BackupLocation::File(path) => {
+                std::fs::copy(snapshot, path)?;
+                return Ok(());
+            }
+            BackupLocation::Http(url) => ureq::put(url)
+                .header("if-none-match", "*")
+                .send(std::fs::File::open(snapshot)?),
+            BackupLocation::S3 { bucket, key } => {
+                let mut file = std::fs::File::open(snapshot)?;
+                let mut hasher = Sha256::new();
+                std::io::copy(&mut file, &mut hasher)?;
+                let payload_hash = format!("{:x}", hasher.finalize());
+
+                ureq::run(
+                    s3_request(conn, "PUT", bucket, key, &payload_hash)?
+                        .body(std::fs::File::open(snapshot)?)?,
+                )
+            }
+        };
+
+        match response {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::StatusCode(412)) => {
+                Err(BladeError::Usage(format!("backup location already exists: {}", self)).into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Streams the backup here into a new file at `snapshot`
+    fn download(&self, conn: &Connection, snapshot: &Path) -> anyhow::Result<()> {
+        let response = match self {
+            BackupLocation::File(path) => {
+                std::fs::copy(path, snapshot)?;
+                return Ok(());
+            }
+            BackupLocation::Http(url) => ureq::get(url).call(),
+            BackupLocation::S3 { bucket, key } => {
+                ureq::run(s3_request(conn, "GET", bucket, key, EMPTY_PAYLOAD_SHA256)?.body(())?)
+            }
+        };
+
+        let response = match response {
+            Err(ureq::Error::StatusCode(404)) => {
+                return Err(BladeError::Usage(format!("backup does not exist: {}", self)).into());
+            }
+            response => response?,
+        };
+
+        std::io::copy(
+            &mut response.into_body().into_reader(),
+            &mut std::fs::File::create_new(snapshot)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl Display for BackupLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupLocation::File(path) => write!(f, "{}", path.display()),
+            BackupLocation::Http(url) => write!(f, "{}", url),
+            BackupLocation::S3 { bucket, key } => write!(f, "s3://{}/{}", bucket, key),
+        }
+    }
+}
+
+/// The SHA-256 of an empty body, for S3 requests without one
+const EMPTY_PAYLOAD_SHA256: &str =
+    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// A request for an S3 object, signed with AWS Signature Version 4 using
+/// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, and `AWS_SESSION_TOKEN`, if it's set.
+/// `AWS_ENDPOINT_URL` points it at another S3-compatible store, like MinIO or R2.
+/// `PUT`s only create objects, failing with 412 if the key is already there
+fn s3_request(
+    conn: &Connection,
+    method: &str,
+    bucket: &str,
+    key: &str,
+    payload_hash: &str,
+) -> anyhow::Result<ureq::http::request::Builder> {
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+    let missing_credentials = || {
+        BladeError::Usage(
+            "set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY to use s3:// locations".to_string(),
+        )
+    };
+    let access_key_id = env("AWS_ACCESS_KEY_ID").ok_or_else(missing_credentials)?;
+    let secret_access_key = env("AWS_SECRET_ACCESS_KEY").ok_or_else(missing_credentials)?;
+
+    let region = env("AWS_REGION")
+        .or_else(|| env("AWS_DEFAULT_REGION"))
+        .unwrap_or_else(|| "us-east-1".to_string());
+
+    let (url, host, path) = match env("AWS_ENDPOINT_URL") {
+        // other stores are addressed by path, like `http://localhost:9000/bucket/key`
+        Some(endpoint) => {
+            let endpoint = endpoint.trim_end_matches('/');
+            let host = endpoint
+                .split_once("://")
+                .map_or(endpoint, |(_, rest)| rest)
+                .split('/')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            let path = format!("/{}/{}", escape_url_path(bucket), escape_url_path(key));
+            (format!("{}{}", endpoint, path), host, path)
+        }
+        None => {
+            let host = format!("{}.s3.{}.amazonaws.com", bucket, region);
+            let path = format!("/{}", escape_url_path(key));
+            (format!("https://{}{}", host, path), host, path)
+        }
+    };
+
+    let timestamp: String =
+        conn.query_one("select strftime('%Y%m%dT%H%M%SZ', 'NOW')", [], |row| {
+            row.get(0)
+        })?;
+    let date = &timestamp[..8];
+
+    // signed headers are in name order
+    let mut headers = vec![("host", host)];
+    if method == "PUT" {
+        headers.push(("if-none-match", "*".to_string()));
+    }
+    headers.push(("x-amz-content-sha256", payload_hash.to_string()));
+    headers.push(("x-amz-date", timestamp.clone()));
+    if let Some(session_token) = env("AWS_SESSION_TOKEN") {
+        headers.push(("x-amz-security-token", session_token));
+    }
+
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        method,
+        path,
+        headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value))
+            .collect::<String>(),
+        signed_headers,
+        payload_hash
+    );
+
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+        timestamp,
+        scope,
+        Sha256::digest(canonical_request)
+    );
+
+    let hmac = |key: &[u8], data: &str| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+        mac.update(data.as_bytes());
+        mac.finalize().into_bytes()
+    };
+
+    let signing_key = hmac(format!("AWS4{}", secret_access_key).as_bytes(), date);
+    let signing_key = hmac(&signing_key, &region);
+    let signing_key = hmac(&signing_key, "s3");
+    let signing_key = hmac(&signing_key, "aws4_request");
+
+    let signature = format!("{:x}", hmac(&signing_key, &string_to_sign));
+
+    debug!(method, url, "s3 request");
+
+    let mut request = ureq::http::Request::builder()
+        .method(method)
+        .uri(&url)
+        .header(
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                access_key_id, scope, signed_headers, signature
+            ),
+        );
+
+    // ureq sets `host` from the URL
+    for (name, value) in &headers[1..] {
+        request = request.header(*name, value);
+    }
+
+    Ok(request)
+}
+
+/// Shows progress on stderr, unless stderr is not a terminal
+fn show_progress(progress: ProgressBar, template: &str, message: &'static str) -> ProgressBar {
+    if !std::io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+
+    let progress = progress
+        .with_style(ProgressStyle::with_template(template).expect("valid progress template"))
+        .with_message(message);
+
+    progress.enable_steady_tick(Duration::from_millis(100));
+
+    progress
+}
+
+/// A progress bar for operations with a known number of steps
+fn progress_bar(message: &'static str, len: u64) -> ProgressBar {
+    show_progress(
+        ProgressBar::new(len),
+        "{msg} [{bar:40}] {pos}/{len} ({per_sec}, {eta})",
+        message,
+    )
+}
+
+/// A running count for operations with an unknown number of steps
+fn progress_counter(message: &'static str) -> ProgressBar {
+    show_progress(
+        ProgressBar::new_spinner(),
+        "{spinner} {msg} {pos} ({per_sec})",
+        message,
+    )
+}
+
+/// A spinner for single operations that can take a while
+fn progress_spinner(message: &'static str) -> ProgressBar {
+    show_progress(
+        ProgressBar::new_spinner(),
+        "{spinner} {msg} ({elapsed})",
+        message,
+    )
+}
+
+/// The token for `list --cursor` to continue after an entry.
+/// It's opaque so that what's in it can change
+fn encode_list_cursor(namespace: &str, key: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(serde_json::json!([namespace, key]).to_string())
+}
+
+fn decode_list_cursor(cursor: &str) -> anyhow::Result<(String, String)> {
+    BASE64_URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or_else(|| BladeError::Usage(format!("invalid cursor: {}", cursor)).into())
+}
+
+/// Writes a value as-is, unless it is binary and we're writing to a terminal
+fn write_value(out: &mut impl Write, value: &[u8], is_terminal: bool) -> std::io::Result<()> {
+    if is_terminal && std::str::from_utf8(value).is_err() {
+        write!(out, "binary data ({} bytes)", value.len())
+    } else {
+        out.write_all(value)
+    }
+}
+
+/// Binary data can garble a terminal, or set its title or clipboard
+fn check_not_binary(namespaced_key: &str, value: &[u8]) -> anyhow::Result<()> {
+    if std::str::from_utf8(value).is_err() {
+        return Err(BladeError::Usage(format!(
+            "{} is binary data ({} bytes). Pass --binary-ok to print it to a terminal anyway",
+            namespaced_key,
+            value.len()
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Writes a column from a query result, writing nothing for `null`
+fn write_sql_value(
+    out: &mut impl Write,
+    value: ValueRef,
+    is_terminal: bool,
+) -> std::io::Result<()> {
+    match value {
+        ValueRef::Null => Ok(()),
+        ValueRef::Integer(n) => write!(out, "{}", n),
+        ValueRef::Real(f) => write!(out, "{}", f),
+        ValueRef::Text(t) | ValueRef::Blob(t) => write_value(out, t, is_terminal),
+    }
+}
+
+fn describe_value(value: &[u8]) -> String {
+    match std::str::from_utf8(value) {
+        Ok(s) => s.to_string(),
+        Err(_) => format!("binary data ({} bytes)", value.len()),
+    }
+}
+
+fn prompt_take_theirs(
+    namespace: &str,
+    key: &str,
+    ours: &[u8],
+    theirs: &[u8],
+) -> anyhow::Result<bool> {
+    let mut err = std::io::stderr();
+
+    writeln!(err, "conflict: {}@{}", key, namespace)?;
+    writeln!(err, "  ours:   {}", describe_value(ours))?;
+    writeln!(err, "  theirs: {}", describe_value(theirs))?;
+
+    loop {
+        write!(err, "keep [o]urs or take [t]heirs? ")?;
+        err.flush()?;
+
+        let mut answer = String::new();
+
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Err(anyhow!(
+                "no answer given for conflict on {}@{}",
+                key,
+                namespace
+            ));
+        }
+
+        match answer.trim() {
+            "o" | "ours" => return Ok(false),
+            "t" | "theirs" => return Ok(true),
+            _ => (),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportedEntry {
+    namespace: String,
+    key: String,
+    /// Set when the value is valid UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    /// Set when the value is binary
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value_base64: Option<String>,
+    inserted_at: String,
+    updated_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+impl ExportedEntry {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        namespace: String,
+        key: String,
+        value: Vec<u8>,
+        inserted_at: String,
+        updated_at: String,
+        content_type: Option<String>,
+        expires_at: Option<String>,
+        note: Option<String>,
+    ) -> Self {
+        let (value, value_base64) = match String::from_utf8(value) {
+            Ok(value) => (Some(value), None),
+            Err(e) => (None, Some(BASE64_STANDARD.encode(e.as_bytes()))),
+        };
+
+        Self {
+            namespace,
+            key,
+            value,
+            value_base64,
+            inserted_at,
+            updated_at,
+            content_type,
+            expires_at,
+            note,
+        }
+    }
+
+    fn value_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        match (&self.value, &self.value_base64) {
+            (Some(value), None) => Ok(value.as_bytes().to_vec()),
+            (None, Some(value_base64)) => Ok(BASE64_STANDARD.decode(value_base64)?),
+            _ => Err(anyhow!(
+                "entry {}@{} must have exactly one of value or value_base64",
+                self.key,
+                self.namespace
+            )),
+        }
+    }
+}
+
+/// Prefix for csv/tsv values that are base64-encoded,
+/// either because they are binary or because they already start with the prefix
+const CSV_BASE64_PREFIX: &str = "base64:";
+
+/// Fails unless `since` is a timestamp SQLite understands
+fn validate_since(conn: &Connection, since: Option<&str>) -> anyhow::Result<()> {
+    if let Some(since) = since {
+        let is_valid: bool =
+            conn.query_one("select julianday(?) is not null", [since], |row| row.get(0))?;
+
+        if !is_valid {
+            return Err(BladeError::Usage(format!(
+                "invalid time: {}. Expected a timestamp like `2024-01-01`, or a duration like `2w`",
+                since
+            ))
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// `--since` as a timestamp. A duration, like `2w`, is that long ago
+fn since_timestamp(conn: &Connection, since: &str) -> anyhow::Result<String> {
+    match parse_duration(since) {
+        Ok(duration) => Ok(conn.query_one(
+            "select strftime('%Y-%m-%d %H:%M:%f', 'NOW', ?)",
+            [format!("-{} seconds", duration.as_secs_f64())],
+            |row| row.get(0),
+        )?),
+        // `validate_since` says what's wrong with it
+        Err(_) => Ok(since.to_string()),
+    }
+}
+
+/// Calls `f` with each entry to export, ordered by namespace and key, showing progress
+fn for_each_exported_entry(
+    conn: &Connection,
+    namespace: Option<&str>,
+    since: Option<&str>,
+    mut f: impl FnMut(ExportedEntry) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let namespace = namespace.map(namespace_glob);
+
+    // times are all stored in one format, so comparing them as strings
+    // is exact and can use the `updated_at` index
+    let count: i64 = conn.query_one(
+        "
+    select
+        count(*)
+    from entries
+    where (?1 is null or namespace glob ?1)
+    and updated_at >= coalesce(strftime('%Y-%m-%d %H:%M:%f', ?2), '')
+    and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+    ",
+        params![namespace, since],
+        |row| row.get(0),
+    )?;
+
+    let mut q = conn.prepare(
+        "
+    select
+        namespace,
+        key,
+        value,
+        inserted_at,
+        updated_at,
+        content_type,
+        expires_at,
+        note
+    from entries
+    where (?1 is null or namespace glob ?1)
+    and updated_at >= coalesce(strftime('%Y-%m-%d %H:%M:%f', ?2), '')
+    and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+    order by namespace, key
+    ",
+    )?;
+
+    let progress = progress_bar("exporting", count as u64);
+
+    let rows = progress.wrap_iter(q.query_map(params![namespace, since], |row| {
+        Ok(ExportedEntry::new(
+            row.get(0)?,
+            row.get(1)?,
+            row.get(2)?,
+            row.get(3)?,
+            row.get(4)?,
+            row.get(5)?,
+            row.get(6)?,
+            row.get(7)?,
+        ))
+    })?);
+
+    for row in rows {
+        f(row?)?;
+    }
+
+    progress.finish_and_clear();
+
+    debug!(rows = count, "exported entries");
+
+    Ok(())
+}
+
+/// Writes a namespace as a Kubernetes Secret, or ConfigMap, manifest.
+/// Keys and values are quoted as JSON strings, which YAML reads the same way
+fn export_k8s_manifest(
+    conn: &Connection,
+    namespace: &str,
+    since: Option<&str>,
+    config_map: bool,
+    name: &str,
+    k8s_namespace: Option<&str>,
+    out: &mut dyn Write,
+) -> anyhow::Result<()> {
+    validate_since(conn, since)?;
+
+    if namespace.contains('*') {
+        return Err(BladeError::Usage(
+            "a manifest holds one namespace, so --namespace can't have a `*`".to_string(),
+        )
+        .into());
+    }
+
+    let mut data = vec![];
+    let mut binary_data = vec![];
+
+    for_each_exported_entry(conn, Some(namespace), since, |entry| {
+        if entry.key.is_empty()
+            || !entry
+                .key
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-._".contains(&b))
+        {
+            return Err(BladeError::Usage(format!(
+                "{} can't be a Kubernetes key, which can only have letters, digits, `-`, `_`, and `.`",
+                entry.key
+            ))
+            .into());
+        }
+
+        let key = serde_json::to_string(&entry.key)?;
+
+        match (config_map, &entry.value) {
+            (true, Some(value)) => data.push((key, serde_json::to_string(value)?)),
+            (true, None) => binary_data.push((key, format!("\"{}\"", entry.value_base64.unwrap()))),
+            (false, _) => data.push((
+                key,
+                format!("\"{}\"", BASE64_STANDARD.encode(entry.value_bytes()?)),
+            )),
+        }
+
+        Ok(())
+    })?;
+
+    writeln!(out, "apiVersion: v1")?;
+    writeln!(
+        out,
+        "kind: {}",
+        if config_map { "ConfigMap" } else { "Secret" }
+    )?;
+    writeln!(out, "metadata:")?;
+    writeln!(out, "  name: {}", serde_json::to_string(name)?)?;
+    if let Some(k8s_namespace) = k8s_namespace {
+        writeln!(
+            out,
+            "  namespace: {}",
+            serde_json::to_string(k8s_namespace)?
+        )?;
+    }
+    if !config_map {
+        writeln!(out, "type: Opaque")?;
+    }
+
+    for (field, pairs) in [("data", data), ("binaryData", binary_data)] {
+        if pairs.is_empty() {
+            continue;
+        }
+
+        writeln!(out, "{}:", field)?;
+        for (key, value) in pairs {
+            writeln!(out, "  {}: {}", key, value)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn export_entries(
+    conn: &Connection,
+    namespace: Option<&str>,
+    since: Option<&str>,
+    format: ExportFormat,
+    out: &mut dyn Write,
+) -> anyhow::Result<()> {
+    validate_since(conn, since)?;
+
+    match format {
+        ExportFormat::Jsonl => for_each_exported_entry(conn, namespace, since, |entry| {
+            serde_json::to_writer(&mut *out, &entry)?;
+            out.write_all(b"\n")?;
+            Ok(())
+        }),
+        ExportFormat::Csv | ExportFormat::Tsv => {
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(format.csv_delimiter())
+                .from_writer(out);
+
+            writer.write_record(["key", "value", "namespace", "inserted_at", "updated_at"])?;
+
+            for_each_exported_entry(conn, namespace, since, |entry| {
+                let value = match &entry.value {
+                    Some(value) if !value.starts_with(CSV_BASE64_PREFIX) => value.clone(),
+                    _ => format!(
+                        "{}{}",
+                        CSV_BASE64_PREFIX,
+                        BASE64_STANDARD.encode(entry.value_bytes()?)
+                    ),
+                };
+
+                writer.write_record([
+                    &entry.key,
+                    &value,
+                    &entry.namespace,
+                    &entry.inserted_at,
+                    &entry.updated_at,
+                ])?;
+
+                Ok(())
+            })?;
+
+            writer.flush()?;
+
+            Ok(())
+        }
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// A file name for a key's page, made of characters that are safe in paths and URLs
+fn html_page_name(entry: &ExportedEntry) -> String {
+    format!(
+        "{}@{}.html",
+        safe_file_name(&entry.key),
+        safe_file_name(&entry.namespace)
+    )
+}
+
+/// `name` with anything but ASCII letters, digits, `-`, and `.` escaped as `_XX`
+fn safe_file_name(name: &str) -> String {
+    let mut safe = String::new();
+
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'.' {
+            safe.push(byte as char);
+        } else {
+            safe.push_str(&format!("_{:02X}", byte));
+        }
+    }
+
+    safe
+}
+
+/// Lists the entries in a `--git-dir` export, one JSON object per line,
+/// in the same order as their files
+const GIT_MANIFEST: &str = "manifest.jsonl";
+
+#[derive(Serialize, Deserialize)]
+struct GitManifestEntry {
+    namespace: String,
+    key: String,
+    /// Relative to the export's directory
+    file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+/// `safe_file_name`, with a leading `.` escaped too,
+/// so that no file is hidden or clashes with `.git`
+fn git_file_name(name: &str) -> String {
+    let safe = safe_file_name(name);
+
+    match safe.strip_prefix('.') {
+        Some(rest) => format!("_2E{}", rest),
+        None => safe,
+    }
+}
+
+fn read_git_manifest(dir: &Path) -> anyhow::Result<Vec<GitManifestEntry>> {
+    let manifest_path = dir.join(GIT_MANIFEST);
+
+    let manifest = std::fs::read_to_string(&manifest_path)
+        .with_context(|| format!("could not read {}", manifest_path.display()))?;
+
+    manifest
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let entry: GitManifestEntry = serde_json::from_str(line)?;
+
+            // don't follow a manifest out of its directory
+            if !Path::new(&entry.file)
+                .components()
+                .all(|component| matches!(component, std::path::Component::Normal(_)))
+            {
+                return Err(BladeError::Usage(format!(
+                    "{} lists a file outside of {}: {}",
+                    GIT_MANIFEST,
+                    dir.display(),
+                    entry.file
+                ))
+                .into());
+            }
+
+            Ok(entry)
+        })
+        .collect()
+}
+
+/// Writes each value to `<dir>/<namespace>/<key>`, and lists them in the manifest.
+/// Timestamps are left out, so that files only change when values do.
+/// Entries from an earlier export are kept, unless they're in `namespace`
+/// and their keys are gone, so that exporting a namespace, or only what changed `since`,
+/// updates an export without losing the rest of it
+fn export_git_dir(
+    conn: &Connection,
+    namespace: Option<&str>,
+    since: Option<&str>,
+    dir: &Path,
+) -> anyhow::Result<()> {
+    validate_since(conn, since)?;
+
+    std::fs::create_dir_all(dir).with_context(|| format!("could not create {}", dir.display()))?;
+
+    let mut earlier: BTreeMap<(String, String), GitManifestEntry> =
+        if dir.join(GIT_MANIFEST).exists() {
+            read_git_manifest(dir)?
+                .into_iter()
+                .map(|entry| ((entry.namespace.clone(), entry.key.clone()), entry))
+                .collect()
+        } else {
+            Default::default()
+        };
+
+    let mut exported = BTreeMap::new();
+
+    for_each_exported_entry(conn, namespace, since, |entry| {
+        let file = format!(
+            "{}/{}",
+            git_file_name(&entry.namespace),
+            git_file_name(&entry.key)
+        );
+
+        let path = dir.join(&file);
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        write_file_atomically(&path, &entry.value_bytes()?)?;
+
+        earlier.remove(&(entry.namespace.clone(), entry.key.clone()));
+
+        exported.insert(
+            (entry.namespace.clone(), entry.key.clone()),
+            GitManifestEntry {
+                namespace: entry.namespace,
+                key: entry.key,
+                file,
+                content_type: entry.content_type,
+                note: entry.note,
+            },
+        );
+
+        Ok(())
+    })?;
+
+    let mut is_stale_q = conn.prepare(
+        "
+    select
+        (?3 is null or ?1 glob ?3)
+        and not exists (
+            select 1
+            from entries
+            where namespace = ?1
+            and key = ?2
+            and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        )
+    ",
+    )?;
+
+    for (id, entry) in earlier {
+        let is_stale: bool = is_stale_q.query_one(
+            params![entry.namespace, entry.key, namespace.map(namespace_glob)],
+            |row| row.get(0),
+        )?;
+
+        if !is_stale {
+            exported.insert(id, entry);
+            continue;
+        }
+
+        let path = dir.join(&entry.file);
+
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => Err(e).with_context(|| format!("could not remove {}", path.display()))?,
+        }
+
+        // only succeeds once the namespace's directory is empty
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::remove_dir(parent);
+        }
+    }
+
+    let mut manifest = vec![];
+
+    for entry in exported.values() {
+        serde_json::to_writer(&mut manifest, entry)?;
+        manifest.write_all(b"\n")?;
+    }
+
+    write_file_atomically(&dir.join(GIT_MANIFEST), &manifest)?;
+
+    Ok(())
+}
+
+fn read_git_dir_entries(dir: &Path) -> anyhow::Result<Vec<ImportedEntry>> {
+    read_git_manifest(dir)?
+        .into_iter()
+        .map(|entry| {
+            let path = dir.join(&entry.file);
+
+            Ok(ImportedEntry {
+                value: std::fs::read(&path)
+                    .with_context(|| format!("could not read {}", path.display()))?,
+                namespace: entry.namespace,
+                key: entry.key,
+                inserted_at: None,
+                updated_at: None,
+                content_type: entry.content_type,
+                ttl: None,
+                expires_at: None,
+                note: entry.note,
+            })
+        })
+        .collect()
+}
+
+/// For `~/.config/direnv/direnvrc`
+const DIRENV_STDLIB: &str = r#"# `use blade <namespace>` in an .envrc exports the namespace's keys, from blade direnv --stdlib
+use_blade() {
+  eval "$(blade direnv "$1")"
+}
+"#;
+
+const HTML_HEADER: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{title}</title>
+<style>
+body { max-width: 48rem; margin: 2rem auto; padding: 0 1rem; font-family: sans-serif; line-height: 1.5; }
+pre { overflow-x: auto; padding: 0.5rem; background: #f4f4f4; }
+.meta { color: #666; font-size: 0.9rem; }
+</style>
+</head>
+<body>
+";
+
+const HTML_FOOTER: &str = "</body>
+</html>
+";
+
+/// Writes a read-only site to `dir`: an `index.html` linking to one page per key,
+/// with text values rendered as markdown
+fn export_html(
+    conn: &Connection,
+    namespace: Option<&str>,
+    since: Option<&str>,
+    dir: &Path,
+) -> anyhow::Result<()> {
+    validate_since(conn, since)?;
+
+    std::fs::create_dir_all(dir).with_context(|| format!("could not create {}", dir.display()))?;
+
+    let mut index = HTML_HEADER.replace("{title}", "blade");
+    index.push_str("<h1>blade</h1>\n");
+
+    let mut current_namespace = None;
+
+    for_each_exported_entry(conn, namespace, since, |entry| {
+        let title = html_escape(&format!("{}@{}", entry.key, entry.namespace));
+        let page_name = html_page_name(&entry);
+
+        if current_namespace.as_ref() != Some(&entry.namespace) {
+            if current_namespace.is_some() {
+                index.push_str("</ul>\n");
+            }
+
+            index.push_str(&format!(
+                "<h2>{}</h2>\n<ul>\n",
+                html_escape(&entry.namespace)
+            ));
+
+            current_namespace = Some(entry.namespace.clone());
+        }
+
+        index.push_str(&format!(
+            "<li><a href=\"{}\">{}</a> <span class=\"meta\">{}</span></li>\n",
+            page_name,
+            html_escape(&entry.key),
+            entry.updated_at
+        ));
+
+        let mut page = HTML_HEADER.replace("{title}", &title);
+
+        page.push_str(&format!(
+            "<p><a href=\"index.html\">index</a></p>\n<h1>{}</h1>\n\
+            <p class=\"meta\">created {}, updated {}</p>\n",
+            title, entry.inserted_at, entry.updated_at
+        ));
+
+        match &entry.value {
+            Some(value) => {
+                // values are shown as written, so HTML in them is escaped rather than run
+                let events = pulldown_cmark::Parser::new_ext(value, pulldown_cmark::Options::all())
+                    .map(|event| match event {
+                        pulldown_cmark::Event::Html(html)
+                        | pulldown_cmark::Event::InlineHtml(html) => {
+                            pulldown_cmark::Event::Text(html)
+                        }
+                        event => event,
+                    });
+
+                pulldown_cmark::html::push_html(&mut page, events);
+            }
+            None => page.push_str(&format!(
+                "<p>binary data ({} bytes)</p>\n",
+                entry.value_bytes()?.len()
+            )),
+        }
+
+        page.push_str(HTML_FOOTER);
+
+        std::fs::write(dir.join(&page_name), page)?;
+
+        Ok(())
+    })?;
+
+    if current_namespace.is_some() {
+        index.push_str("</ul>\n");
+    }
+
+    index.push_str(HTML_FOOTER);
+
+    std::fs::write(dir.join("index.html"), index)?;
+
+    Ok(())
+}
+
+struct ImportedEntry {
+    namespace: String,
+    key: String,
+    value: Vec<u8>,
+    inserted_at: Option<String>,
+    updated_at: Option<String>,
+    content_type: Option<String>,
+    /// Seconds until it expires
+    ttl: Option<f64>,
+    /// When it expires, for entries exported from blade
+    expires_at: Option<String>,
+    note: Option<String>,
+}
+
+fn read_jsonl_entries(
+    input: &mut dyn BufRead,
+) -> impl Iterator<Item = anyhow::Result<ImportedEntry>> {
+    input
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| {
+            let entry: ExportedEntry = serde_json::from_str(&line?)?;
+
+            Ok(ImportedEntry {
+                value: entry.value_bytes()?,
+                namespace: entry.namespace,
+                key: entry.key,
+                inserted_at: Some(entry.inserted_at),
+                updated_at: Some(entry.updated_at),
+                content_type: entry.content_type,
+                ttl: None,
+                expires_at: entry.expires_at,
+                note: entry.note,
+            })
+        })
+}
+
+fn read_csv_entries<'a>(
+    input: &'a mut dyn BufRead,
+    format: ExportFormat,
+    options: &'a CsvImportOptions,
+) -> impl Iterator<Item = anyhow::Result<ImportedEntry>> + 'a {
+    let reader = csv::ReaderBuilder::new()
+        .delimiter(format.csv_delimiter())
+        .has_headers(!options.no_header)
+        .flexible(true)
+        .from_reader(input);
+
+    reader.into_records().map(move |record| {
+        let record = record?;
+
+        let column = |i: usize| {
+            record.get(i).ok_or_else(|| {
+                BladeError::Usage(format!(
+                    "row {} has no column {}",
+                    record.position().map(|p| p.line()).unwrap_or_default(),
+                    i
+                ))
+            })
+        };
+
+        let value = column(options.value_column)?;
+
+        let value = match value.strip_prefix(CSV_BASE64_PREFIX) {
+            Some(encoded) => BASE64_STANDARD.decode(encoded)?,
+            None => value.as_bytes().to_vec(),
+        };
+
+        let namespace = match options.namespace_column {
+            Some(i) => column(i)?.to_string(),
+            None => options
+                .namespace
+                .clone()
+                .unwrap_or_else(|| default_namespace().to_string()),
+        };
+
+        Ok(ImportedEntry {
+            namespace,
+            key: column(options.key_column)?.to_string(),
+            value,
+            inserted_at: None,
+            updated_at: None,
+            content_type: None,
+            ttl: None,
+            expires_at: None,
+            note: None,
+        })
+    })
+}
+
+/// The string keys in a Redis database matching `pattern`, with their TTLs.
+/// Keys of other types are skipped with a warning
+fn read_redis_entries(
+    url: &str,
+    pattern: &str,
+    namespace: &str,
+) -> anyhow::Result<Vec<ImportedEntry>> {
+    let client = redis::Client::open(url).with_context(|| format!("invalid redis url: {}", url))?;
+    let mut conn = client
+        .get_connection()
+        .with_context(|| format!("could not connect to {}", url))?;
+
+    let progress = progress_counter("scanning");
+
+    let mut entries = vec![];
+    let mut cursor = 0;
+
+    // SCAN rather than KEYS, which blocks the server until it's done
+    loop {
+        let (next_cursor, keys): (u64, Vec<Vec<u8>>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(1000)
+            .query(&mut conn)?;
+
+        let mut types = redis::pipe();
+        for key in &keys {
+            types.cmd("TYPE").arg(key);
+        }
+        let types: Vec<String> = types.query(&mut conn)?;
+
+        let mut strings = vec![];
+
+        for (key, key_type) in keys.into_iter().zip(types) {
+            match String::from_utf8(key) {
+                Ok(key) if key_type == "string" => strings.push(key),
+                Ok(key) => warn!(
+                    "skipping {}, which is a {} rather than a string",
+                    key, key_type
+                ),
+                Err(e) => warn!(
+                    "skipping {}, which is not UTF-8",
+                    String::from_utf8_lossy(e.as_bytes())
+                ),
+            }
+        }
+
+        let mut values = redis::pipe();
+        for key in &strings {
+            values.cmd("GET").arg(key).cmd("PTTL").arg(key);
+        }
+        let values: Vec<(Option<Vec<u8>>, i64)> = values.query(&mut conn)?;
+
+        for (key, (value, pttl)) in strings.into_iter().zip(values) {
+            // it expired or was deleted since the SCAN
+            let Some(value) = value else {
+                continue;
+            };
+
+            progress.inc(1);
+
+            entries.push(ImportedEntry {
+                namespace: namespace.to_string(),
+                key,
+                value,
+                inserted_at: None,
+                updated_at: None,
+                content_type: None,
+                // -1 is no expiry
+                ttl: (pttl >= 0).then(|| pttl as f64 / 1000.0),
+                expires_at: None,
+                note: None,
+            });
+        }
+
+        if next_cursor == 0 {
+            break;
+        }
+
+        cursor = next_cursor;
+    }
+
+    progress.finish_and_clear();
+
+    Ok(entries)
+}
+
+/// A key/value store with an HTTP API that namespaces can be copied to and from.
+/// The path in its URL is a prefix, with keys under `<prefix>/<namespace>/<key>`,
+/// or `<prefix>/<key>` when copying one namespace
+#[derive(Clone, Copy)]
+enum KvStore {
+    /// Like `http://localhost:8500/v1/kv/myapp`
+    Consul,
+    /// Like `http://localhost:2379/myapp`, using the v3 JSON gateway
+    Etcd,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulPair {
+    key: String,
+    /// Base64, and null for folders
+    value: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EtcdRange {
+    #[serde(default)]
+    kvs: Vec<EtcdPair>,
+}
+
+/// Both base64. Empty values are left out
+#[derive(Deserialize)]
+struct EtcdPair {
+    key: String,
+    #[serde(default)]
+    value: String,
+}
+
+impl KvStore {
+    fn from_flags<'a>(consul: Option<&'a str>, etcd: Option<&'a str>) -> Option<(Self, &'a str)> {
+        match (consul, etcd) {
+            (Some(url), _) => Some((KvStore::Consul, url)),
+            (_, Some(url)) => Some((KvStore::Etcd, url)),
+            _ => None,
+        }
+    }
+
+    /// The URL to send requests to, and the prefix of the keys, ending in `/` if not empty
+    fn split_url(self, url: &str) -> anyhow::Result<(String, String)> {
+        let usage = || {
+            BladeError::Usage(match self {
+                KvStore::Consul => format!(
+                    "consul urls look like http://localhost:8500/v1/kv/<prefix>, not: {}",
+                    url
+                ),
+                KvStore::Etcd => format!(
+                    "etcd urls look like http://localhost:2379/<prefix>, not: {}",
+                    url
+                ),
+            })
+        };
+
+        let (scheme, rest) = url.split_once("://").ok_or_else(usage)?;
+        let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+
+        let path = match self {
+            KvStore::Consul => path.strip_prefix("v1/kv").ok_or_else(usage)?,
+            KvStore::Etcd => path,
+        };
+
+        let prefix = match path.trim_matches('/') {
+            "" => String::new(),
+            prefix => format!("{}/", prefix),
+        };
+
+        Ok((format!("{}://{}", scheme, host), prefix))
+    }
+
+    /// Every key under the URL's prefix, without the prefix, and its value
+    fn list(self, url: &str) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+        let (base, prefix) = self.split_url(url)?;
+
+        let pairs = match self {
+            KvStore::Consul => {
+                let url = format!("{}/v1/kv/{}", base, escape_url_path(&prefix));
+
+                debug!(url, "listing consul keys");
+
+                let response = match ureq::get(&url).query("recurse", "true").call() {
+                    // nothing under the prefix
+                    Err(ureq::Error::StatusCode(404)) => return Ok(vec![]),
+                    response => response?,
+                };
+
+                let pairs: Vec<ConsulPair> =
+                    serde_json::from_reader(response.into_body().into_reader())?;
+
+                pairs
+                    .into_iter()
+                    .filter_map(|pair| Some((pair.key, pair.value?)))
+                    .map(|(key, value)| Ok((key, BASE64_STANDARD.decode(value)?)))
+                    .collect::<anyhow::Result<Vec<_>>>()?
+            }
+            KvStore::Etcd => {
+                // every key from the prefix up to, but not including, the prefix with its last byte
+                // incremented, or every key if there is no prefix
+                let (key, range_end) = match prefix.as_bytes().split_last() {
+                    Some((last, rest)) => {
+                        (prefix.as_bytes().to_vec(), [rest, &[last + 1]].concat())
+                    }
+                    None => (vec![0], vec![0]),
+                };
+
+                let request = serde_json::to_vec(&serde_json::json!({
+                    "key": BASE64_STANDARD.encode(key),
+                    "range_end": BASE64_STANDARD.encode(range_end),
+                }))?;
+
+                let url = format!("{}/v3/kv/range", base);
+
+                debug!(url, "listing etcd keys");
+
+                let response = ureq::post(&url)
+                    .content_type("application/json")
+                    .send(&request[..])?;
+
+                let range: EtcdRange = serde_json::from_reader(response.into_body().into_reader())?;
+
+                range
+                    .kvs
+                    .into_iter()
+                    .map(|pair| {
+                        let key = String::from_utf8(BASE64_STANDARD.decode(pair.key)?)?;
+                        Ok((key, BASE64_STANDARD.decode(pair.value)?))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?
+            }
+        };
+
+        Ok(pairs
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let key = key.strip_prefix(&prefix)?;
+                (!key.is_empty()).then(|| (key.to_string(), value))
+            })
+            .collect())
+    }
+
+    /// Sets `key` under the URL's prefix
+    fn put(self, url: &str, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        let (base, prefix) = self.split_url(url)?;
+
+        match self {
+            KvStore::Consul => {
+                ureq::put(&format!(
+                    "{}/v1/kv/{}",
+                    base,
+                    escape_url_path(&format!("{}{}", prefix, key))
+                ))
+                .send(value)?;
+            }
+            KvStore::Etcd => {
+                let request = serde_json::to_vec(&serde_json::json!({
+                    "key": BASE64_STANDARD.encode(format!("{}{}", prefix, key)),
+                    "value": BASE64_STANDARD.encode(value),
+                }))?;
+
+                ureq::post(&format!("{}/v3/kv/put", base))
+                    .content_type("application/json")
+                    .send(&request[..])?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Percent-encodes everything but unreserved characters and `/`
+fn escape_url_path(path: &str) -> String {
+    let mut escaped = String::new();
+
+    for b in path.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            escaped.push(b as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", b));
+        }
+    }
+
+    escaped
+}
+
+/// The keys under the URL's prefix. Without `namespace`,
+/// the first part of each key's path is its namespace
+fn read_kv_store_entries(
+    store: KvStore,
+    url: &str,
+    namespace: Option<&str>,
+) -> anyhow::Result<Vec<ImportedEntry>> {
+    let progress = progress_spinner("listing keys");
+    let pairs = store.list(url)?;
+    progress.finish_and_clear();
+
+    let mut entries = vec![];
+
+    for (path, value) in pairs {
+        let (namespace, key) = match namespace {
+            Some(namespace) => (namespace.to_string(), path),
+            None => match path.split_once('/') {
+                Some((namespace, key)) if !namespace.is_empty() && !key.is_empty() => {
+                    (namespace.to_string(), key.to_string())
+                }
+                _ => {
+                    warn!(
+                        "skipping {}, which has no namespace in its path. Pass --namespace to import it",
+                        path
+                    );
+                    continue;
+                }
+            },
+        };
+
+        entries.push(ImportedEntry {
+            namespace,
+            key,
+            value,
+            inserted_at: None,
+            updated_at: None,
+            content_type: None,
+            ttl: None,
+            expires_at: None,
+            note: None,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Copies the entries to export to the store, under `<namespace>/<key>`,
+/// or just `<key>` when exporting one namespace
+fn export_to_kv_store(
+    conn: &Connection,
+    namespace: Option<&str>,
+    since: Option<&str>,
+    store: KvStore,
+    url: &str,
+) -> anyhow::Result<()> {
+    validate_since(conn, since)?;
+
+    // fail on a bad url before exporting anything
+    store.split_url(url)?;
+
+    let one_namespace = namespace.is_some_and(|namespace| !namespace.contains('*'));
+
+    for_each_exported_entry(conn, namespace, since, |entry| {
+        let value = entry.value_bytes()?;
+
+        let path = if one_namespace {
+            entry.key
+        } else {
+            format!("{}/{}", entry.namespace, entry.key)
+        };
+
+        store
+            .put(url, &path, &value)
+            .with_context(|| format!("could not export {}", path))
+    })
+}
+
+#[derive(Default)]
+struct ImportSummary {
+    created: usize,
+    updated: usize,
+    skipped: usize,
+    /// The namespaces and keys of the entries that were created or updated
+    written: Vec<(String, String)>,
+}
+
+fn import_entries(
+    conn: &mut Connection,
+    config: &Config,
+    db_location: &Path,
+    entries: impl Iterator<Item = anyhow::Result<ImportedEntry>>,
+    on_conflict: ConflictStrategy,
+    dry_run: bool,
+) -> anyhow::Result<ImportSummary> {
+    let progress = progress_counter("importing");
+
+    // everything is read first, so the transaction can be retried if the database is busy
+    let entries = progress
+        .wrap_iter(entries)
+        .map(|entry| {
+            let entry = entry?;
+            check_qualified_key(&entry.namespace, &entry.key)?;
+            Ok(entry)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    // entries in attached or sharded namespaces are imported into the databases they're
+    // kept in, which are all committed together once every entry has been imported
+    let mut routes: BTreeMap<&str, Option<PathBuf>> = BTreeMap::new();
+    let mut by_db: BTreeMap<Option<PathBuf>, Vec<&ImportedEntry>> = BTreeMap::new();
+    let mut others: BTreeMap<PathBuf, Connection> = BTreeMap::new();
+
+    for entry in &entries {
+        if !routes.contains_key(entry.namespace.as_str()) {
+            let route = namespace_db_location(
+                conn,
+                config,
+                db_location,
+                std::slice::from_ref(&entry.namespace),
+            )?;
+
+            if let Some(other) = &route
+                && !others.contains_key(other)
+            {
+                others.insert(other.clone(), open_namespace_db(other, config)?);
+            }
+
+            routes.insert(&entry.namespace, route);
+        }
+
+        by_db
+            .entry(routes[entry.namespace.as_str()].clone())
+            .or_default()
+            .push(entry);
+    }
+
+    let summary = with_busy_retry(config, || {
+        let tx = conn.transaction()?;
+
+        let other_txs = others
+            .iter_mut()
+            .map(|(other, conn)| Ok((other, conn.transaction()?)))
+            .collect::<rusqlite::Result<BTreeMap<_, _>>>()?;
+
+        let mut summary = ImportSummary::default();
+
+        for (route, entries) in &by_db {
+            let tx = match route {
+                Some(other) => &other_txs[other],
+                None => &tx,
+            };
+
+            // dropping the transactions rolls them back
+            if let Err(e) = import_into(tx, entries, on_conflict, &mut summary)? {
+                return Ok(Err(e));
+            }
+        }
+
+        // a dry run writes too, so a key that appears twice is counted like a real import
+        // counts it, and then it's rolled back when the transactions are dropped
+        if !dry_run {
+            tx.commit()?;
+
+            for other_tx in other_txs.into_values() {
+                other_tx.commit()?;
+            }
+        }
+
+        Ok(Ok(summary))
+    })??;
+
+    progress.finish_and_clear();
+
+    Ok(summary)
+}
+
+/// Imports entries in one database's transaction, adding to `summary`
+fn import_into(
+    tx: &rusqlite::Transaction,
+    entries: &[&ImportedEntry],
+    on_conflict: ConflictStrategy,
+    summary: &mut ImportSummary,
+) -> rusqlite::Result<Result<(), BladeError>> {
+    let mut existing_q = tx.prepare_cached(
+        "
+    select
+        value,
+        julianday(coalesce(?3, 'now')) > julianday(updated_at)
+    from entries
+    where namespace = ?1
+    and key = ?2
+    ",
+    )?;
+
+    let mut expired_q = tx.prepare_cached(
+        "
+    select coalesce(
+        strftime('%Y-%m-%d %H:%M:%f', ?) <= strftime('%Y-%m-%d %H:%M:%f', 'NOW'),
+        false
+    )
+    ",
+    )?;
+
+    let mut upsert_q = tx.prepare_cached(
+        "
+    insert into entries (
+        namespace,
+        key,
+        value,
+        inserted_at,
+        updated_at,
+        content_type,
+        expires_at,
+        note
+    )
+    values (
+        ?1,
+        ?2,
+        ?3,
+        coalesce(strftime('%Y-%m-%d %H:%M:%f', ?4), strftime('%Y-%m-%d %H:%M:%f', 'NOW')),
+        coalesce(strftime('%Y-%m-%d %H:%M:%f', ?5), strftime('%Y-%m-%d %H:%M:%f', 'NOW')),
+        ?6,
+        coalesce(
+            strftime('%Y-%m-%d %H:%M:%f', ?9),
+            strftime('%Y-%m-%d %H:%M:%f', 'NOW', '+' || ?7 || ' seconds')
+        ),
+        ?8
+    )
+    on conflict do update
+    set value = excluded.value,
+    content_type = excluded.content_type,
+    expires_at = coalesce(excluded.expires_at, entries.expires_at),
+    note = coalesce(excluded.note, entries.note)
+    where namespace = excluded.namespace
+    and key = excluded.key
+    ",
+    )?;
+
+    for entry in entries {
+        if let Some(expires_at) = &entry.expires_at
+            && expired_q.query_one([expires_at], |row| row.get(0))?
+        {
+            summary.skipped += 1;
+            continue;
+        }
+
+        let existing: Option<(Vec<u8>, bool)> = existing_q
+            .query_one(
+                params![entry.namespace, entry.key, entry.updated_at],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        let write = match existing {
+            None => {
+                summary.created += 1;
+                true
+            }
+            Some((value, _)) if value == entry.value => {
+                summary.skipped += 1;
+                false
+            }
+            Some((_, is_newer)) => {
+                let overwrite = match on_conflict {
+                    ConflictStrategy::Skip => false,
+                    ConflictStrategy::Overwrite => true,
+                    ConflictStrategy::Newest => is_newer,
+                    ConflictStrategy::Fail => {
+                        // dropping the transaction rolls it back
+                        return Ok(Err(BladeError::Conflict(format!(
+                            "conflict on {}@{}",
+                            entry.key, entry.namespace
+                        ))));
+                    }
+                };
+
+                if overwrite {
+                    summary.updated += 1;
+                } else {
+                    summary.skipped += 1;
+                }
+
+                overwrite
+            }
+        };
+
+        if write {
+            summary
+                .written
+                .push((entry.namespace.clone(), entry.key.clone()));
+
+            upsert_q.execute(params![
+                entry.namespace,
+                entry.key,
+                entry.value,
+                entry.inserted_at,
+                entry.updated_at,
+                entry.content_type,
+                entry.ttl,
+                entry.note,
+                entry.expires_at
+            ])?;
+        }
+    }
+
+    Ok(Ok(()))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn import_from(
+    conn: &mut Connection,
+    config: &Config,
+    db_location: &Path,
+    input: &mut dyn BufRead,
+    format: ExportFormat,
+    csv_options: &CsvImportOptions,
+    on_conflict: ConflictStrategy,
+    dry_run: bool,
+) -> anyhow::Result<ImportSummary> {
+    match format {
+        ExportFormat::Jsonl => import_entries(
+            conn,
+            config,
+            db_location,
+            read_jsonl_entries(input),
+            on_conflict,
+            dry_run,
+        ),
+        ExportFormat::Csv | ExportFormat::Tsv => import_entries(
+            conn,
+            config,
+            db_location,
+            read_csv_entries(input, format, csv_options),
+            on_conflict,
+            dry_run,
+        ),
+    }
+}
+
+const AGE_MAGIC: &[u8] = b"age-encryption.org/";
+
+fn write_archive(
+    path: &Path,
+    passphrase: Option<&str>,
+    f: impl FnOnce(&mut dyn Write) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    if path.exists() {
+        return Err(std::io::Error::from(std::io::ErrorKind::AlreadyExists))
+            .with_context(|| format!("could not create {}", path.display()));
+    }
+
+    let temp = temp_file_for(path)?;
+    let file = std::io::BufWriter::new(temp.as_file());
+
+    match passphrase {
+        Some(passphrase) => {
+            let encryptor =
+                age::Encryptor::with_user_passphrase(SecretString::from(passphrase.to_string()));
+            let mut encrypted = encryptor.wrap_output(file)?;
+            let mut compressed = zstd::Encoder::new(&mut encrypted, 0)?;
+            f(&mut compressed)?;
+            compressed.finish()?;
+            encrypted.finish()?.flush()?;
+        }
+        None => {
+            let mut compressed = zstd::Encoder::new(file, 0)?;
+            f(&mut compressed)?;
+            compressed.finish()?.flush()?;
+        }
+    }
+
+    temp.persist_noclobber(path).map_err(|e| e.error)?;
+
+    Ok(())
+}
+
+fn read_archive(path: &Path, passphrase: Option<&str>) -> anyhow::Result<Box<dyn BufRead>> {
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+
+    let is_encrypted = file.fill_buf()?.starts_with(AGE_MAGIC);
+
+    let decrypted: Box<dyn Read> = if is_encrypted {
+        let passphrase = passphrase.ok_or_else(|| {
+            BladeError::Usage("archive is encrypted, a passphrase is required".to_string())
+        })?;
+
+        let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_string()));
+
+        let decrypted = age::Decryptor::new_buffered(file).and_then(|decryptor| {
+            decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity))
+        });
+
+        Box::new(decrypted.map_err(|e| match e {
+            age::DecryptError::DecryptionFailed | age::DecryptError::NoMatchingKeys => {
+                anyhow::Error::from(BladeError::Rejected(format!(
+                    "wrong passphrase for archive: {}",
+                    path.display()
+                )))
+            }
+            age::DecryptError::InvalidHeader
+            | age::DecryptError::InvalidMac
+            | age::DecryptError::UnknownFormat => anyhow::Error::from(BladeError::Corrupt(
+                format!("archive is corrupt: {}: {}", path.display(), e),
+            )),
+            e => e.into(),
+        })?)
+    } else {
+        Box::new(file)
+    };
+
+    Ok(Box::new(std::io::BufReader::new(zstd::Decoder::new(
+        decrypted,
+    )?)))
+}
+
+fn decode_key(encoded: &str) -> anyhow::Result<[u8; 32]> {
+    BASE64_STANDARD
+        .decode(encoded.trim())?
+        .try_into()
+        .map_err(|_| BladeError::Usage("key must be 32 bytes".to_string()).into())
+}
+
+fn signature_path(archive: &Path) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(".sig");
+    PathBuf::from(path)
+}
+
+fn sign_archive(archive: &Path, secret_key_path: &Path) -> anyhow::Result<()> {
+    let secret_key = decode_key(&std::fs::read_to_string(secret_key_path)?)?;
+
+    let signature = SigningKey::from_bytes(&secret_key).sign(&std::fs::read(archive)?);
+
+    std::fs::write(
+        signature_path(archive),
+        BASE64_STANDARD.encode(signature.to_bytes()) + "\n",
+    )?;
+
+    Ok(())
+}
+
+fn verify_archive(archive: &Path, public_key: &str) -> anyhow::Result<()> {
+    let public_key = VerifyingKey::from_bytes(&decode_key(public_key)?)?;
+
+    let signature_path = signature_path(archive);
+
+    let signature = std::fs::read_to_string(&signature_path)
+        .with_context(|| format!("could not read {}", signature_path.display()))?;
+
+    let signature = Signature::from_slice(&BASE64_STANDARD.decode(signature.trim())?)?;
+
+    public_key
+        .verify_strict(&std::fs::read(archive)?, &signature)
+        .map_err(|_| {
+            BladeError::Rejected(format!(
+                "archive signature is not valid: {}",
+                archive.display()
+            ))
+            .into()
+        })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Changed => "changed",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Serialize)]
+struct EntryChange {
+    change: Change,
+    namespace: String,
+    key: String,
+}
+
+/// Parses a size like `512`, `1k`, `10MB`, or `2g`, using powers of 1024
+fn parse_size(s: &str) -> anyhow::Result<usize> {
+    let s = s.trim();
+
+    let split_at = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+
+    let (number, unit) = s.split_at(split_at);
+
+    let number: f64 = number.parse().map_err(|_| anyhow!("invalid size: {}", s))?;
+
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        _ => return Err(anyhow!("invalid size unit: {}", unit)),
+    };
+
+    Ok((number * multiplier as f64) as usize)
+}
+
+/// The inverse of `parse_duration`, in the largest unit that divides `seconds`
+fn format_duration(seconds: i64) -> String {
+    for (unit, unit_seconds) in [
+        ("w", 60 * 60 * 24 * 7),
+        ("d", 60 * 60 * 24),
+        ("h", 60 * 60),
+        ("m", 60),
+    ] {
+        if seconds != 0 && seconds % unit_seconds == 0 {
+            return format!("{}{}", seconds / unit_seconds, unit);
+        }
+    }
+
+    format!("{}s", seconds)
+}
+
+/// `seconds` in every unit it spans, like `1d2h3m4s`
+fn format_remaining(seconds: i64) -> String {
+    let mut remaining = seconds;
+    let mut formatted = String::new();
+
+    for (unit, unit_seconds) in [("d", 60 * 60 * 24), ("h", 60 * 60), ("m", 60), ("s", 1)] {
+        if remaining >= unit_seconds {
+            formatted.push_str(&format!("{}{}", remaining / unit_seconds, unit));
+            remaining %= unit_seconds;
+        }
+    }
+
+    if formatted.is_empty() {
+        formatted.push_str("0s");
+    }
+
+    formatted
+}
+
+/// A duration like `90s`, `12h`, or `6mo`. Months are 30 days and years are 365
+fn parse_duration(s: &str) -> anyhow::Result<std::time::Duration> {
+    let s = s.trim();
+
+    let split_at = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+
+    let (number, unit) = s.split_at(split_at);
+
+    let number: f64 = number.parse().map_err(|_| {
+        anyhow!(
+            "invalid duration: {}. Expected a number and a unit, like `7d`",
+            s
+        )
+    })?;
+
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "ms" => 0.001,
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 60.0 * 60.0,
+        "d" => 60.0 * 60.0 * 24.0,
+        "w" => 60.0 * 60.0 * 24.0 * 7.0,
+        "mo" => 60.0 * 60.0 * 24.0 * 30.0,
+        "y" => 60.0 * 60.0 * 24.0 * 365.0,
+        _ => {
+            return Err(anyhow!(
+                "invalid duration unit: {:?} in {}. Units are ms, s, m, h, d, w, mo, and y",
+                unit,
+                s
+            ));
+        }
+    };
+
+    std::time::Duration::try_from_secs_f64(number * multiplier)
+        .map_err(|_| anyhow!("duration is too long: {}", s))
+}
+
+/// 9999-12-31 23:59:59 UTC, the latest time SQLite's date functions work with, in unix seconds
+const MAX_SQLITE_TIME: Duration = Duration::from_secs(253_402_300_799);
+
+/// A ttl, which has to expire before `MAX_SQLITE_TIME`.
+/// Past it, the expiry would be stored as null and the key would never expire
+fn parse_ttl(s: &str) -> anyhow::Result<std::time::Duration> {
+    let ttl = parse_duration(s)?;
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+
+    if now
+        .checked_add(ttl)
+        .is_none_or(|expires| expires > MAX_SQLITE_TIME)
+    {
+        return Err(anyhow!(
+            "ttl is too long: {}. Keys have to expire before the year 10000",
+            s
+        ));
+    }
+
+    Ok(ttl)
+}
+
+struct BenchPhase {
+    elapsed: Duration,
+    latencies: Vec<Duration>,
+}
+
+impl BenchPhase {
+    fn percentile(&self, p: f64) -> Duration {
+        let i = ((self.latencies.len() - 1) as f64 * p).round() as usize;
+        self.latencies[i]
+    }
+}
+
+/// Runs `op` `ops` times, split across `concurrency` threads that each have their own connection
+fn run_bench_phase(
+    db_location: &Path,
+    config: &Config,
+    ops: usize,
+    concurrency: usize,
+    op: impl Fn(&Connection, usize) -> rusqlite::Result<()> + Sync,
+) -> anyhow::Result<BenchPhase> {
+    let start = Instant::now();
+
+    let per_thread = std::thread::scope(|scope| {
+        let handles = (0..concurrency)
+            .map(|thread| {
+                let op = &op;
+
+                scope.spawn(move || -> anyhow::Result<Vec<Duration>> {
+                    let conn = open_db_connection(db_location, config)?;
+
+                    let mut latencies = vec![];
+
+                    for i in (thread..ops).step_by(concurrency) {
+                        let op_start = Instant::now();
+                        with_busy_retry(config, || op(&conn, i))?;
+                        latencies.push(op_start.elapsed());
+                    }
+
+                    Ok(latencies)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("bench thread panicked"))
+            .collect::<anyhow::Result<Vec<_>>>()
+    })?;
+
+    let elapsed = start.elapsed();
+
+    let mut latencies = per_thread.concat();
+    latencies.sort();
+
+    Ok(BenchPhase { elapsed, latencies })
+}
+
+struct Key<'input> {
+    namespace: &'input str,
+    name: &'input str,
+}
+
+/// Unescapes `\n`, `\t`, `\0`, and `\\`, leaving any other backslashes alone
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('0') => unescaped.push('\0'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+/// `s` as an SQL string literal, for the few places that can't use parameters
+fn sql_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// The expression that `index create` indexes and `list --where` compares.
+/// Values that aren't JSON are null, rather than an error
+fn json_path_expression(path: &str) -> String {
+    format!(
+        "json_extract(case when json_valid(value) then value end, {})",
+        sql_literal(path)
+    )
+}
+
+/// A `list --where` filter, like `$.env = prod`
+#[derive(Clone)]
+struct Where {
+    path: String,
+    operator: &'static str,
+    value: rusqlite::types::Value,
+}
+
+fn parse_where(s: &str) -> Result<Where, String> {
+    let start = s
+        .find(['=', '!', '<', '>'])
+        .ok_or_else(|| format!("expected a comparison, like `$.env = prod`: {}", s))?;
+
+    let operator = ["!=", "<=", ">=", "=", "<", ">"]
+        .into_iter()
+        .find(|operator| s[start..].starts_with(operator))
+        .ok_or_else(|| format!("unknown operator: {}", s))?;
+
+    let path = s[..start].trim();
+
+    if !path.starts_with('$') {
+        return Err(format!("paths start with `$`, like `$.env`: {}", path));
+    }
+
+    let value = s[start + operator.len()..].trim();
+
+    // compare the way `json_extract` returns values,
+    // so numbers and booleans compare as numbers
+    let value = if let Ok(i) = value.parse::<i64>() {
+        rusqlite::types::Value::Integer(i)
+    } else if let Ok(f) = value.parse::<f64>() {
+        rusqlite::types::Value::Real(f)
+    } else if value == "true" || value == "false" {
+        rusqlite::types::Value::Integer((value == "true") as i64)
+    } else {
+        let unquoted = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value);
+        rusqlite::types::Value::Text(unquoted.to_string())
+    };
+
+    Ok(Where {
+        path: path.to_string(),
+        operator,
+        value,
+    })
+}
+
+fn parse_query_where(s: &str) -> anyhow::Result<QueryWhere> {
+    let start = s
+        .find(['=', '!', '<', '>', '~'])
+        .ok_or_else(|| anyhow!("expected a comparison, like `size > 1MB`: {}", s))?;
+
+    let operator = ["!=", "<=", ">=", "=", "<", ">", "~"]
+        .into_iter()
+        .find(|operator| s[start..].starts_with(operator))
+        .ok_or_else(|| anyhow!("unknown operator: {}", s))?;
+
+    let field = QueryField::from_str(s[..start].trim(), true).map_err(|e| anyhow!(e))?;
+
+    let value = s[start + operator.len()..].trim();
+
+    let value = if operator == "~" {
+        QueryValue::Literal(rusqlite::types::Value::Text(value.to_string()))
+    } else if field == QueryField::Size {
+        QueryValue::Literal(rusqlite::types::Value::Integer(parse_size(value)? as i64))
+    } else if field.is_time()
+        && let Some(duration) = value.strip_suffix("ago")
+    {
+        QueryValue::Ago(parse_duration(duration)?)
+    } else {
+        QueryValue::Literal(rusqlite::types::Value::Text(value.to_string()))
+    };
+
+    Ok(QueryWhere {
+        field,
+        operator: if operator == "~" { "glob" } else { operator },
+        value,
+    })
+}
+
+/// A `glob` pattern for `namespace`, where only `*` is special
+fn namespace_glob(namespace: &str) -> String {
+    namespace.replace('[', "[[]").replace('?', "[?]")
+}
+
+fn split_maybe_qualified_key(maybe_qualified_key: &str) -> anyhow::Result<Key<'_>> {
+    if maybe_qualified_key.trim().is_empty() {
+        return Err(BladeError::Usage("key cannot be empty".to_string()).into());
+    }
+
+    let mut split = maybe_qualified_key.split("@");
+
+    match (split.next(), split.next()) {
+        (Some(name), None) => Ok(Key {
+            namespace: default_namespace(),
+            name,
+        }),
+        (Some(name), Some(namespace)) => {
+            if name.trim().is_empty() {
+                Err(BladeError::Usage("key cannot be empty".to_string()).into())
+            } else if namespace.trim().is_empty() {
+                Err(BladeError::Usage("namespace cannot be empty".to_string()).into())
+            } else {
+                Ok(Key { namespace, name })
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Checks that a key from somewhere other than the command line, like an import,
+/// could be given as `key@namespace`, so that `get` and `delete` can reach it
+fn check_qualified_key(namespace: &str, name: &str) -> anyhow::Result<()> {
+    let qualified = format!("{}@{}", name, namespace);
+    let key = split_maybe_qualified_key(&qualified)?;
+
+    if key.name != name || key.namespace != namespace {
+        return Err(BladeError::Usage(format!(
+            "keys and namespaces can't contain @: {:?} in namespace {:?}",
+            name, namespace
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+fn get_value(conn: &Connection, key: &Key) -> rusqlite::Result<Option<Vec<u8>>> {
+    conn.get(key.namespace, key.name)
+}
+
+/// The elements of a value that `push` and `lrange` work on
+fn json_array(namespaced_key: &str, value: &[u8]) -> anyhow::Result<Vec<serde_json::Value>> {
+    serde_json::from_slice(value).map_err(|_| {
+        BladeError::Usage(format!("{} doesn't hold a JSON array", namespaced_key)).into()
+    })
+}
+
+/// Sets or, with `None`, deletes a key's note. Notes aren't changes to the value,
+/// so this doesn't touch `updated_at`
+fn set_note(
+    conn: &Connection,
+    config: &Config,
+    send_hooks: bool,
+    namespaced_key: &str,
+    note: Option<&str>,
+) -> anyhow::Result<()> {
+    let key = split_maybe_qualified_key(namespaced_key)?;
+    let (namespace, name) = resolve_alias(conn, &key)?;
+
+    let updated = with_busy_retry(config, || {
+        conn.execute(
+            "
+        update entries
+        set note = ?3
+        where namespace = ?1
+        and key = ?2
+        and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        ",
+            params![namespace, name, note],
+        )
+    })?;
+
+    if updated == 0 {
+        return Err(BladeError::NotFound(format!("key not found: {}", namespaced_key)).into());
+    }
+
+    if send_hooks {
+        send_webhooks(
+            conn,
+            config,
+            ChangeOp::Touch,
+            &Key {
+                namespace: &namespace,
+                name: &name,
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes what `get --meta` prints, a `field\tvalue` line for each of `query`'s fields
+/// other than the value itself, and the note
+fn write_entry_meta(
+    conn: &Connection,
+    key: &Key,
+    times: TimeDisplay,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let fields: Vec<QueryField> = QueryField::value_variants()
+        .iter()
+        .copied()
+        .filter(|field| *field != QueryField::Value)
+        .collect();
+
+    let columns: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.is_time() {
+                times.sql(field.column())
+            } else {
+                field.column().to_string()
+            }
+        })
+        .collect();
+
+    let mut q = conn.prepare(&format!(
+        "
+    select
+        {}
+    from entries
+    where namespace = ?
+    and key = ?
+    and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+    ",
+        columns.join(", ")
+    ))?;
+
+    let mut rows = q.query([key.namespace, key.name])?;
+
+    let Some(row) = rows.next()? else {
+        return Err(
+            BladeError::NotFound(format!("key not found: {}@{}", key.name, key.namespace)).into(),
+        );
+    };
+
+    for (i, field) in fields.iter().enumerate() {
+        let name = field
+            .to_possible_value()
+            .expect("no fields are skipped")
+            .get_name()
+            .to_string();
+
+        write!(out, "{}\t", name)?;
+
+        if field.is_time() {
+            times.write(out, row.get_ref(i)?)?;
+        } else {
+            write_sql_value(out, row.get_ref(i)?, false)?;
+        }
+
+        out.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// The type the key's values must have, if it was set with `set --type`
+fn get_value_type(conn: &Connection, key: &Key) -> anyhow::Result<Option<ValueType>> {
+    let value_type: Option<String> = conn
+        .prepare_cached(
+            "
+        select
+            value_type
+        from entries
+        where namespace = ?
+        and key = ?
+        and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        ",
+        )?
+        .query_one([key.namespace, key.name], |row| row.get(0))
+        .optional()?
+        .flatten();
+
+    value_type
+        .map(|value_type| {
+            ValueType::from_str(&value_type, false)
+                .map_err(|e| anyhow!("unknown value type {}: {}", value_type, e))
+        })
+        .transpose()
+}
+
+/// Where a value set with `--from-url` came from
+struct UrlSource {
+    url: String,
+    headers: Vec<String>,
+    content_type: Option<String>,
+}
+
+impl UrlSource {
+    /// The headers to store for `--refresh`. Credentials are stored as only their names
+    fn stored_headers(&self) -> String {
+        self.headers
+            .iter()
+            .map(|header| match header.split_once(':') {
+                Some((name, _)) if is_credential_header(name.trim()) => name.trim(),
+                _ => header,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Headers whose values are likely secrets, like `Authorization`, `Cookie`, or `X-Api-Key`
+fn is_credential_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+
+    name == "cookie"
+        || ["auth", "token", "key", "secret", "session", "password"]
+            .iter()
+            .any(|word| name.contains(word))
+}
+
+/// A header stored by [`UrlSource::stored_headers`], with its value,
+/// which comes from `BLADE_HEADER_<NAME>` if it's a credential
+fn stored_header(stored: &str) -> anyhow::Result<String> {
+    if stored.contains(':') {
+        return Ok(stored.to_string());
+    }
+
+    let var = format!(
+        "BLADE_HEADER_{}",
+        stored.to_ascii_uppercase().replace('-', "_")
+    );
+
+    let value = std::env::var(&var).map_err(|_| {
+        BladeError::Usage(format!(
+            "the {} header isn't stored. Set {} to send it, or pass --header",
+            stored, var
+        ))
+    })?;
+
+    Ok(format!("{}: {}", stored, value))
+}
+
+/// Downloads `url`, returning the body and its content type
+fn fetch_url(url: &str, headers: &[String]) -> anyhow::Result<(Vec<u8>, Option<String>)> {
+    let mut request = ureq::get(url);
+
+    for header in headers {
+        let (name, value) = header.split_once(':').ok_or_else(|| {
+            BladeError::Usage(format!("headers must be like `name: value`: {}", header))
+        })?;
+
+        request = request.header(name.trim(), value.trim());
+    }
+
+    debug!(url, "fetching");
+
+    let response = request.call()?;
+
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|content_type| content_type.to_str().ok())
+        .map(String::from);
+
+    let mut body = vec![];
+    response.into_body().into_reader().read_to_end(&mut body)?;
+
+    Ok((body, content_type))
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ChangeOp {
+    Set,
+    Delete,
+    Touch,
+}
+
+#[derive(Serialize)]
+struct ChangeEvent<'a> {
+    op: ChangeOp,
+    namespace: &'a str,
+    key: &'a str,
+    timestamp: String,
+}
+
+const WEBHOOK_ATTEMPTS: u32 = 3;
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// POSTs a `ChangeEvent` to each of the namespace's webhooks.
+/// The change has already been committed by now,
+/// so webhooks that still fail after retrying are only logged
+fn send_webhooks(
+    conn: &Connection,
+    config: &Config,
+    op: ChangeOp,
+    key: &Key,
+) -> anyhow::Result<()> {
+    let Some(namespace_config) = config.namespaces.get(key.namespace) else {
+        return Ok(());
+    };
+
+    if namespace_config.webhooks.is_empty() {
+        return Ok(());
+    }
+
+    let timestamp = conn.query_one("select strftime('%Y-%m-%d %H:%M:%f', 'NOW')", [], |row| {
+        row.get(0)
+    })?;
+
+    let event = serde_json::to_vec(&ChangeEvent {
+        op,
+        namespace: key.namespace,
+        key: key.name,
+        timestamp,
+    })?;
+
+    for url in &namespace_config.webhooks {
+        let mut backoff = Duration::from_millis(100);
+
+        for attempt in 1..=WEBHOOK_ATTEMPTS {
+            debug!(url, attempt, "sending webhook");
+
+            match ureq::post(url)
+                .config()
+                .timeout_global(Some(WEBHOOK_TIMEOUT))
+                .build()
+                .content_type("application/json")
+                .send(&event[..])
+            {
+                Ok(_) => break,
+                Err(e) if attempt < WEBHOOK_ATTEMPTS => {
+                    warn!(
+                        "webhook {} failed, retrying in {:?} (attempt {} of {}): {}",
+                        url, backoff, attempt, WEBHOOK_ATTEMPTS, e
+                    );
+
+                    std::thread::sleep(backoff);
+
+                    backoff *= 2;
+                }
+                Err(e) => {
+                    warn!(
+                        "webhook {} failed after {} attempts: {}",
+                        url, WEBHOOK_ATTEMPTS, e
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends webhooks for each of `keys`, which were changed together,
+/// like the keys an `import` wrote or `gc` deleted
+fn send_webhooks_for_each(
+    conn: &Connection,
+    config: &Config,
+    op: ChangeOp,
+    keys: &[(String, String)],
+) -> anyhow::Result<()> {
+    for (namespace, name) in keys {
+        send_webhooks(conn, config, op, &Key { namespace, name })?;
+    }
+
+    Ok(())
+}
+
+/// Checks `value` against the schema of the longest prefix of `key` that has one, if any
+fn validate_value(conn: &Connection, key: &Key, value: &[u8]) -> anyhow::Result<()> {
+    let Some((prefix, schema)): Option<(String, String)> = conn
+        .prepare_cached(
+            "
+        select
+            prefix,
+            schema
+        from schemas
+        where namespace = ?1
+        and substr(?2, 1, length(prefix)) = prefix
+        order by length(prefix) desc
+        limit 1
+        ",
+        )?
+        .query_one([key.namespace, key.name], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()?
+    else {
+        return Ok(());
+    };
+
+    let schema: serde_json::Value = serde_json::from_str(&schema)?;
+    let validator =
+        jsonschema::validator_for(&schema).map_err(|e| anyhow!("invalid schema: {}", e))?;
+
+    let schema_name = if prefix.is_empty() {
+        key.namespace.to_string()
+    } else {
+        format!("{}* in {}", prefix, key.namespace)
+    };
+
+    let value: serde_json::Value = serde_json::from_slice(value).map_err(|e| {
+        BladeError::Rejected(format!(
+            "the schema for {} requires a JSON value: {}",
+            schema_name, e
+        ))
+    })?;
+
+    let errors: Vec<String> = validator
+        .iter_errors(&value)
+        .map(|e| {
+            let path = e.instance_path().to_string();
+
+            format!(
+                "  at {}: {}",
+                if path.is_empty() { "the root" } else { &path },
+                e
+            )
+        })
+        .collect();
+
+    if !errors.is_empty() {
+        return Err(BladeError::Rejected(format!(
+            "value does not match the schema for {}:\n{}",
+            schema_name,
+            errors.join("\n")
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Runs a hook from `[hooks]`, failing if it can't be run or exits unsuccessfully
+fn run_hook(command: &str, hook: &str, key: &Key, value: &[u8]) -> anyhow::Result<()> {
+    debug!(hook, command, "running hook");
+
+    let mut child = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("BLADE_HOOK", hook)
+        .env("BLADE_NAMESPACE", key.namespace)
+        .env("BLADE_KEY", key.name)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .with_context(|| format!("could not run {} hook", hook))?;
+
+    // hooks don't have to read the value,
+    // so a hook exiting before it's all written is fine
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(value);
+    }
+
+    let status = child.wait()?;
+
+    if !status.success() {
+        return Err(BladeError::Rejected(format!("{} hook failed: {}", hook, status)).into());
+    }
+
+    Ok(())
+}
+
+enum SetOutcome {
+    /// Set, evicting these keys in the namespace to stay under its quota
+    Set {
+        evicted: Vec<String>,
+    },
+    OverQuota(String),
+}
+
+/// What `set` can do besides storing a value
+#[derive(Default)]
+struct SetOptions {
+    ttl: Option<std::time::Duration>,
+    source: Option<UrlSource>,
+    value_type: Option<ValueType>,
+    /// The version a `rollback` restores, to record in the key's history
+    restores: Option<i64>,
+}
+
+/// Sets a key the way `set` does: checking the value's type and schema,
+/// and running hooks and sending webhooks unless `send_hooks` is false
+fn set_key(
+    conn: &mut Connection,
+    config: &Config,
+    send_hooks: bool,
+    namespaced_key: &str,
+    key: &Key,
+    value: &[u8],
+    options: &SetOptions,
+) -> anyhow::Result<()> {
+    set_keys(
+        conn,
+        config,
+        send_hooks,
+        &[(
+            namespaced_key,
+            Key {
+                namespace: key.namespace,
+                name: key.name,
+            },
+            value,
+        )],
+        options,
+    )
+}
+
+/// Sets keys like `set_key`, in one transaction.
+/// Nothing is set if any of them can't be
+fn set_keys(
+    conn: &mut Connection,
+    config: &Config,
+    send_hooks: bool,
+    entries: &[(&str, Key, &[u8])],
+    options: &SetOptions,
+) -> anyhow::Result<()> {
+    // setting an alias sets the key it points to, which is the one `get` reads
+    let targets = entries
+        .iter()
+        .map(|(_, key, _)| resolve_alias(conn, key))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let entries = entries
+        .iter()
+        .zip(&targets)
+        .map(|((namespaced_key, _, value), (namespace, name))| {
+            (*namespaced_key, Key { namespace, name }, *value)
+        })
+        .collect::<Vec<_>>();
+
+    for (namespaced_key, key, value) in &entries {
+        if let Some(value_type) = options.value_type.or(get_value_type(conn, key)?) {
+            value_type.check(namespaced_key, value)?;
+        }
+
+        validate_value(conn, key, value)?;
+
+        if send_hooks && let Some(pre_set) = &config.hooks.pre_set {
+            run_hook(pre_set, "pre-set", key, value)?;
+        }
+    }
+
+    let set = with_busy_retry(config, || {
+        let tx = conn.transaction()?;
+
+        let mut evicted = vec![];
+
+        for (_, key, value) in &entries {
+            match set_value(
+                &tx,
+                config,
+                key,
+                value,
+                options.ttl,
+                options.source.as_ref(),
+                options.value_type,
+            )? {
+                SetOutcome::Set { evicted: keys } => evicted.extend(
+                    keys.into_iter()
+                        .map(|name| (key.namespace.to_string(), name)),
+                ),
+                // dropping the transaction rolls it back
+                SetOutcome::OverQuota(message) => return Ok(Err(message)),
+            }
+
+            if let Some(version) = options.restores {
+                tx.prepare_cached(
+                    "
+                update entry_history
+                set restored_version = ?3
+                where namespace = ?1
+                and key = ?2
+                and version = (
+                    select max(version)
+                    from entry_history
+                    where namespace = ?1
+                    and key = ?2
+                )
+                ",
+                )?
+                .execute(params![key.namespace, key.name, version])?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(Ok(evicted))
+    })?;
+
+    let evicted = set.map_err(BladeError::OverQuota)?;
+
+    if send_hooks {
+        for (_, key, value) in &entries {
+            if let Some(post_set) = &config.hooks.post_set
+                && let Err(e) = run_hook(post_set, "post-set", key, value)
+            {
+                warn!("{:#}", e);
+            }
+
+            send_webhooks(conn, config, ChangeOp::Set, key)?;
+        }
+
+        send_webhooks_for_each(conn, config, ChangeOp::Delete, &evicted)?;
+    }
+
+    Ok(())
+}
+
+/// Deletes up to `max` expired entries, oldest first, or all of them without a `max`.
+/// Doesn't take the write lock unless there's something to delete
+fn sweep_expired(
+    conn: &mut Connection,
+    config: &Config,
+    max: Option<u64>,
+) -> rusqlite::Result<Vec<(String, String)>> {
+    let any_expired: bool = conn.query_one(
+        "
+    select exists (
+        select 1
+        from entries
+        where expires_at <= strftime('%Y-%m-%d %H:%M:%f', 'NOW')
+    )
+    ",
+        [],
+        |row| row.get(0),
+    )?;
+
+    if !any_expired {
+        return Ok(vec![]);
+    }
+
+    with_busy_retry(config, || {
+        conn.prepare(
+            "
+        delete from entries
+        where (namespace, key) in (
+            select namespace, key
+            from entries
+            where expires_at <= strftime('%Y-%m-%d %H:%M:%f', 'NOW')
+            order by expires_at
+            limit coalesce(?, -1)
+        )
+        returning namespace, key
+        ",
+        )?
+        .query_map([max.map(|max| max as i64)], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect()
+    })
+}
+
+/// Expires a key after `ttl`, or stops it expiring if there's none.
+/// Keys that have already expired can't be changed
+fn set_expiry(
+    conn: &Connection,
+    config: &Config,
+    send_hooks: bool,
+    namespaced_key: &str,
+    key: &Key,
+    ttl: Option<std::time::Duration>,
+) -> anyhow::Result<()> {
+    let updated = with_busy_retry(config, || {
+        conn.execute(
+            "
+        update entries
+        set expires_at = strftime('%Y-%m-%d %H:%M:%f', 'NOW', '+' || ?1 || ' seconds')
+        where namespace = ?2
+        and key = ?3
+        and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        ",
+            params![ttl.map(|ttl| ttl.as_secs_f64()), key.namespace, key.name],
+        )
+    })?;
+
+    if updated == 0 {
+        return Err(BladeError::NotFound(format!("key not found: {}", namespaced_key)).into());
+    }
+
+    if send_hooks {
+        send_webhooks(conn, config, ChangeOp::Touch, key)?;
+    }
+
+    Ok(())
+}
+
+/// Keys read by a command, to record as accessed all at once when it's done,
+/// rather than with a write per read
+#[derive(Default)]
+struct AccessLog {
+    keys: Vec<(String, String)>,
+}
+
+impl AccessLog {
+    /// Records the reads in one transaction.
+    /// A failure to record them shouldn't fail the reads, so it's only logged
+    fn flush(self, conn: &Connection, config: &Config) {
+        if self.keys.is_empty() {
+            return;
+        }
+
+        let start = Instant::now();
+
+        let result = with_busy_retry(config, || {
+            let tx = conn.unchecked_transaction()?;
+
+            {
+                let mut q = tx.prepare_cached(
+                    "
+                update entries
+                set last_accessed_at = strftime('%Y-%m-%d %H:%M:%f', 'NOW')
+                where namespace = ?
+                and key = ?
+                ",
+                )?;
+
+                for (namespace, name) in &self.keys {
+                    q.execute([namespace, name])?;
+                }
+            }
+
+            tx.commit()
+        });
+
+        match result {
+            Ok(()) => debug!(keys = self.keys.len(), elapsed = ?start.elapsed(), "recorded access"),
+            Err(e) => warn!(
+                "could not record last access of {} keys: {}",
+                self.keys.len(),
+                e
+            ),
+        }
+    }
+}
+
+/// Gets the value of a key the way `get` does, resolving aliases,
+/// and adding it to `accessed` if the config asks for reads to be recorded
+fn read_value(
+    conn: &Connection,
+    config: &Config,
+    namespaced_key: &str,
+    accessed: &mut AccessLog,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let key = split_maybe_qualified_key(namespaced_key)?;
+
+    let (namespace, name) = resolve_alias(conn, &key)?;
+
+    let key = Key {
+        namespace: &namespace,
+        name: &name,
+    };
+
+    let value = get_value(conn, &key)?;
+
+    if value.is_some()
+        && (config.track_last_accessed
+            || config
+                .namespaces
+                .get(key.namespace)
+                .is_some_and(|namespace_config| namespace_config.tracks_last_accessed()))
+    {
+        accessed.keys.push((namespace.clone(), name.clone()));
+    }
+
+    Ok(value)
+}
+
+/// Deletes the versions of keys' history that `[history]` in the config doesn't keep.
+/// The version holding a key's current value is always kept.
+/// Returns how many were deleted
+fn trim_history(conn: &Connection, config: &Config) -> anyhow::Result<usize> {
+    let retention = &config.history;
+
+    if retention.is_empty() {
+        return Ok(0);
+    }
+
+    let max_age = retention
+        .max_age
+        .as_deref()
+        .map(parse_duration)
+        .transpose()
+        .map_err(|e| {
+            BladeError::Usage(format!("invalid history.max_age in the config: {:#}", e))
+        })?;
+
+    let trimmed = with_busy_retry(config, || {
+        conn.execute(
+            "
+        delete from entry_history
+        where (
+            ?1 is not null
+            and version <= (
+                select max(latest.version)
+                from entry_history latest
+                where latest.namespace = entry_history.namespace
+                and latest.key = entry_history.key
+            ) - max(?1, 1)
+        )
+        or (
+            ?2 is not null
+            and julianday(recorded_at) < julianday('now') - ?2 / 86400.0
+            and (
+                value is null
+                or version < (
+                    select max(latest.version)
+                    from entry_history latest
+                    where latest.namespace = entry_history.namespace
+                    and latest.key = entry_history.key
+                )
+            )
+        )
+        ",
+            params![
+                retention
+                    .max_versions
+                    .map(|max_versions| max_versions as i64),
+                max_age.map(|max_age| max_age.as_secs_f64()),
+            ],
+        )
+    })?;
+
+    Ok(trimmed)
+}
+
+/// Deletes keys the way `delete` does, in one transaction, along with their history
+/// if `purge_history` is true, then runs hooks and sends webhooks for the keys that existed,
+/// unless `send_hooks` is false. Returns how many keys existed
+fn delete_keys(
+    conn: &mut Connection,
+    config: &Config,
+    send_hooks: bool,
+    keys: &[Key],
+    purge_history: bool,
+) -> anyhow::Result<usize> {
+    let existed = with_busy_retry(config, || {
+        let tx = conn.transaction()?;
+
+        let mut existed = vec![];
+
+        for key in keys {
+            existed.push(tx.delete(key.namespace, key.name)?);
+
+            if purge_history {
+                tx.prepare_cached(
+                    "
+                delete from entry_history
+                where namespace = ?
+                and key = ?
+                ",
+                )?
+                .execute([key.namespace, key.name])?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(existed)
+    })?;
+
+    if send_hooks {
+        for (key, _) in keys.iter().zip(&existed).filter(|(_, existed)| **existed) {
+            if let Some(post_delete) = &config.hooks.post_delete
+                && let Err(e) = run_hook(post_delete, "post-delete", key, &[])
+            {
+                warn!("{:#}", e);
+            }
+
+            send_webhooks(conn, config, ChangeOp::Delete, key)?;
+        }
+    }
+
+    Ok(existed.into_iter().filter(|existed| *existed).count())
+}
+
+/// The `(old, new)` names `rename` gives the keys in `namespace` matching `glob`,
+/// leaving out keys whose name wouldn't change
+fn plan_renames(
+    conn: &Connection,
+    namespace: &str,
+    glob: &str,
+    replace: &str,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let glob_stars = glob.matches('*').count();
+    let replace_stars = replace.matches('*').count();
+
+    if replace_stars != 0 && replace_stars != glob_stars {
+        return Err(BladeError::Usage(format!(
+            "--replace has {} `*`s, but --glob has {}",
+            replace_stars, glob_stars
+        ))
+        .into());
+    }
+
+    // each `*` captures what it matched
+    let pattern = regex::Regex::new(&format!(
+        "^{}$",
+        glob.split('*')
+            .map(regex::escape)
+            .collect::<Vec<_>>()
+            .join("(.*)")
+    ))?;
+
+    let keys: Vec<String> = conn
+        .prepare(
+            "
+        select
+            key
+        from entries
+        where namespace = ?
+        and key glob ?
+        and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        order by key
+        ",
+        )?
+        .query_map([namespace, &namespace_glob(glob)], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+
+    let mut renames = vec![];
+
+    for key in keys {
+        let Some(captures) = pattern.captures(&key) else {
+            continue;
+        };
+
+        let matched: Vec<&str> = captures
+            .iter()
+            .skip(1)
+            .map(|m| m.map_or("", |m| m.as_str()))
+            .collect();
+
+        let new_key = if replace_stars == 0 {
+            format!("{}{}", replace, matched.concat())
+        } else {
+            let mut new_key = String::new();
+            for (i, part) in replace.split('*').enumerate() {
+                if i > 0 {
+                    new_key.push_str(matched[i - 1]);
+                }
+                new_key.push_str(part);
+            }
+            new_key
+        };
+
+        if new_key.trim().is_empty() {
+            return Err(
+                BladeError::Usage(format!("{} would be renamed to an empty key", key)).into(),
+            );
+        }
+
+        if new_key != key {
+            renames.push((key, new_key));
+        }
+    }
+
+    let renamed: BTreeMap<&str, &str> = renames
+        .iter()
+        .map(|(old, new)| (old.as_str(), new.as_str()))
+        .collect();
+
+    let mut taken: BTreeMap<&str, &str> = BTreeMap::new();
+
+    for (old, new) in &renames {
+        if let Some(other) = taken.insert(new, old) {
+            return Err(BladeError::Conflict(format!(
+                "both {} and {} would be renamed to {}",
+                other, old, new
+            ))
+            .into());
+        }
+
+        // keys being renamed give up their names
+        if !renamed.contains_key(new.as_str())
+            && get_value(
+                conn,
+                &Key {
+                    namespace,
+                    name: new,
+                },
+            )?
+            .is_some()
+        {
+            return Err(BladeError::Conflict(format!(
+                "{} would be renamed to {}, which already exists",
+                old, new
+            ))
+            .into());
+        }
+    }
+
+    Ok(renames)
+}
+
+/// Renames keys in one transaction, along with the aliases pointing to them
+fn rename_keys(
+    conn: &mut Connection,
+    config: &Config,
+    send_hooks: bool,
+    namespace: &str,
+    renames: &[(String, String)],
+) -> anyhow::Result<()> {
+    // keys go through placeholder names first, so that they can swap names.
+    // the updated_at trigger only matches rows by their old key, so set it here
+    let placeholder = |i: usize| format!("\0rename\0{}", i);
+
+    with_busy_retry(config, || {
+        let tx = conn.transaction()?;
+
+        {
+            let mut rename_q = tx.prepare_cached(
+                "
+            update entries
+            set key = ?3,
+            updated_at = strftime('%Y-%m-%d %H:%M:%f', 'NOW')
+            where namespace = ?1
+            and key = ?2
+            ",
+            )?;
+
+            let mut rename_alias_targets_q = tx.prepare_cached(
+                "
+            update aliases
+            set target_key = ?3
+            where target_namespace = ?1
+            and target_key = ?2
+            ",
+            )?;
+
+            // expired keys keep their names until `gc`
+            let mut delete_expired_q = tx.prepare_cached(
+                "
+            delete from entries
+            where namespace = ?
+            and key = ?
+            and expires_at <= strftime('%Y-%m-%d %H:%M:%f', 'NOW')
+            ",
+            )?;
+
+            for (i, (old, _)) in renames.iter().enumerate() {
+                rename_q.execute(params![namespace, old, placeholder(i)])?;
+                rename_alias_targets_q.execute(params![namespace, old, placeholder(i)])?;
+            }
+
+            for (i, (_, new)) in renames.iter().enumerate() {
+                delete_expired_q.execute([namespace, new])?;
+                rename_q.execute(params![namespace, placeholder(i), new])?;
+                rename_alias_targets_q.execute(params![namespace, placeholder(i), new])?;
+            }
+        }
+
+        tx.commit()
+    })?;
+
+    if send_hooks {
+        for (old, new) in renames {
+            send_webhooks(
+                conn,
+                config,
+                ChangeOp::Delete,
+                &Key {
+                    namespace,
+                    name: old,
+                },
+            )?;
+            send_webhooks(
+                conn,
+                config,
+                ChangeOp::Set,
+                &Key {
+                    namespace,
+                    name: new,
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves the keys in `from` matching `glob` to `to`, returning the keys moved
+/// and how many were skipped because they were already in `to`
+fn move_keys(
+    conn: &mut Connection,
+    config: &Config,
+    send_hooks: bool,
+    glob: &str,
+    from: &str,
+    to: &str,
+    on_conflict: ConflictStrategy,
+) -> anyhow::Result<(Vec<String>, usize)> {
+    let glob = namespace_glob(glob);
+
+    let (moved, skipped) = with_busy_retry(config, || {
+        let tx = conn.transaction()?;
+
+        // expired keys keep their names until `gc`
+        tx.execute(
+            "
+        delete from entries
+        where namespace = ?2
+        and expires_at <= strftime('%Y-%m-%d %H:%M:%f', 'NOW')
+        and key in (select key from entries where namespace = ?1 and key glob ?3)
+        ",
+            [from, to, &glob],
+        )?;
+
+        let (matched, conflicts): (i64, i64) = tx.query_one(
+            "
+        select
+            count(*),
+            count(t.key)
+        from entries s
+        left join entries t
+        on t.namespace = ?2
+        and t.key = s.key
+        where s.namespace = ?1
+        and s.key glob ?3
+        and (s.expires_at is null or s.expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        ",
+            [from, to, &glob],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        let replace = match on_conflict {
+            ConflictStrategy::Fail if conflicts > 0 => {
+                // dropping the transaction rolls it back
+                return Ok(Err(conflicts));
+            }
+            ConflictStrategy::Fail | ConflictStrategy::Skip => None,
+            ConflictStrategy::Overwrite => Some("1"),
+            ConflictStrategy::Newest => Some("s.updated_at > entries.updated_at"),
+        };
+
+        if let Some(replace) = replace {
+            tx.execute(
+                &format!(
+                    "
+            delete from entries
+            where namespace = ?2
+            and exists (
+                select 1
+                from entries s
+                where s.namespace = ?1
+                and s.key = entries.key
+                and s.key glob ?3
+                and (s.expires_at is null or s.expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+                and {}
+            )
+            ",
+                    replace
+                ),
+                [from, to, &glob],
+            )?;
+        }
+
+        let movable = "
+            namespace = ?1
+            and key glob ?3
+            and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+            and not exists (
+                select 1
+                from entries t
+                where t.namespace = ?2
+                and t.key = entries.key
+            )";
+
+        tx.execute(
+            &format!(
+                "
+        update aliases
+        set target_namespace = ?2
+        where target_namespace = ?1
+        and target_key in (select key from entries where {})
+        ",
+                movable
+            ),
+            [from, to, &glob],
+        )?;
+
+        // the updated_at trigger only matches rows by their old namespace, so set it here
+        let moved: Vec<String> = tx
+            .prepare(&format!(
+                "
+        update entries
+        set namespace = ?2,
+        updated_at = strftime('%Y-%m-%d %H:%M:%f', 'NOW')
+        where {}
+        returning key
+        ",
+                movable
+            ))?
+            .query_map([from, to, &glob], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+
+        tx.commit()?;
+
+        let skipped = matched as usize - moved.len();
+
+        Ok(Ok((moved, skipped)))
+    })?
+    .map_err(|conflicts| {
+        BladeError::Conflict(format!(
+            "{} of the keys to move already exist in {}. Pass --on-conflict to say what to do",
+            conflicts, to
+        ))
+    })?;
+
+    if send_hooks {
+        for key in &moved {
+            send_webhooks(
+                conn,
+                config,
+                ChangeOp::Delete,
+                &Key {
+                    namespace: from,
+                    name: key,
+                },
+            )?;
+            send_webhooks(
+                conn,
+                config,
+                ChangeOp::Set,
+                &Key {
+                    namespace: to,
+                    name: key,
+                },
+            )?;
+        }
+    }
+
+    Ok((moved, skipped))
+}
+
+/// Copies the keys in `source` matching `filter` to `destination`, with `key_prefix`
+/// before their names, returning how many were copied
+#[allow(clippy::too_many_arguments)]
+fn copy_namespace(
+    conn: &mut Connection,
+    config: &Config,
+    send_hooks: bool,
+    source: &str,
+    destination: &str,
+    key_prefix: &str,
+    filter: &str,
+    overwrite: bool,
+) -> anyhow::Result<usize> {
+    let filter = namespace_glob(filter);
+
+    let copied = with_busy_retry(config, || {
+        let tx = conn.transaction()?;
+
+        // expired keys keep their names until `gc`
+        tx.execute(
+            "
+        delete from entries
+        where namespace = ?2
+        and expires_at <= strftime('%Y-%m-%d %H:%M:%f', 'NOW')
+        and key in (select ?3 || key from entries where namespace = ?1 and key glob ?4)
+        ",
+            [source, destination, key_prefix, &filter],
+        )?;
+
+        if !overwrite {
+            let existing: Option<String> = tx
+                .query_one(
+                    "
+                select
+                    d.key
+                from entries s
+                join entries d
+                on d.namespace = ?2
+                and d.key = ?3 || s.key
+                where s.namespace = ?1
+                and s.key glob ?4
+                and (s.expires_at is null or s.expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+                limit 1
+                ",
+                    [source, destination, key_prefix, &filter],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            if let Some(existing) = existing {
+                // dropping the transaction rolls it back
+                return Ok(Err(existing));
+            }
+        }
+
+        let copied: Vec<String> = tx
+            .prepare(
+                "
+        insert into entries (
+            namespace,
+            key,
+            value,
+            expires_at,
+            content_type,
+            source_url,
+            source_headers,
+            value_type,
+            note
+        )
+        select
+            ?2,
+            ?3 || key,
+            value,
+            expires_at,
+            content_type,
+            source_url,
+            source_headers,
+            value_type,
+            note
+        from entries
+        where namespace = ?1
+        and key glob ?4
+        and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        on conflict do update
+        set value = excluded.value,
+        expires_at = excluded.expires_at,
+        content_type = excluded.content_type,
+        source_url = excluded.source_url,
+        source_headers = excluded.source_headers,
+        value_type = excluded.value_type,
+        note = excluded.note
+        returning key
+        ",
+            )?
+            .query_map([source, destination, key_prefix, &filter], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+
+        tx.commit()?;
+
+        Ok(Ok(copied))
+    })?
+    .map_err(|existing| {
+        BladeError::Conflict(format!(
+            "{}@{} already exists. Pass --overwrite to replace it",
+            existing, destination
+        ))
+    })?;
+
+    if send_hooks {
+        for key in &copied {
+            send_webhooks(
+                conn,
+                config,
+                ChangeOp::Set,
+                &Key {
+                    namespace: destination,
+                    name: key,
+                },
+            )?;
+        }
+    }
+
+    Ok(copied.len())
+}
+
+fn check_shell_variable(variable: &str) -> anyhow::Result<()> {
+    let mut chars = variable.chars();
+
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if !valid {
+        return Err(BladeError::Usage(format!("not a shell variable name: {}", variable)).into());
+    }
+
+    Ok(())
+}
+
+/// `key` as a shell variable name, like `DB_HOST` for `db-host`
+fn shell_variable_name(key: &str) -> String {
+    let mut variable: String = key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if !variable.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        variable.insert(0, '_');
+    }
+
+    variable
+}
+
+/// Writes `variable='value'`, with single quotes in the value written as `'\''`,
+/// so that the value survives `eval` as-is, newlines and all
+fn write_shell_assignment(
+    out: &mut impl Write,
+    variable: &str,
+    namespaced_key: &str,
+    value: &[u8],
+) -> anyhow::Result<()> {
+    if value.contains(&0) {
+        return Err(BladeError::Usage(format!(
+            "{} contains a NUL byte, which shell variables can't hold",
+            namespaced_key
+        ))
+        .into());
+    }
+
+    out.write_all(variable.as_bytes())?;
+    out.write_all(b"=")?;
+    write_shell_quoted(out, value)?;
+    out.write_all(b"\n")?;
+
+    Ok(())
+}
+
+fn write_shell_quoted(out: &mut impl Write, value: &[u8]) -> std::io::Result<()> {
+    out.write_all(b"'")?;
+
+    for (i, part) in value.split(|b| *b == b'\'').enumerate() {
+        if i > 0 {
+            out.write_all(b"'\\''")?;
+        }
+        out.write_all(part)?;
+    }
+
+    out.write_all(b"'")
+}
+
+/// The keys, as `key@namespace`, in namespaces matching `namespace` whose `column`,
+/// `value` or `note`, `matches` accepts. Keys without a note never match on notes
+fn find_keys(
+    conn: &Connection,
+    namespace: &str,
+    column: &str,
+    mut matches: impl FnMut(&[u8]) -> bool,
+) -> anyhow::Result<Vec<String>> {
+    let mut q = conn.prepare(&format!(
+        "
+    select
+        namespace,
+        key,
+        {}
+    from entries
+    where namespace glob ?
+    and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+    order by namespace, key
+    ",
+        column
+    ))?;
+
+    let mut rows = q.query([namespace_glob(namespace)])?;
+
+    let mut found = vec![];
+
+    while let Some(row) = rows.next()? {
+        if let Some(value) = row.get_ref(2)?.as_bytes_or_null()?
+            && matches(value)
+        {
+            let namespace: String = row.get(0)?;
+            let key: String = row.get(1)?;
+            found.push(format!("{}@{}", key, namespace));
+        }
+    }
+
+    Ok(found)
+}
+
+/// The entries `pick` and `delete --interactive` choose from, and how they're shown.
+/// `*` in `namespace` matches anything, and then keys are shown as `key@namespace`
+#[allow(clippy::type_complexity)]
+fn entries_to_pick(
+    conn: &Connection,
+    namespace: &str,
+) -> anyhow::Result<(Vec<(String, String, Vec<u8>)>, Vec<String>)> {
+    let entries: Vec<(String, String, Vec<u8>)> = conn
+        .prepare(
+            "
+        select
+            namespace,
+            key,
+            value
+        from entries
+        where namespace glob ?
+        and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        order by namespace, key
+        ",
+        )?
+        .query_map([namespace_glob(namespace)], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    if entries.is_empty() {
+        return Err(BladeError::NotFound(format!("no keys in {}", namespace)).into());
+    }
+
+    let is_pattern = namespace.contains('*');
+
+    let names = entries
+        .iter()
+        .map(|(namespace, key, _)| {
+            if is_pattern {
+                format!("{}@{}", key, namespace)
+            } else {
+                key.clone()
+            }
+        })
+        .collect();
+
+    Ok((entries, names))
+}
+
+/// Sets `key` to `value` in `tx`, enforcing the namespace's quota, if it has one,
+/// by evicting entries. When the quota can't be met, this reports that the namespace
+/// would be over quota, and `tx` should be rolled back
+fn set_value(
+    tx: &rusqlite::Transaction,
+    config: &Config,
+    key: &Key,
+    value: &[u8],
+    ttl: Option<std::time::Duration>,
+    source: Option<&UrlSource>,
+    value_type: Option<ValueType>,
+) -> rusqlite::Result<SetOutcome> {
+    let ttl_seconds = ttl.map(|ttl| ttl.as_secs_f64());
+
+    let set = |tx: &rusqlite::Transaction| -> rusqlite::Result<()> {
+        tx.prepare_cached(SET_QUERY)?.execute(params![
+            key.namespace,
+            key.name,
+            value,
+            ttl_seconds
+        ])?;
+
+        // a plain `set` replaces a downloaded value, so it forgets where it came from
+        tx.prepare_cached(
+            "
+        update entries
+        set content_type = ?3,
+        source_url = ?4,
+        source_headers = ?5
+        where namespace = ?1
+        and key = ?2
+        and (source_url is not null or ?4 is not null)
+        ",
+        )?
+        .execute(params![
+            key.namespace,
+            key.name,
+            source.and_then(|source| source.content_type.as_deref()),
+            source.map(|source| source.url.as_str()),
+            source.map(UrlSource::stored_headers),
+        ])?;
+
+        if let Some(value_type) = value_type {
+            tx.prepare_cached(
+                "
+            update entries
+            set value_type = ?
+            where namespace = ?
+            and key = ?
+            ",
+            )?
+            .execute([value_type.as_str(), key.namespace, key.name])?;
+        }
+
+        Ok(())
+    };
+
+    let Some(namespace_config) = config.namespaces.get(key.namespace) else {
+        set(tx)?;
+        return Ok(SetOutcome::Set { evicted: vec![] });
+    };
+
+    if let Some(max_bytes) = namespace_config.max_bytes
+        && value.len() as u64 > max_bytes
+    {
+        return Ok(SetOutcome::OverQuota(format!(
+            "value is larger than max_bytes ({}) for namespace {}",
+            max_bytes, key.namespace
+        )));
+    }
+
+    set(tx)?;
+
+    let max_entries = namespace_config.max_entries.map(|n| n as i64);
+    let max_bytes = namespace_config.max_bytes.map(|n| n as i64);
+
+    let eviction = namespace_config.eviction();
+
+    let outcome = match eviction {
+        Eviction::None => {
+            let (entries, bytes): (i64, i64) = tx.query_one(
+                "
+            select
+                count(*),
+                coalesce(sum(length(value)), 0)
+            from entries
+            where namespace = ?
+            and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+            ",
+                [key.namespace],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+
+            match (max_entries, max_bytes) {
+                (Some(max_entries), _) if entries > max_entries => SetOutcome::OverQuota(format!(
+                    "namespace {} would be over max_entries ({})",
+                    key.namespace, max_entries
+                )),
+                (_, Some(max_bytes)) if bytes > max_bytes => SetOutcome::OverQuota(format!(
+                    "namespace {} would be over max_bytes ({})",
+                    key.namespace, max_bytes
+                )),
+                _ => SetOutcome::Set { evicted: vec![] },
+            }
+        }
+        Eviction::Lru | Eviction::Fifo => {
+            // keep the most recently used (or inserted) entries,
+            // starting with the one just set, while they fit.
+            // Expired entries don't count, and are left for `gc`
+            let evicted = tx
+                .prepare_cached(
+                    "
+            delete from entries
+            where namespace = ?1
+            and key in (
+                select key from (
+                    select
+                        key,
+                        count(*) over recency as entries,
+                        sum(length(value)) over recency as bytes
+                    from entries
+                    where namespace = ?1
+                    and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+                    window recency as (
+                        order by
+                            key = ?2 desc,
+                            case
+                                when ?5 then inserted_at
+                                else max(coalesce(last_accessed_at, updated_at), updated_at)
+                            end desc
+                        rows unbounded preceding
+                    )
+                )
+                where entries > coalesce(?3, entries)
+                or bytes > coalesce(?4, bytes)
+            )
+            returning key
+            ",
+                )?
+                .query_map(
+                    params![
+                        key.namespace,
+                        key.name,
+                        max_entries,
+                        max_bytes,
+                        eviction == Eviction::Fifo
+                    ],
+                    |row| row.get(0),
+                )?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+
+            if !evicted.is_empty() {
+                debug!(
+                    evicted = evicted.len(),
+                    namespace = key.namespace,
+                    "evicted entries"
+                );
+            }
+
+            SetOutcome::Set { evicted }
+        }
+    };
+
+    Ok(outcome)
+}
+
+/// Follows aliases from `key` to the key they ultimately point to.
+/// Returns `key` itself if it isn't an alias
+fn resolve_alias(conn: &Connection, key: &Key) -> anyhow::Result<(String, String)> {
+    let mut chain = alias_chain(conn, key)?;
+    Ok(chain.pop().expect("the chain always starts with key"))
+}
+
+/// `key`, followed by every alias or key it points to, in order
+fn alias_chain(conn: &Connection, key: &Key) -> anyhow::Result<Vec<(String, String)>> {
+    let mut q = conn.prepare_cached(
+        "
+    select
+        target_namespace,
+        target_key
+    from aliases
+    where namespace = ?
+    and alias = ?
+    ",
+    )?;
+
+    let mut current = (key.namespace.to_string(), key.name.to_string());
+    let mut chain = vec![];
+
+    while let Some(target) = q
+        .query_one([&current.0, &current.1], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()?
+    {
+        chain.push(current);
+
+        if chain.contains(&target) {
+            return Err(
+                BladeError::Corrupt(format!("alias cycle at {}@{}", target.1, target.0)).into(),
+            );
+        }
+
+        current = target;
+    }
+
+    chain.push(current);
+
+    Ok(chain)
+}
+
+/// Larger values make QR codes too dense to scan from a terminal
+const QR_MAX_BYTES: usize = 1024;
+
+/// `value` as a QR code drawn with unicode half blocks, two rows per line
+fn render_qr(namespaced_key: &str, value: &[u8]) -> anyhow::Result<String> {
+    if value.len() > QR_MAX_BYTES {
+        return Err(BladeError::Usage(format!(
+            "{} is too large for a QR code: {} bytes, and the limit is {}",
+            namespaced_key,
+            value.len(),
+            QR_MAX_BYTES
+        ))
+        .into());
+    }
+
+    let code = qrcode::QrCode::new(value)
+        .map_err(|e| BladeError::Usage(format!("could not make a QR code: {}", e)))?;
+
+    // light on dark, so it scans on terminals with dark backgrounds too
+    Ok(code
+        .render::<qrcode::render::unicode::Dense1x2>()
+        .dark_color(qrcode::render::unicode::Dense1x2::Light)
+        .light_color(qrcode::render::unicode::Dense1x2::Dark)
+        .build())
+}
+
+/// An fzf-style picker on the terminal. Typing filters `items` by fuzzy match,
+/// up and down move, and enter picks the highlighted item.
+/// With `multi`, tab marks items, and enter picks the marked ones,
+/// or the highlighted one if none are marked.
+/// `preview` is shown under the list for the highlighted item.
+/// Returns the indexes of the picked items, or `None` if cancelled with escape
+fn pick(
+    items: &[String],
+    multi: bool,
+    preview: impl Fn(usize) -> String,
+) -> anyhow::Result<Option<Vec<usize>>> {
+    use console::Key;
+    use fuzzy_matcher::FuzzyMatcher;
+
+    let term = console::Term::stderr();
+
+    if !term.is_term() {
+        return Err(BladeError::Usage("picking keys needs a terminal".to_string()).into());
+    }
+
+    let matcher = fuzzy_matcher::skim::SkimMatcherV2::default();
+
+    let (rows, columns) = term.size();
+    let width = columns as usize;
+    let list_height = (rows as usize / 2).clamp(3, 15);
+    let preview_height = (rows as usize).saturating_sub(list_height + 4).clamp(1, 15);
+
+    // every line is cut to the terminal's width, so each takes exactly one row to clear
+    let fit =
+        |line: &str| console::truncate_str(&line.replace('\t', "    "), width, "…").to_string();
+
+    let mut query = String::new();
+    let mut cursor = 0;
+    let mut marked = std::collections::BTreeSet::new();
+    let mut drawn = 0;
+
+    term.hide_cursor()?;
+
+    let picked = loop {
+        let mut matches: Vec<(i64, usize)> = items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| matcher.fuzzy_match(item, &query).map(|score| (score, i)))
+            .collect();
+
+        // best first, and in their original order when the scores are the same
+        matches.sort_by_key(|(score, i)| (std::cmp::Reverse(*score), *i));
+
+        cursor = cursor.min(matches.len().saturating_sub(1));
+
+        let mut lines = vec![fit(&format!("> {}", query))];
+
+        // scroll so the cursor is always in view
+        let first = (cursor + 1).saturating_sub(list_height);
+
+        for (row, (_, i)) in matches.iter().enumerate().skip(first).take(list_height) {
+            let line = fit(&format!(
+                "{}{}{}",
+                if row == cursor { "▶ " } else { "  " },
+                match (multi, marked.contains(i)) {
+                    (false, _) => "",
+                    (true, true) => "◉ ",
+                    (true, false) => "○ ",
+                },
+                items[*i]
+            ));
+
+            lines.push(if row == cursor {
+                console::style(line).bold().to_string()
+            } else {
+                line
+            });
+        }
+
+        let counts = if multi {
+            format!(
+                "  {}/{}, {} marked",
+                matches.len(),
+                items.len(),
+                marked.len()
+            )
+        } else {
+            format!("  {}/{}", matches.len(), items.len())
+        };
+        lines.push(console::style(fit(&counts)).dim().to_string());
+        lines.push(console::style("─".repeat(width)).dim().to_string());
+
+        if let Some((_, i)) = matches.get(cursor) {
+            lines.extend(preview(*i).lines().take(preview_height).map(fit));
+        }
+
+        term.clear_last_lines(drawn)?;
+
+        for line in &lines {
+            term.write_line(line)?;
+        }
+
+        drawn = lines.len();
+
+        match term.read_key()? {
+            Key::Char(c) if !c.is_control() => {
+                query.push(c);
+                cursor = 0;
+            }
+            Key::Backspace => {
+                query.pop();
+                cursor = 0;
+            }
+            Key::ArrowUp => cursor = cursor.saturating_sub(1),
+            Key::ArrowDown => cursor += 1,
+            Key::Tab if multi => {
+                if let Some((_, i)) = matches.get(cursor) {
+                    if !marked.remove(i) {
+                        marked.insert(*i);
+                    }
+
+                    cursor += 1;
+                }
+            }
+            Key::Enter => {
+                if !marked.is_empty() {
+                    break Some(marked.into_iter().collect());
+                }
+
+                if let Some((_, i)) = matches.get(cursor) {
+                    break Some(vec![*i]);
+                }
+            }
+            Key::Escape | Key::CtrlC => break None,
+            _ => (),
+        }
+    };
+
+    term.clear_last_lines(drawn)?;
+    term.show_cursor()?;
+
+    Ok(picked)
+}
+
+/// Copies `value` with the first clipboard tool that's installed
+fn copy_to_clipboard(value: &[u8]) -> anyhow::Result<()> {
+    let tools: [(&str, &[&str]); 5] = [
+        ("pbcopy", &[]),
+        ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]),
+        ("xsel", &["--clipboard", "--input"]),
+        ("clip.exe", &[]),
+    ];
+
+    for (program, args) in tools {
+        let mut child = match std::process::Command::new(program)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("could not run {}", program)),
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(value)?;
+        }
+
+        let status = child.wait()?;
+
+        if !status.success() {
+            return Err(anyhow!("{} failed: {}", program, status));
+        }
+
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "no clipboard tool found. Tried pbcopy, wl-copy, xclip, xsel, and clip.exe"
+    ))
+}
+
+/// Opens `value` in `$VISUAL` or `$EDITOR` (default: `vi`), returning what was saved
+fn edit_value(value: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(value)?;
+    file.flush()?;
+
+    // through the shell, so editors with arguments like `code --wait` work
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(file.path())
+        .status()
+        .with_context(|| format!("could not run {}", editor))?;
+
+    if !status.success() {
+        return Err(anyhow!("{} failed: {}", editor, status));
+    }
+
+    Ok(std::fs::read(file.path())?)
+}
+
+/// How `get --render` shows a value on a terminal.
+/// JSON and TOML are detected by parsing them, and any other text is treated as markdown
+fn render_value(value: &str, width: usize) -> String {
+    let trimmed = value.trim_start();
+
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && let Ok(json) = serde_json::from_str::<serde_json::Value>(value)
+    {
+        let mut out = String::new();
+        render_json(&json, 0, &mut out);
+        out.push('\n');
+        return out;
+    }
+
+    if value.contains('=') && toml::from_str::<toml::Table>(value).is_ok() {
+        return render_toml(value);
+    }
+
+    render_markdown(value, width)
+}
+
+fn render_json(value: &serde_json::Value, depth: usize, out: &mut String) {
+    let indent = |depth: usize| "  ".repeat(depth);
+
+    match value {
+        serde_json::Value::Null => out.push_str(&console::style("null").magenta().to_string()),
+        serde_json::Value::Bool(b) => out.push_str(&console::style(b).yellow().to_string()),
+        serde_json::Value::Number(n) => out.push_str(&console::style(n).cyan().to_string()),
+        serde_json::Value::String(s) => out.push_str(
+            &console::style(serde_json::Value::String(s.clone()))
+                .green()
+                .to_string(),
+        ),
+        serde_json::Value::Array(items) if items.is_empty() => out.push_str("[]"),
+        serde_json::Value::Array(items) => {
+            out.push_str("[\n");
+
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(&indent(depth + 1));
+                render_json(item, depth + 1, out);
+                out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+            }
+
+            out.push_str(&indent(depth));
+            out.push(']');
+        }
+        serde_json::Value::Object(fields) if fields.is_empty() => out.push_str("{}"),
+        serde_json::Value::Object(fields) => {
+            out.push_str("{\n");
+
+            for (i, (key, value)) in fields.iter().enumerate() {
+                out.push_str(&indent(depth + 1));
+                out.push_str(
+                    &console::style(serde_json::Value::String(key.clone()))
+                        .blue()
+                        .bold()
+                        .to_string(),
+                );
+                out.push_str(": ");
+                render_json(value, depth + 1, out);
+                out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+            }
+
+            out.push_str(&indent(depth));
+            out.push('}');
+        }
+    }
+}
+
+fn render_toml(value: &str) -> String {
+    let mut out = String::new();
+
+    for line in value.lines() {
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with('[') {
+            out.push_str(&console::style(line).cyan().bold().to_string());
+        } else if trimmed.starts_with('#') {
+            out.push_str(&console::style(line).dim().to_string());
+        } else if let Some((key, rest)) = line.split_once('=') {
+            out.push_str(&console::style(key).blue().bold().to_string());
+            out.push('=');
+            out.push_str(&console::style(rest).green().to_string());
+        } else {
+            out.push_str(line);
+        }
+
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Greedily wraps `text` to `width` columns, not counting color codes,
+/// starting the first line with `first_prefix` and the rest with `prefix`
+fn wrap_text(text: &str, width: usize, first_prefix: &str, prefix: &str) -> String {
+    let mut out = String::new();
+
+    for (i, line) in text.split('\n').enumerate() {
+        let mut line_prefix = if i == 0 { first_prefix } else { prefix };
+        let mut column = 0;
+
+        for word in line.split(' ').filter(|word| !word.is_empty()) {
+            let word_width = console::measure_text_width(word);
+
+            if column > 0 && column + 1 + word_width > width {
+                out.push('\n');
+                column = 0;
+            }
+
+            if column == 0 {
+                out.push_str(line_prefix);
+                column = console::measure_text_width(line_prefix);
+                line_prefix = prefix;
+            } else {
+                out.push(' ');
+                column += 1;
+            }
+
+            out.push_str(word);
+            column += word_width;
+        }
+
+        if column == 0 {
+            out.push_str(line_prefix.trim_end());
+        }
+
+        out.push('\n');
+    }
+
+    out
+}
+
+fn render_markdown(value: &str, width: usize) -> String {
+    use pulldown_cmark::{Event, Tag, TagEnd};
+
+    let mut out = String::new();
+    // the text of the paragraph, heading, or list item being rendered
+    let mut block = String::new();
+    let mut bold = 0;
+    let mut italic = 0;
+    let mut in_code_block = false;
+    let mut links = vec![];
+    // the next number of each ordered list, or `None` for bulleted lists
+    let mut lists: Vec<Option<u64>> = vec![];
+    let mut item_prefix = None;
+    let mut quote_depth = 0;
+
+    let flush = |out: &mut String,
+                 block: &mut String,
+                 item_prefix: &mut Option<String>,
+                 lists: &[Option<u64>],
+                 quote_depth: usize| {
+        if block.trim().is_empty() && item_prefix.is_none() {
+            block.clear();
+            return;
+        }
+
+        let quote = "│ ".repeat(quote_depth);
+        let indent = format!("{}{}", quote, "  ".repeat(lists.len()));
+        let first_prefix = match item_prefix.take() {
+            Some(item_prefix) => format!(
+                "{}{}{}",
+                quote,
+                "  ".repeat(lists.len().saturating_sub(1)),
+                item_prefix
+            ),
+            None => indent.clone(),
+        };
+
+        out.push_str(&wrap_text(block.trim(), width, &first_prefix, &indent));
+        block.clear();
+    };
+
+    for event in pulldown_cmark::Parser::new_ext(value, pulldown_cmark::Options::all()) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => bold += 1,
+            Event::End(TagEnd::Heading(_)) => {
+                bold -= 1;
+                let heading = console::style(block.trim()).bold().underlined().to_string();
+                out.push_str(&wrap_text(&heading, width, "", ""));
+                out.push('\n');
+                block.clear();
+            }
+            Event::End(TagEnd::Paragraph) => {
+                flush(&mut out, &mut block, &mut item_prefix, &lists, quote_depth);
+
+                if lists.is_empty() {
+                    out.push('\n');
+                }
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                flush(&mut out, &mut block, &mut item_prefix, &lists, quote_depth);
+                quote_depth += 1;
+            }
+            Event::End(TagEnd::BlockQuote(_)) => quote_depth -= 1,
+            Event::Start(Tag::List(start)) => {
+                flush(&mut out, &mut block, &mut item_prefix, &lists, quote_depth);
+                lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+
+                if lists.is_empty() {
+                    out.push('\n');
+                }
+            }
+            Event::Start(Tag::Item) => {
+                item_prefix = Some(match lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "• ".to_string(),
+                });
+            }
+            Event::End(TagEnd::Item) => {
+                flush(&mut out, &mut block, &mut item_prefix, &lists, quote_depth)
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                flush(&mut out, &mut block, &mut item_prefix, &lists, quote_depth);
+                in_code_block = true;
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                in_code_block = false;
+                out.push('\n');
+            }
+            Event::Start(Tag::Strong) => bold += 1,
+            Event::End(TagEnd::Strong) => bold -= 1,
+            Event::Start(Tag::Emphasis) => italic += 1,
+            Event::End(TagEnd::Emphasis) => italic -= 1,
+            Event::Start(Tag::Link { dest_url, .. }) => links.push(dest_url),
+            Event::End(TagEnd::Link) => {
+                if let Some(url) = links.pop() {
+                    block.push_str(&console::style(format!(" ({})", url)).dim().to_string());
+                }
+            }
+            Event::Text(text) if in_code_block => {
+                for line in text.lines() {
+                    out.push_str("    ");
+                    out.push_str(&console::style(line).yellow().to_string());
+                    out.push('\n');
+                }
+            }
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                let mut styled = console::style(text.to_string());
+
+                if bold > 0 {
+                    styled = styled.bold();
+                }
+
+                if italic > 0 {
+                    styled = styled.italic();
+                }
+
+                if !links.is_empty() {
+                    styled = styled.underlined();
+                }
+
+                block.push_str(&styled.to_string());
+            }
+            Event::Code(code) => block.push_str(&console::style(code).yellow().to_string()),
+            Event::SoftBreak => block.push(' '),
+            Event::HardBreak => block.push('\n'),
+            Event::Rule => {
+                out.push_str(&"─".repeat(width));
+                out.push_str("\n\n");
+            }
+            Event::TaskListMarker(checked) => {
+                block.push_str(if checked { "[x] " } else { "[ ] " });
+            }
+            _ => (),
+        }
+    }
+
+    flush(&mut out, &mut block, &mut item_prefix, &lists, quote_depth);
+
+    // blocks are separated by a blank line, but there's no need for one at the end
+    while out.ends_with("\n\n") {
+        out.pop();
+    }
+
+    out
+}
+
+/// Writes `template` to `out`, with each `{{ key[@namespace] }}` replaced by its value
+fn render_template(conn: &Connection, template: &str, out: &mut dyn Write) -> anyhow::Result<()> {
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        out.write_all(&rest.as_bytes()[..start])?;
+
+        let after_open = &rest[start + 2..];
+
+        let end = after_open.find("}}").ok_or_else(|| {
+            BladeError::Usage(format!(
+                "unclosed placeholder: {{{{{}",
+                after_open.lines().next().unwrap_or_default()
+            ))
+        })?;
+
+        let namespaced_key = after_open[..end].trim();
+        let key = split_maybe_qualified_key(namespaced_key)?;
+        let (namespace, name) = resolve_alias(conn, &key)?;
+
+        let value = get_value(
+            conn,
+            &Key {
+                namespace: &namespace,
+                name: &name,
+            },
+        )?
+        .ok_or_else(|| BladeError::NotFound(format!("key not found: {}", namespaced_key)))?;
+
+        out.write_all(&value)?;
+
+        rest = &after_open[end + 2..];
+    }
+
+    out.write_all(rest.as_bytes())?;
+
+    Ok(())
+}
+
+fn init_logging(verbose: u8, log_format: OutputFormat) {
+    let filter = tracing_subscriber::EnvFilter::try_from_env("BLADE_LOG").unwrap_or_else(|_| {
+        tracing_subscriber::EnvFilter::new(match verbose {
+            0 => "warn",
+            1 => "warn,blade=debug",
+            _ => "warn,blade=trace",
+        })
+    });
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_env_filter(filter);
+
+    match log_format {
+        OutputFormat::Text => subscriber.init(),
+        OutputFormat::Json => subscriber.json().init(),
+    }
+}
+
+/// Errors that should exit with a specific code.
+/// Anything else is classified by `ErrorKind::of`
+#[derive(Debug)]
+enum BladeError {
+    NotFound(String),
+    Usage(String),
+    Corrupt(String),
+    Conflict(String),
+    OverQuota(String),
+    Rejected(String),
+    TimedOut(String),
+}
+
+impl Display for BladeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BladeError::NotFound(message)
+            | BladeError::Usage(message)
+            | BladeError::Corrupt(message)
+            | BladeError::Conflict(message)
+            | BladeError::OverQuota(message)
+            | BladeError::Rejected(message)
+            | BladeError::TimedOut(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for BladeError {}
+
+/// The kind of an error, with its exit code.
+/// These codes are stable, so new kinds must be added at the end
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ErrorKind {
+    NotFound = 1,
+    Usage = 2,
+    Locked = 3,
+    Corrupt = 4,
+    Io = 5,
+    Conflict = 6,
+    Other = 7,
+    OverQuota = 8,
+    Rejected = 9,
+    TimedOut = 10,
+}
+
+impl ErrorKind {
+    fn of(e: &anyhow::Error) -> Self {
+        for cause in e.chain() {
+            if let Some(e) = cause.downcast_ref::<BladeError>() {
+                return match e {
+                    BladeError::NotFound(_) => ErrorKind::NotFound,
+                    BladeError::Usage(_) => ErrorKind::Usage,
+                    BladeError::Corrupt(_) => ErrorKind::Corrupt,
+                    BladeError::Conflict(_) => ErrorKind::Conflict,
+                    BladeError::OverQuota(_) => ErrorKind::OverQuota,
+                    BladeError::Rejected(_) => ErrorKind::Rejected,
+                    BladeError::TimedOut(_) => ErrorKind::TimedOut,
+                };
+            }
+
+            if let Some(e) = cause.downcast_ref::<rusqlite::Error>() {
+                return match e.sqlite_error_code() {
+                    Some(
+                        rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked,
+                    ) => ErrorKind::Locked,
+                    Some(
+                        rusqlite::ErrorCode::DatabaseCorrupt | rusqlite::ErrorCode::NotADatabase,
+                    ) => ErrorKind::Corrupt,
+                    Some(
+                        rusqlite::ErrorCode::CannotOpen
+                        | rusqlite::ErrorCode::SystemIoFailure
+                        | rusqlite::ErrorCode::DiskFull
+                        | rusqlite::ErrorCode::PermissionDenied,
+                    ) => ErrorKind::Io,
+                    _ => ErrorKind::Other,
+                };
+            }
+
+            if cause.is::<std::io::Error>() {
+                return ErrorKind::Io;
+            }
+        }
+
+        ErrorKind::Other
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorReport {
+    kind: ErrorKind,
+    code: u8,
+    message: String,
+    causes: Vec<String>,
+}
+
+fn report_error(
+    err: &mut impl Write,
+    e: &anyhow::Error,
+    kind: ErrorKind,
+    errors: OutputFormat,
+) -> anyhow::Result<()> {
+    match errors {
+        OutputFormat::Text => writeln!(err, "Error: {:?}", e)?,
+        OutputFormat::Json => {
+            serde_json::to_writer(
+                &mut *err,
+                &serde_json::json!({
+                    "error": ErrorReport {
+                        kind,
+                        code: kind as u8,
+                        message: e.to_string(),
+                        causes: e.chain().skip(1).map(|cause| cause.to_string()).collect(),
+                    }
+                }),
+            )?;
+            err.write_all(b"\n")?;
+        }
+    }
+
+    Ok(())
+}
+
+fn log_statement(event: TraceEvent<'_>) {
+    if let TraceEvent::Profile(stmt, elapsed) = event {
+        let sql = stmt.sql().split_whitespace().collect::<Vec<_>>().join(" ");
+        debug!(%sql, ?elapsed, "executed statement");
+    }
+}
+
+/// `--timeout`, set before the command runs
+static TIMEOUT: std::sync::OnceLock<Duration> = std::sync::OnceLock::new();
+
+/// Set once `--timeout` has passed, so that waits for a busy database give up
+static TIMED_OUT: AtomicBool = AtomicBool::new(false);
+
+/// `sqlite_busy_timeout_ms`, for `wait_while_busy`
+static BUSY_TIMEOUT_MS: AtomicI32 = AtomicI32::new(0);
+
+/// Every connection the command opens, for `--timeout` to interrupt
+static INTERRUPT_HANDLES: Mutex<Vec<rusqlite::InterruptHandle>> = Mutex::new(vec![]);
+
+/// Files from `temp_file_for` that haven't been renamed into place yet
+static TEMP_FILES: Mutex<Vec<PathBuf>> = Mutex::new(vec![]);
+
+/// How long a timed out command has to stop after its queries are interrupted,
+/// before blade exits without it
+const TIMEOUT_GRACE: Duration = Duration::from_secs(1);
+
+fn lock_ignoring_poison<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// The busy handler when there's a `--timeout`. It waits like `busy_timeout` does,
+/// but stops once the timeout has passed
+fn wait_while_busy(attempts: i32) -> bool {
+    const WAIT_MS: i32 = 5;
+
+    if TIMED_OUT.load(Ordering::Relaxed)
+        || attempts.saturating_mul(WAIT_MS) >= BUSY_TIMEOUT_MS.load(Ordering::Relaxed)
+    {
+        return false;
+    }
+
+    std::thread::sleep(Duration::from_millis(WAIT_MS as u64));
+
+    true
+}
+
+fn timeout_error(timeout: Duration) -> anyhow::Error {
+    BladeError::TimedOut(format!("timed out after {:?}", timeout)).into()
+}
+
+/// A temporary file next to `path`, to write and then rename to `path`,
+/// so that a command that fails or times out doesn't leave part of a file there
+fn temp_file_for(path: &Path) -> anyhow::Result<tempfile::NamedTempFile> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let file = tempfile::Builder::new()
+        .prefix(".blade-")
+        .tempfile_in(dir)
+        .with_context(|| format!("could not create a temporary file in {}", dir.display()))?;
+
+    lock_ignoring_poison(&TEMP_FILES).push(file.path().to_path_buf());
+
+    Ok(file)
+}
+
+/// Writes `contents` to `path` through a file from [`temp_file_for`]
+fn write_file_atomically(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let mut file = temp_file_for(path)?;
+    file.write_all(contents)?;
+    file.persist(path)
+        .map_err(|e| e.error)
+        .with_context(|| format!("could not write {}", path.display()))?;
+    Ok(())
+}
+
+/// Interrupts the command's queries once `timeout` has passed, so that `run` rolls back
+/// and returns. Commands that are stuck somewhere else, like reading stdin,
+/// are exited without, after removing the files they were writing
+fn spawn_timeout_watchdog(timeout: Duration, errors: OutputFormat) {
+    let _ = TIMEOUT.set(timeout);
+
+    std::thread::spawn(move || {
+        std::thread::sleep(timeout);
+
+        TIMED_OUT.store(true, Ordering::Relaxed);
+
+        // SQLite only interrupts the statements running at the time,
+        // so keep interrupting until the command stops
+        let interrupted_at = Instant::now();
+        while interrupted_at.elapsed() < TIMEOUT_GRACE {
+            for handle in lock_ignoring_poison(&INTERRUPT_HANDLES).iter() {
+                handle.interrupt();
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        for path in lock_ignoring_poison(&TEMP_FILES).iter() {
+            let _ = std::fs::remove_file(path);
+        }
+
+        let mut message = vec![];
+        let e = timeout_error(timeout);
+        let _ = report_error(&mut message, &e, ErrorKind::TimedOut, errors);
+        write_stderr_unlocked(&message);
+
+        std::process::exit(ErrorKind::TimedOut as i32);
+    });
+}
+
+/// Writes to stderr without taking its lock, which the command might be holding
+fn write_stderr_unlocked(message: &[u8]) {
+    #[cfg(unix)]
+    {
+        use std::os::fd::FromRawFd;
+        // SAFETY: stderr stays open, and `ManuallyDrop` keeps this from closing it
+        let mut stderr = std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(2) });
+        let _ = stderr.write_all(message);
+    }
+
+    #[cfg(not(unix))]
+    let _ = message;
+}
+
+fn main() -> ExitCode {
+    let options = Options::parse();
+
+    init_logging(options.verbose, options.log_format);
+
+    let errors = options.errors;
+
+    if let Some(timeout) = options.timeout {
+        spawn_timeout_watchdog(timeout, errors);
+    }
+
+    match run(options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // whatever the command failed with, it was because it was interrupted
+            let e = match TIMEOUT.get() {
+                Some(&timeout) if TIMED_OUT.load(Ordering::Relaxed) => timeout_error(timeout),
+                _ => e,
+            };
+
+            let kind = ErrorKind::of(&e);
+
+            if let Err(report_error) = report_error(&mut std::io::stderr().lock(), &e, kind, errors)
+            {
+                eprintln!("Error: {:?}", e);
+                eprintln!("could not report error: {}", report_error);
+            }
+
+            ExitCode::from(kind as u8)
+        }
+    }
+}
+
+/// Runs a command on a hosted `libsql://` database, authenticating with `LIBSQL_AUTH_TOKEN`
+#[cfg(feature = "libsql")]
+fn run_remote(
+    url: &str,
+    config: &Config,
+    send_hooks: bool,
+    command: Command,
+) -> anyhow::Result<()> {
+    check_runs_on_backend(config, send_hooks, &command)?;
+
+    let auth_token = std::env::var("LIBSQL_AUTH_TOKEN").unwrap_or_default();
+
+    let start = Instant::now();
+    let backend = blade::remote::LibsqlBackend::open(url, &auth_token)?;
+    debug!(url, elapsed = ?start.elapsed(), "opened remote database");
+
+    if let Command::Set { namespaced_key, .. } = &command
+        && !command.sets_pairs()
+    {
+        let key = split_maybe_qualified_key(namespaced_key)?;
+
+        if backend.has_checks(key.namespace, key.name)? {
+            return Err(BladeError::Usage(format!(
+                "{} has a schema or a type, which libsql databases don't check",
+                namespaced_key
+            ))
+            .into());
+        }
+    }
+
+    run_on_backend(&backend, command)
+}
+
+/// `run_on_backend` only stores and reads keys, so it refuses changes that the config
+/// has hooks, webhooks, or quotas for, rather than skip them
+#[cfg(feature = "libsql")]
+fn check_runs_on_backend(
+    config: &Config,
+    send_hooks: bool,
+    command: &Command,
+) -> anyhow::Result<()> {
+    let (hooks, op) = match command {
+        Command::Set { .. } => ([&config.hooks.pre_set, &config.hooks.post_set], "set"),
+        Command::Delete { .. } => ([&config.hooks.post_delete, &None], "delete"),
+        _ => return Ok(()),
+    };
+
+    let refuse = |namespace: &str, what: &str| -> anyhow::Result<()> {
+        Err(BladeError::Usage(format!(
+            "can't {} keys in {} on a libsql database, which doesn't {}",
+            op, namespace, what
+        ))
+        .into())
+    };
+
+    for namespace in command.namespaces() {
+        if send_hooks && hooks.iter().any(|hook| hook.is_some()) {
+            refuse(&namespace, "run hooks. Pass --no-hooks to skip them")?;
+        }
+
+        let Some(namespace_config) = config.namespaces.get(&namespace) else {
+            continue;
+        };
+
+        if send_hooks && !namespace_config.webhooks.is_empty() {
+            refuse(&namespace, "send webhooks. Pass --no-hooks to skip them")?;
+        }
+
+        if op == "set"
+            && (namespace_config.max_entries.is_some() || namespace_config.max_bytes.is_some())
+        {
+            refuse(&namespace, "enforce quotas")?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "libsql"))]
+fn run_remote(
+    url: &str,
+    _config: &Config,
+    _send_hooks: bool,
+    _command: Command,
+) -> anyhow::Result<()> {
+    Err(BladeError::Usage(format!(
+        "{} is a libsql database, which needs blade to be built with the libsql feature",
+        url
+    ))
+    .into())
+}
+
+/// Runs the commands that only need a [`Backend`]: getting, setting, and deleting single keys,
+/// and listing a namespace. Everything else, like hooks, types, and expiry,
+/// needs a local database, and `run_remote` refuses changes that would need them
+#[cfg(feature = "libsql")]
+fn run_on_backend(backend: &impl Backend, command: Command) -> anyhow::Result<()> {
+    let sets_pairs = command.sets_pairs();
+
+    match command {
+        Command::Get {
+            namespaced_key: Some(namespaced_key),
+            stdin: false,
+            default,
+            default_file: None,
+            value_type: None,
+            render: false,
+            qr: false,
+            base64: false,
+            hex: false,
+            shell: None,
+            binary_ok,
+            newline,
+            meta: false,
+            ..
+        } => {
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+
+            let value = backend
+                .get(key.namespace, key.name)?
+                .or(default.map(String::into_bytes))
+                .ok_or_else(|| {
+                    BladeError::NotFound(format!("key not found: {}", namespaced_key))
+                })?;
+
+            let mut out = std::io::stdout().lock();
+
+            let is_terminal = out.is_terminal();
+
+            if is_terminal && !binary_ok {
+                check_not_binary(&namespaced_key, &value)?;
+            }
+
+            out.write_all(&value)?;
+
+            if is_terminal || newline {
+                out.write_all(b"\n")?;
+            }
+        }
+        Command::Set {
+            namespaced_key,
+            value,
+            more,
+            namespace: None,
+            ttl: None,
+            from_url: None,
+            header,
+            refresh: false,
+            value_type: None,
+            base64: false,
+            hex: false,
+            tee: false,
+        } if !sets_pairs && more.is_empty() && header.is_empty() => {
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+
+            let value = match value {
+                Some(value) => value.into_bytes(),
+                None => {
+                    let mut value = vec![];
+                    std::io::stdin().read_to_end(&mut value)?;
+                    value
+                }
+            };
+
+            backend.set(key.namespace, key.name, &value)?;
+        }
+        Command::Delete {
+            namespaced_keys,
+            interactive: false,
+            purge_history: false,
+        } => {
+            let mut deleted = 0;
+
+            for namespaced_key in &namespaced_keys {
+                let key = split_maybe_qualified_key(namespaced_key)?;
+
+                if backend.delete(key.namespace, key.name)? {
+                    deleted += 1;
+                }
+            }
+
+            if namespaced_keys.len() > 1 {
+                writeln!(
+                    std::io::stderr(),
+                    "deleted {} of {} keys",
+                    deleted,
+                    namespaced_keys.len()
+                )?;
+            }
+        }
+        Command::List {
+            namespace,
+            delimiter,
+            recently_used: false,
+            prefix,
+            wheres,
+            updated_within: None,
+            keys_only,
+            long: false,
+            limit: None,
+            cursor: None,
+        } if wheres.is_empty() => {
+            let namespace = namespace.unwrap_or_else(|| default_namespace().to_string());
+
+            if namespace.contains('*') {
+                return Err(BladeError::Usage(
+                    "namespace patterns don't work on libsql databases".to_string(),
+                )
+                .into());
+            }
+
+            let is_terminal = std::io::stdout().is_terminal();
+
+            let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+
+            for (key, value) in backend.scan(&namespace, &prefix)? {
+                out.write_all(key.as_bytes())?;
+                if keys_only {
+                    writeln!(out, "@{}", namespace)?;
+                } else {
+                    out.write_all(delimiter.as_bytes())?;
+                    write_value(&mut out, &value, is_terminal)?;
+                    out.write_all(b"\n")?;
+                }
+            }
+
+            out.flush()?;
+        }
+        _ => {
+            return Err(BladeError::Usage(
+                "only get, set, delete, and list work on libsql databases, without options \
+                that need a local database"
+                    .to_string(),
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+fn run(options: Options) -> anyhow::Result<()> {
+    // these have to work without a valid config file, or before there is one
+    match options.command {
+        Command::Init { force } => return init(force),
+        Command::Doctor => return doctor(options.db_location.as_deref()),
+        _ => (),
+    }
+
+    let start = Instant::now();
+    let config = get_or_create_config_file()?;
+    debug!(elapsed = ?start.elapsed(), "loaded config");
+
+    check_default_namespace(&config.default_namespace)?;
+    CONFIGURED_DEFAULT_NAMESPACE.get_or_init(|| config.default_namespace.clone());
+
+    let db_location = options
+        .db_location
+        .clone()
+        .unwrap_or_else(|| config.db_location.clone());
+
+    if let Some(url) = db_location
+        .to_str()
+        .filter(|location| location.starts_with("libsql://"))
+    {
+        return run_remote(url, &config, !options.no_hooks, options.command);
+    }
+
+    let start = Instant::now();
+    let conn = open_or_create_db(&db_location, &config)?;
+    debug!(db_location = %db_location.display(), elapsed = ?start.elapsed(), "opened database");
+
+    let start = Instant::now();
+    let mut conn = migrate_db(conn)?;
+    debug!(elapsed = ?start.elapsed(), "migrated database");
+
+    // keys set before `shard_by_namespace` was turned on would be hidden
+    if config.shard_by_namespace
+        && conn.query_one(
+            "
+        select exists(
+            select 1
+            from entries
+            where namespace not in (select namespace from attachments)
+        )
+        ",
+            [],
+            |row| row.get(0),
+        )?
+    {
+        return Err(BladeError::Usage(format!(
+            "{} has keys from before shard_by_namespace was turned on, which it would hide. \
+            To move them, turn it off and run `blade export > keys.jsonl` and \
+            `blade prune --older-than 0s`, then turn it on and run `blade import < keys.jsonl`",
+            db_location.display()
+        ))
+        .into());
+    }
+
+    if let Some(pattern) = options.command.spanned_namespaces() {
+        check_spans_one_db(&conn, &config, &pattern)?;
+    }
+
+    if let Some(namespace_db_location) =
+        namespace_db_location(&conn, &config, &db_location, &options.command.namespaces())?
+    {
+        let start = Instant::now();
+        conn = open_namespace_db(&namespace_db_location, &config)?;
+        debug!(db_location = %namespace_db_location.display(), elapsed = ?start.elapsed(), "opened namespace database");
+    }
+
+    let send_hooks = !options.no_hooks;
+
+    if let Some(limit) = config.expiry_sweep_limit {
+        let start = Instant::now();
+        // a failed sweep shouldn't fail the command, the next one will try again
+        match sweep_expired(&mut conn, &config, Some(limit)) {
+            Ok(swept) => {
+                debug!(swept = swept.len(), elapsed = ?start.elapsed(), "swept expired entries");
+
+                if send_hooks
+                    && let Err(e) = send_webhooks_for_each(&conn, &config, ChangeOp::Delete, &swept)
+                {
+                    warn!("{:#}", e);
+                }
+            }
+            Err(e) => warn!("could not sweep expired entries: {}", e),
+        }
+    }
+
+    let times = TimeDisplay {
+        format: options.time_format,
+        local: options.local && !options.utc,
+    };
+
+    let sets_pairs = options.command.sets_pairs();
+
+    match options.command {
+        Command::Get {
+            namespaced_key: None,
+            null,
+            delimiter,
+            default,
+            default_file,
+            value_type,
+            base64,
+            hex,
+            shell,
+            binary_ok,
+            ..
+        } => {
+            let delimiter = unescape(&delimiter);
+
+            if let Some(prefix) = &shell
+                && !prefix.is_empty()
+            {
+                check_shell_variable(prefix)?;
+            }
+
+            let default = match (default, default_file) {
+                (Some(default), _) => Some(default.into_bytes()),
+                (None, Some(default_file)) => Some(
+                    std::fs::read(&default_file)
+                        .with_context(|| format!("could not read {}", default_file.display()))?,
+                ),
+                (None, None) => None,
+            };
+
+            let mut input = vec![];
+            std::io::stdin().read_to_end(&mut input)?;
+
+            let separator = if null { b'\0' } else { b'\n' };
+
+            // the keys are read from the database their namespaces are kept in
+            let namespaces = input
+                .split(|b| *b == separator)
+                .filter_map(|namespaced_key| std::str::from_utf8(namespaced_key).ok())
+                .map(|namespaced_key| namespaced_key.trim_end_matches('\r'))
+                .filter(|namespaced_key| !namespaced_key.is_empty())
+                .filter_map(|namespaced_key| split_maybe_qualified_key(namespaced_key).ok())
+                .map(|key| key.namespace.to_string())
+                .collect::<Vec<_>>();
+
+            if let Some(namespace_db_location) =
+                namespace_db_location(&conn, &config, &db_location, &namespaces)?
+            {
+                conn = open_namespace_db(&namespace_db_location, &config)?;
+            }
+
+            let mut out = std::io::stdout().lock();
+
+            let is_terminal = out.is_terminal();
+
+            let mut missing = vec![];
+
+            let mut accessed = AccessLog::default();
+
+            // record what was read even if a later key fails
+            let result = (|| -> anyhow::Result<()> {
+                for namespaced_key in input.split(|b| *b == separator) {
+                    let namespaced_key = std::str::from_utf8(namespaced_key)
+                        .context("keys must be UTF-8")?
+                        .trim_end_matches('\r');
+
+                    if namespaced_key.is_empty() {
+                        continue;
+                    }
+
+                    let Some(value) = read_value(&conn, &config, namespaced_key, &mut accessed)?
+                        .or_else(|| default.clone())
+                    else {
+                        missing.push(namespaced_key.to_string());
+                        continue;
+                    };
+
+                    let value = match value_type {
+                        Some(value_type) => value_type.check(namespaced_key, &value)?,
+                        None => value,
+                    };
+
+                    let value = match Encoding::from_flags(base64, hex) {
+                        Some(encoding) => encoding.encode(&value),
+                        None => value,
+                    };
+
+                    if let Some(prefix) = &shell {
+                        let (name, _) = namespaced_key
+                            .split_once('@')
+                            .unwrap_or((namespaced_key, ""));
+                        let variable = format!("{}{}", prefix, shell_variable_name(name));
+                        write_shell_assignment(&mut out, &variable, namespaced_key, &value)?;
+                        continue;
+                    }
+
+                    if is_terminal && !binary_ok {
+                        check_not_binary(namespaced_key, &value)?;
+                    }
+
+                    out.write_all(&value)?;
+                    out.write_all(delimiter.as_bytes())?;
+                }
+
+                out.flush()?;
+
+                Ok(())
+            })();
+
+            accessed.flush(&conn, &config);
+
+            result?;
+
+            if !missing.is_empty() {
+                return Err(BladeError::NotFound(format!(
+                    "keys not found: {}",
+                    missing.join(", ")
+                ))
+                .into());
+            }
+        }
+        Command::Get {
+            namespaced_key: Some(namespaced_key),
+            default,
+            default_file,
+            value_type,
+            render,
+            qr,
+            base64,
+            hex,
+            shell,
+            binary_ok,
+            newline,
+            meta,
+            ..
+        } => {
+            if meta {
+                let key = split_maybe_qualified_key(&namespaced_key)?;
+                let (namespace, name) = resolve_alias(&conn, &key)?;
+
+                let key = Key {
+                    namespace: &namespace,
+                    name: &name,
+                };
+
+                let mut out = std::io::stdout().lock();
+                return write_entry_meta(&conn, &key, times, &mut out);
+            }
+
+            let mut accessed = AccessLog::default();
+
+            let value = read_value(&conn, &config, &namespaced_key, &mut accessed)?;
+
+            accessed.flush(&conn, &config);
+
+            let value = match value {
+                Some(value) => value,
+                None => {
+                    if let Some(default) = default {
+                        default.into_bytes()
+                    } else if let Some(default_file) = default_file {
+                        std::fs::read(&default_file)
+                            .with_context(|| format!("could not read {}", default_file.display()))?
+                    } else {
+                        return Err(BladeError::NotFound(format!(
+                            "key not found: {}",
+                            namespaced_key
+                        ))
+                        .into());
+                    }
+                }
+            };
+
+            let value = match value_type {
+                Some(value_type) => value_type.check(&namespaced_key, &value)?,
+                None => value,
+            };
+
+            let value = match Encoding::from_flags(base64, hex) {
+                Some(encoding) => encoding.encode(&value),
+                None => value,
+            };
+
+            let mut out = std::io::stdout();
+
+            if let Some(variable) = shell {
+                check_shell_variable(&variable)?;
+                write_shell_assignment(&mut out, &variable, &namespaced_key, &value)?;
+                return Ok(());
+            }
+
+            if qr {
+                writeln!(out, "{}", render_qr(&namespaced_key, &value)?)?;
+                return Ok(());
+            }
+
+            if render
+                && out.is_terminal()
+                && let Ok(value) = std::str::from_utf8(&value)
+            {
+                let (_, columns) = console::Term::stdout().size();
+                out.write_all(render_value(value, (columns as usize).min(100)).as_bytes())?;
+                return Ok(());
+            }
+
+            let is_terminal = out.is_terminal();
+
+            if is_terminal && !binary_ok {
+                check_not_binary(&namespaced_key, &value)?;
+            }
+
+            out.write_all(&value)?;
+
+            // anything else would change what's piped
+            if is_terminal || newline {
+                out.write_all(b"\n")?;
+            }
+        }
+        Command::Cat {
+            namespaced_keys,
+            delimiter,
+        } => {
+            let delimiter = unescape(&delimiter);
+
+            let mut out = std::io::stdout().lock();
+
+            let mut missing = vec![];
+
+            let mut first = true;
+
+            for namespaced_key in &namespaced_keys {
+                let key = split_maybe_qualified_key(namespaced_key)?;
+                let (namespace, name) = resolve_alias(&conn, &key)?;
+
+                let Some(value) = get_value(
+                    &conn,
+                    &Key {
+                        namespace: &namespace,
+                        name: &name,
+                    },
+                )?
+                else {
+                    missing.push(namespaced_key.as_str());
+                    continue;
+                };
+
+                if !first {
+                    out.write_all(delimiter.as_bytes())?;
+                }
+
+                out.write_all(&value)?;
+                first = false;
+            }
+
+            out.flush()?;
+
+            if !missing.is_empty() {
+                return Err(BladeError::NotFound(format!(
+                    "keys not found: {}",
+                    missing.join(", ")
+                ))
+                .into());
+            }
+        }
+        Command::Set {
+            namespaced_key,
+            value,
+            more,
+            namespace,
+            ttl,
+            value_type,
+            base64,
+            hex,
+            ..
+        } if sets_pairs => {
+            let namespace = namespace.unwrap_or_else(|| default_namespace().to_string());
+
+            let pairs = std::iter::once(&namespaced_key)
+                .chain(&value)
+                .chain(&more)
+                .map(|pair| {
+                    let (namespaced_key, value) = pair.split_once('=').ok_or_else(|| {
+                        BladeError::Usage(format!("expected key=value, not: {}", pair))
+                    })?;
+
+                    let value = match Encoding::from_flags(base64, hex) {
+                        Some(encoding) => encoding.decode(value.as_bytes())?,
+                        None => value.as_bytes().to_vec(),
+                    };
+
+                    let namespaced_key = if namespaced_key.contains('@') {
+                        namespaced_key.to_string()
+                    } else {
+                        format!("{}@{}", namespaced_key, namespace)
+                    };
+
+                    Ok((namespaced_key, value))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let entries = pairs
+                .iter()
+                .map(|(namespaced_key, value)| {
+                    Ok((
+                        namespaced_key.as_str(),
+                        split_maybe_qualified_key(namespaced_key)?,
+                        value.as_slice(),
+                    ))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            set_keys(
+                &mut conn,
+                &config,
+                send_hooks,
+                &entries,
+                &SetOptions {
+                    ttl,
+                    source: None,
+                    value_type,
+                    restores: None,
+                },
+            )?;
+        }
+        Command::Set {
+            namespaced_key,
+            value,
+            ttl,
+            from_url,
+            header,
+            refresh,
+            value_type,
+            base64,
+            hex,
+            tee,
+            ..
+        } => {
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+
+            let from_url = if refresh {
+                let (source_url, source_headers): (Option<String>, Option<String>) = conn
+                    .query_one(
+                        "
+                    select
+                        source_url,
+                        source_headers
+                    from entries
+                    where namespace = ?
+                    and key = ?
+                    ",
+                        [key.namespace, key.name],
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()?
+                    .ok_or_else(|| {
+                        BladeError::NotFound(format!("key not found: {}", namespaced_key))
+                    })?;
+
+                let source_url = source_url.ok_or_else(|| {
+                    BladeError::Usage(format!("{} was not set from a url", namespaced_key))
+                })?;
+
+                let headers = if header.is_empty() {
+                    source_headers
+                        .map(|headers| headers.lines().map(stored_header).collect())
+                        .transpose()?
+                        .unwrap_or_default()
+                } else {
+                    header
+                };
+
+                Some((source_url, headers))
+            } else {
+                from_url.map(|url| (url, header))
+            };
+
+            let mut source = None;
+
+            let value = if let Some((url, headers)) = from_url {
+                let (value, content_type) = fetch_url(&url, &headers)?;
+
+                source = Some(UrlSource {
+                    url,
+                    headers,
+                    content_type,
+                });
+
+                value
+            } else if let Some(value) = value {
+                value.into_bytes()
+            } else if tee {
+                let mut value = vec![];
+                let mut stdin = std::io::stdin().lock();
+                let mut out = std::io::stdout().lock();
+                let mut buf = [0u8; 64 * 1024];
+
+                loop {
+                    let n = match stdin.read(&mut buf) {
+                        Ok(0) => break,
+                        Ok(n) => n,
+                        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                        Err(e) => return Err(e.into()),
+                    };
+                    out.write_all(&buf[..n])?;
+                    out.flush()?;
+                    value.extend_from_slice(&buf[..n]);
+                }
+
+                value
+            } else {
+                let mut value = vec![];
+
+                std::io::stdin().read_to_end(&mut value)?;
+
+                value
+            };
+
+            let value = match Encoding::from_flags(base64, hex) {
+                Some(encoding) => encoding.decode(&value)?,
+                None => value,
+            };
+
+            set_key(
+                &mut conn,
+                &config,
+                send_hooks,
+                &namespaced_key,
+                &key,
+                &value,
+                &SetOptions {
+                    ttl,
+                    source,
+                    value_type,
+                    restores: None,
+                },
+            )?;
+        }
+        Command::SetFromEnv {
+            namespaced_key,
+            variable,
+            namespace,
+            ttl,
+        } => {
+            let value = std::env::var_os(&variable).ok_or_else(|| {
+                BladeError::NotFound(format!("environment variable not set: {}", variable))
+            })?;
+
+            let namespaced_key = match namespace {
+                Some(namespace) if !namespaced_key.contains('@') => {
+                    format!("{}@{}", namespaced_key, namespace)
+                }
+                _ => namespaced_key,
+            };
+
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+
+            set_key(
+                &mut conn,
+                &config,
+                send_hooks,
+                &namespaced_key,
+                &key,
+                &value.into_encoded_bytes(),
+                &SetOptions {
+                    ttl,
+                    source: None,
+                    value_type: None,
+                    restores: None,
+                },
+            )?;
+        }
+        Command::CaptureEnv {
+            prefix,
+            namespace,
+            ttl,
+        } => {
+            let namespace = namespace.unwrap_or_else(|| default_namespace().to_string());
+
+            let mut variables = std::env::vars_os()
+                .filter_map(|(variable, value)| {
+                    let name = variable.to_str()?.strip_prefix(&prefix)?;
+                    if name.is_empty() {
+                        return None;
+                    }
+                    Some((
+                        format!("{}@{}", name.to_lowercase(), namespace),
+                        value.into_encoded_bytes(),
+                    ))
+                })
+                .collect::<Vec<_>>();
+
+            if variables.is_empty() {
+                return Err(BladeError::NotFound(format!(
+                    "no environment variables start with {}",
+                    prefix
+                ))
+                .into());
+            }
+
+            variables.sort();
+
+            let entries = variables
+                .iter()
+                .map(|(namespaced_key, value)| {
+                    Ok((
+                        namespaced_key.as_str(),
+                        split_maybe_qualified_key(namespaced_key)?,
+                        value.as_slice(),
+                    ))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            set_keys(
+                &mut conn,
+                &config,
+                send_hooks,
+                &entries,
+                &SetOptions {
+                    ttl,
+                    source: None,
+                    value_type: None,
+                    restores: None,
+                },
+            )?;
+
+            writeln!(std::io::stdout(), "captured {} variables", entries.len())?;
+        }
+        Command::Push {
+            namespaced_key,
+            values,
+            json,
+        } => {
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+            let (namespace, name) = resolve_alias(&conn, &key)?;
+            let key = Key {
+                namespace: &namespace,
+                name: &name,
+            };
+
+            let pushed = values
+                .into_iter()
+                .map(|value| {
+                    if json {
+                        serde_json::from_str(&value).map_err(|e| {
+                            BladeError::Usage(format!("invalid JSON: {}: {}", value, e))
+                        })
+                    } else {
+                        Ok(serde_json::Value::String(value))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // reading the list and writing it back happen under the write lock,
+            // so concurrent pushes can't lose each other's elements
+            let pushed = with_busy_retry(&config, || {
+                let tx =
+                    conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+
+                let push = || -> anyhow::Result<(Vec<u8>, usize)> {
+                    let mut elements = match get_value(&tx, &key)? {
+                        Some(value) => json_array(&namespaced_key, &value)?,
+                        None => vec![],
+                    };
+
+                    elements.extend(pushed.iter().cloned());
+
+                    let value = serde_json::to_vec(&elements)?;
+
+                    if let Some(value_type) = get_value_type(&tx, &key)? {
+                        value_type.check(&namespaced_key, &value)?;
+                    }
+
+                    validate_value(&tx, &key, &value)?;
+
+                    if send_hooks && let Some(pre_set) = &config.hooks.pre_set {
+                        run_hook(pre_set, "pre-set", &key, &value)?;
+                    }
+
+                    Ok((value, elements.len()))
+                };
+
+                let (value, len) = match push() {
+                    Ok(pushed) => pushed,
+                    Err(e) => return Ok(Err(e)),
+                };
+
+                let evicted = match set_value(&tx, &config, &key, &value, None, None, None)? {
+                    SetOutcome::Set { evicted } => evicted,
+                    // dropping the transaction rolls it back
+                    SetOutcome::OverQuota(message) => {
+                        return Ok(Err(BladeError::OverQuota(message).into()));
+                    }
+                };
+
+                tx.commit()?;
+
+                Ok(Ok((value, len, evicted)))
+            })?;
+            let (value, len, evicted) = pushed?;
+
+            if send_hooks {
+                if let Some(post_set) = &config.hooks.post_set
+                    && let Err(e) = run_hook(post_set, "post-set", &key, &value)
+                {
+                    warn!("{:#}", e);
+                }
+
+                send_webhooks(&conn, &config, ChangeOp::Set, &key)?;
+
+                for name in &evicted {
+                    let evicted_key = Key {
+                        namespace: key.namespace,
+                        name,
+                    };
+                    send_webhooks(&conn, &config, ChangeOp::Delete, &evicted_key)?;
+                }
+            }
+
+            writeln!(std::io::stdout(), "{}", len)?;
+        }
+        Command::Lrange {
+            namespaced_key,
+            start,
+            stop,
+        } => {
+            let mut accessed = AccessLog::default();
+
+            let value =
+                read_value(&conn, &config, &namespaced_key, &mut accessed)?.ok_or_else(|| {
+                    BladeError::NotFound(format!("key not found: {}", namespaced_key))
+                })?;
+
+            accessed.flush(&conn, &config);
+
+            let elements = json_array(&namespaced_key, &value)?;
+
+            let len = elements.len() as i64;
+            let index = |i: i64| if i < 0 { len + i } else { i };
+            let start = index(start).max(0);
+            let stop = index(stop).min(len - 1);
+
+            let mut out = std::io::stdout().lock();
+
+            for element in elements
+                .iter()
+                .take((stop + 1).max(0) as usize)
+                .skip(start as usize)
+            {
+                match element {
+                    serde_json::Value::String(s) => writeln!(out, "{}", s)?,
+                    element => writeln!(out, "{}", element)?,
+                }
+            }
+        }
+        Command::Delete {
+            namespaced_keys,
+            interactive: false,
+            purge_history,
+        } => {
+            let keys = namespaced_keys
+                .iter()
+                .map(|namespaced_key| split_maybe_qualified_key(namespaced_key))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let deleted = delete_keys(&mut conn, &config, send_hooks, &keys, purge_history)?;
+
+            if keys.len() > 1 {
+                writeln!(
+                    std::io::stderr(),
+                    "deleted {} of {} keys",
+                    deleted,
+                    keys.len()
+                )?;
+            }
+        }
+        Command::Delete {
+            namespaced_keys,
+            interactive: true,
+            purge_history,
+        } => {
+            let namespace = match namespaced_keys.as_slice() {
+                [] => default_namespace().to_string(),
+                [namespace] => namespace.clone(),
+                _ => {
+                    return Err(
+                        BladeError::Usage("--interactive takes one namespace".to_string()).into(),
+                    );
+                }
+            };
+
+            let (entries, names) = entries_to_pick(&conn, &namespace)?;
+
+            let Some(picked) = pick(&names, true, |i| describe_value(&entries[i].2))? else {
+                return Ok(());
+            };
+
+            let mut err = std::io::stderr();
+
+            for i in &picked {
+                writeln!(err, "  {}", names[*i])?;
+            }
+
+            write!(err, "delete {} keys? [y/N] ", picked.len())?;
+            err.flush()?;
+
+            let mut answer = String::new();
+            std::io::stdin().read_line(&mut answer)?;
+
+            if !matches!(answer.trim(), "y" | "yes") {
+                return Ok(());
+            }
+
+            let keys: Vec<Key> = picked
+                .iter()
+                .map(|i| Key {
+                    namespace: &entries[*i].0,
+                    name: &entries[*i].1,
+                })
+                .collect();
+
+            let deleted = delete_keys(&mut conn, &config, send_hooks, &keys, purge_history)?;
+
+            writeln!(err, "deleted {} keys", deleted)?;
+        }
+        Command::FindValue {
+            substring,
+            regex,
+            namespace,
+            binary,
+            notes,
+        } => {
+            let pattern = match (substring, regex) {
+                (Some(substring), _) => regex::escape(&substring),
+                (None, Some(regex)) => regex,
+                (None, None) => unreachable!("clap requires one of them"),
+            };
+
+            let pattern = regex::bytes::Regex::new(&pattern)
+                .map_err(|e| BladeError::Usage(format!("invalid regex: {}", e)))?;
+
+            let column = if notes { "note" } else { "value" };
+
+            let found = find_keys(&conn, &namespace, column, |value| {
+                (binary || std::str::from_utf8(value).is_ok()) && pattern.is_match(value)
+            })?;
+
+            if found.is_empty() {
+                return Err(BladeError::NotFound("no values match".to_string()).into());
+            }
+
+            let mut out = std::io::stdout().lock();
+
+            for namespaced_key in found {
+                writeln!(out, "{}", namespaced_key)?;
+            }
+        }
+        Command::Which {
+            hash,
+            value_file,
+            namespace,
+        } => {
+            let found = if let Some(value_file) = value_file {
+                let expected = std::fs::read(&value_file)
+                    .with_context(|| format!("could not read {}", value_file.display()))?;
+
+                find_keys(&conn, &namespace, "value", |value| value == expected)?
+            } else {
+                let hash = hash.expect("clap requires one of them").to_lowercase();
+
+                find_keys(&conn, &namespace, "value", |value| {
+                    format!("{:x}", Sha256::digest(value)) == hash
+                })?
+            };
+
+            if found.is_empty() {
+                return Err(BladeError::NotFound("no values match".to_string()).into());
+            }
+
+            let mut out = std::io::stdout().lock();
+
+            for namespaced_key in found {
+                writeln!(out, "{}", namespaced_key)?;
+            }
+        }
+        Command::Pick { namespace, then } => {
+            let namespace = namespace.unwrap_or_else(|| default_namespace().to_string());
+
+            let (entries, names) = entries_to_pick(&conn, &namespace)?;
+
+            let Some(picked) = pick(&names, false, |i| describe_value(&entries[i].2))? else {
+                return Ok(());
+            };
+
+            let (namespace, name, value) = &entries[picked[0]];
+
+            let key = Key { namespace, name };
+
+            match then {
+                PickAction::Print => {
+                    let mut out = std::io::stdout();
+                    write_value(&mut out, value, std::io::stdout().is_terminal())?;
+                    out.write_all(b"\n")?;
+                }
+                PickAction::Delete => {
+                    delete_keys(&mut conn, &config, send_hooks, &[key], false)?;
+                }
+                PickAction::Clip => copy_to_clipboard(value)?,
+                PickAction::Edit => {
+                    let edited = edit_value(value)?;
+
+                    if edited != *value {
+                        set_key(
+                            &mut conn,
+                            &config,
+                            send_hooks,
+                            &format!("{}@{}", name, namespace),
+                            &key,
+                            &edited,
+                            &SetOptions::default(),
+                        )?;
+                    }
+                }
+            }
+        }
+        Command::Rename {
+            glob,
+            replace,
+            namespace,
+            dry_run,
+        } => {
+            let namespace = namespace.unwrap_or_else(|| default_namespace().to_string());
+
+            let renames = plan_renames(&conn, &namespace, &glob, &replace)?;
+
+            if renames.is_empty() {
+                return Err(BladeError::NotFound(format!("no keys match {}", glob)).into());
+            }
+
+            let mut out = std::io::stdout().lock();
+
+            if dry_run {
+                for (old, new) in &renames {
+                    writeln!(out, "{}\t{}", old, new)?;
+                }
+                return Ok(());
+            }
+
+            rename_keys(&mut conn, &config, send_hooks, &namespace, &renames)?;
+
+            writeln!(out, "renamed {} keys", renames.len())?;
+        }
+        Command::Move {
+            glob,
+            from,
+            to,
+            on_conflict,
+        } => {
+            let from = from.unwrap_or_else(|| default_namespace().to_string());
+
+            if from == to {
+                return Err(BladeError::Usage(format!("keys are already in {}", to)).into());
+            }
+
+            if to.trim().is_empty() {
+                return Err(BladeError::Usage("namespace cannot be empty".to_string()).into());
+            }
+
+            let (moved, skipped) = move_keys(
+                &mut conn,
+                &config,
+                send_hooks,
+                &glob,
+                &from,
+                &to,
+                on_conflict,
+            )?;
+
+            if moved.is_empty() && skipped == 0 {
+                return Err(BladeError::NotFound(format!("no keys match {}", glob)).into());
+            }
+
+            writeln!(
+                std::io::stdout(),
+                "moved {}, skipped {}",
+                moved.len(),
+                skipped
+            )?;
+        }
+        Command::Touch {
+            namespaced_key,
+            created,
+        } => {
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+
+            // the entries_updated_at trigger sets updated_at
+            let touched = with_busy_retry(&config, || {
+                conn.execute(
+                    "
+                update entries
+                set inserted_at = case when ? then strftime('%Y-%m-%d %H:%M:%f', 'NOW') else inserted_at end
+                where namespace = ?
+                and key = ?
+            ",
+                    params![created, key.namespace, key.name],
+                )
+            })?;
+
+            if touched == 0 {
+                return Err(
+                    BladeError::NotFound(format!("key not found: {}", namespaced_key)).into(),
+                );
+            }
+
+            if send_hooks {
+                send_webhooks(&conn, &config, ChangeOp::Touch, &key)?;
+            }
+        }
+        Command::Expire {
+            namespaced_key,
+            ttl,
+        } => {
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+
+            set_expiry(&conn, &config, send_hooks, &namespaced_key, &key, Some(ttl))?;
+        }
+        Command::Persist { namespaced_key } => {
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+
+            set_expiry(&conn, &config, send_hooks, &namespaced_key, &key, None)?;
+        }
+        #[cfg(feature = "fuser")]
+        Command::Mount { mountpoint } => {
+            mount::mount(conn, config, send_hooks, &mountpoint)?;
+        }
+        Command::History {
+            namespaced_key: Some(namespaced_key),
+            command: None,
+        } => {
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+            let (namespace, name) = resolve_alias(&conn, &key)?;
+
+            let mut q = conn.prepare(
+                "
+            select
+                version,
+                recorded_at,
+                value is null,
+                restored_version
+            from entry_history
+            where namespace = ?
+            and key = ?
+            order by version
+            ",
+            )?;
+
+            let mut rows = q.query([&namespace, &name])?;
+            let mut out = std::io::stdout().lock();
+            let mut any = false;
+
+            while let Some(row) = rows.next()? {
+                any = true;
+
+                let version: i64 = row.get(0)?;
+                let recorded_at: String = row.get(1)?;
+                let deleted: bool = row.get(2)?;
+                let restored_version: Option<i64> = row.get(3)?;
+
+                let change = match (deleted, restored_version) {
+                    (true, _) => "delete".to_string(),
+                    (false, Some(restored_version)) => format!("rollback to {}", restored_version),
+                    (false, None) => "set".to_string(),
+                };
+
+                writeln!(out, "{}\t{}\t{}", version, recorded_at, change)?;
+            }
+
+            if !any {
+                return Err(
+                    BladeError::NotFound(format!("no history for {}", namespaced_key)).into(),
+                );
+            }
+        }
+        Command::History {
+            command: Some(HistoryCommand::Purge { namespaced_key }),
+            ..
+        } => {
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+            let (namespace, name) = resolve_alias(&conn, &key)?;
+
+            // a deleted key's newest version has no value, so all of its history goes
+            let purged = with_busy_retry(&config, || {
+                conn.execute(
+                    "
+                delete from entry_history
+                where namespace = ?1
+                and key = ?2
+                and (
+                    value is null
+                    or version < (
+                        select max(version)
+                        from entry_history
+                        where namespace = ?1
+                        and key = ?2
+                    )
+                )
+                ",
+                    [&namespace, &name],
+                )
+            })?;
+
+            writeln!(std::io::stdout(), "purged {} versions", purged)?;
+        }
+        Command::History {
+            namespaced_key: None,
+            command: None,
+        } => unreachable!("clap requires a key or a subcommand"),
+        Command::Rollback {
+            namespaced_key,
+            to_version,
+            to,
+        } => {
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+            let (namespace, name) = resolve_alias(&conn, &key)?;
+            let key = Key {
+                namespace: &namespace,
+                name: &name,
+            };
+
+            let to = to.map(|to| since_timestamp(&conn, &to)).transpose()?;
+            validate_since(&conn, to.as_deref())?;
+
+            let (version, value): (i64, Option<Vec<u8>>) = conn
+                .query_one(
+                    "
+                select
+                    version,
+                    value
+                from entry_history
+                where namespace = ?1
+                and key = ?2
+                and (version = ?3 or (?3 is null and recorded_at <= strftime('%Y-%m-%d %H:%M:%f', ?4)))
+                order by version desc
+                limit 1
+                ",
+                    params![namespace, name, to_version, to],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?
+                .ok_or_else(|| {
+                    BladeError::NotFound(format!("no such version of {}", namespaced_key))
+                })?;
+
+            let value = value.ok_or_else(|| {
+                BladeError::NotFound(format!(
+                    "{} was deleted at version {}",
+                    namespaced_key, version
+                ))
+            })?;
+
+            set_key(
+                &mut conn,
+                &config,
+                send_hooks,
+                &namespaced_key,
+                &key,
+                &value,
+                &SetOptions {
+                    ttl: None,
+                    source: None,
+                    value_type: None,
+                    restores: Some(version),
+                },
+            )?;
+        }
+        Command::Ttl { namespaced_key } => {
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+
+            let remaining_seconds: Option<f64> = conn
+                .query_one(
+                    "
+                select (julianday(expires_at) - julianday('NOW')) * 86400
+                from entries
+                where namespace = ?
+                and key = ?
+                and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+                ",
+                    [key.namespace, key.name],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .ok_or_else(|| {
+                    BladeError::NotFound(format!("key not found: {}", namespaced_key))
+                })?;
+
+            let mut out = std::io::stdout().lock();
+
+            match remaining_seconds {
+                Some(remaining_seconds) => {
+                    writeln!(out, "{}", format_remaining(remaining_seconds.ceil() as i64))?
+                }
+                None => writeln!(out, "none")?,
+            }
+        }
+        Command::Gc { max } => {
+            let mut swept = sweep_expired(&mut conn, &config, max)?;
+            let mut trimmed = trim_history(&conn, &config)?;
+
+            // attached and sharded namespaces are collected too, with `--max` shared between them
+            for other_db_location in other_db_locations(&conn, &config, &db_location)? {
+                let max = max.map(|max| max.saturating_sub(swept.len() as u64));
+
+                let mut other = open_namespace_db(&other_db_location, &config)?;
+                swept.extend(sweep_expired(&mut other, &config, max)?);
+                trimmed += trim_history(&other, &config)?;
+            }
+
+            if send_hooks {
+                send_webhooks_for_each(&conn, &config, ChangeOp::Delete, &swept)?;
+            }
+
+            let mut out = std::io::stdout().lock();
+            writeln!(out, "deleted {} expired entries", swept.len())?;
+
+            if trimmed > 0 {
+                writeln!(out, "trimmed {} versions from history", trimmed)?;
+            }
+        }
+        Command::Prune {
+            namespace,
+            not_used_in,
+            older_than,
+            larger_than,
+            dry_run,
+        } => {
+            let (pruned, reclaimed_bytes) = with_busy_retry(&config, || {
+                let tx = conn.transaction()?;
+
+                let (pruned, reclaimed_bytes): (Vec<(String, String)>, i64) = {
+                    let mut q = tx.prepare(
+                        "
+                delete from entries
+                where (?1 is null or namespace glob ?1)
+                and (
+                    ?2 is null
+                    or julianday(max(coalesce(last_accessed_at, updated_at), updated_at))
+                        < julianday('now') - ?2 / 86400.0
+                )
+                and (?3 is null or julianday(updated_at) < julianday('now') - ?3 / 86400.0)
+                and (?4 is null or length(value) > ?4)
+                returning namespace, key, length(value)
+                ",
+                    )?;
+
+                    let rows = q.query_map(
+                        params![
+                            namespace.as_deref().map(namespace_glob),
+                            not_used_in.map(|d| d.as_secs_f64()),
+                            older_than.map(|d| d.as_secs_f64()),
+                            larger_than.map(|size| size as i64),
+                        ],
+                        |row| Ok((row.get(0)?, row.get(1)?, row.get::<_, i64>(2)?)),
+                    )?;
+
+                    let mut pruned = vec![];
+                    let mut reclaimed_bytes = 0;
+
+                    for row in rows {
+                        let (namespace, key, len) = row?;
+                        pruned.push((namespace, key));
+                        reclaimed_bytes += len;
+                    }
+
+                    (pruned, reclaimed_bytes)
+                };
+
+                if dry_run {
+                    tx.rollback()?;
+                } else {
+                    tx.commit()?;
+                }
+
+                Ok((pruned, reclaimed_bytes))
+            })?;
+
+            if send_hooks && !dry_run {
+                send_webhooks_for_each(&conn, &config, ChangeOp::Delete, &pruned)?;
+            }
+
+            let mut out = std::io::stdout().lock();
+
+            if dry_run {
+                writeln!(
+                    out,
+                    "would prune {} entries ({} bytes)",
+                    pruned.len(),
+                    reclaimed_bytes
+                )?;
+            } else {
+                writeln!(
+                    out,
+                    "pruned {} entries ({} bytes)",
+                    pruned.len(),
+                    reclaimed_bytes
+                )?;
+            }
+        }
+        Command::List {
+            namespace,
+            delimiter,
+            recently_used,
+            prefix,
+            wheres,
+            updated_within,
+            keys_only,
+            long,
+            limit,
+            cursor,
+        } => {
+            let namespace = namespace.unwrap_or_else(|| default_namespace().to_string());
+
+            let is_pattern = namespace.contains('*');
+
+            // equality rather than `glob` when we can, so the key range below
+            // can use the primary key index too
+            let (mut namespace_filter, namespace) = if is_pattern {
+                ("namespace glob ?1".to_string(), namespace_glob(&namespace))
+            } else {
+                ("namespace = ?1".to_string(), namespace)
+            };
+
+            // a range rather than `like`, so the primary key index is used.
+            // x'ff' never appears in UTF-8, so it sorts after every key with the prefix.
+            // without a prefix the range would only keep SQLite from walking
+            // the `(namespace, inserted_at)` index in order
+            let key_range = if prefix.is_empty() {
+                "and ?2 = ''"
+            } else {
+                "and key >= ?2 and key < ?2 || x'ff'"
+            };
+
+            let mut params: Vec<rusqlite::types::Value> = vec![
+                rusqlite::types::Value::Text(namespace.clone()),
+                rusqlite::types::Value::Text(prefix),
+            ];
+
+            if !wheres.is_empty() && !is_pattern {
+                // indexes from `index create` are partial indexes on the namespace,
+                // which SQLite only uses when the query has the namespace as a literal
+                namespace_filter.push_str(&format!(" and namespace = {}", sql_literal(&namespace)));
+            }
+
+            for filter in wheres {
+                params.push(filter.value);
+
+                namespace_filter.push_str(&format!(
+                    " and {} {} ?{}",
+                    json_path_expression(&filter.path),
+                    filter.operator,
+                    params.len()
+                ));
+            }
+
+            if let Some(updated_within) = updated_within {
+                params.push(rusqlite::types::Value::Text(format!(
+                    "-{} seconds",
+                    updated_within.as_secs_f64()
+                )));
+
+                namespace_filter.push_str(&format!(
+                    " and updated_at >= strftime('%Y-%m-%d %H:%M:%f', 'NOW', ?{})",
+                    params.len()
+                ));
+            }
+
+            let is_paged = limit.is_some() || cursor.is_some();
+
+            // keyset pagination, so each page is a seek in the primary key index
+            // rather than an `offset` that walks every entry before it
+            if let Some(cursor) = cursor {
+                let (after_namespace, after_key) = decode_list_cursor(&cursor)?;
+
+                if is_pattern {
+                    params.push(rusqlite::types::Value::Text(after_namespace));
+                    params.push(rusqlite::types::Value::Text(after_key));
+
+                    // SQLite only seeks on the parts of a row value comparison
+                    // after an equality, so spell the seek out
+                    namespace_filter.push_str(&format!(
+                        " and namespace >= ?{0} and (namespace, key) > (?{0}, ?{1})",
+                        params.len() - 1,
+                        params.len()
+                    ));
+                } else {
+                    if after_namespace != namespace {
+                        return Err(BladeError::Usage(format!(
+                            "this cursor is for namespace {}, not {}",
+                            after_namespace, namespace
+                        ))
+                        .into());
+                    }
+
+                    params.push(rusqlite::types::Value::Text(after_key));
+
+                    namespace_filter.push_str(&format!(" and key > ?{}", params.len()));
+                }
+            }
+
+            let order_by = if recently_used {
+                "and last_accessed_at is not null
+            order by last_accessed_at desc"
+                    .to_string()
+            } else if is_paged {
+                match limit {
+                    // one more than the limit, to know whether there's a next page
+                    Some(limit) => format!("order by namespace asc, key asc limit {}", limit + 1),
+                    None => "order by namespace asc, key asc".to_string(),
+                }
+            } else {
+                "order by inserted_at desc".to_string()
+            };
+
+            let mut q = conn.prepare(&format!(
+                "
+            select
+                namespace,
+                key,
+                value,
+                note
+            from entries
+            where {}
+            {}
+            and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+            {}
+            ",
+                namespace_filter, key_range, order_by
+            ))?;
+
+            let mut rows = q.query(rusqlite::params_from_iter(params))?;
+
+            let is_terminal = std::io::stdout().is_terminal();
+
+            // stdout is line buffered, which means a write per entry
+            let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+
+            let mut count = 0;
+
+            let mut last_listed: Option<(String, String)> = None;
+            let mut next_cursor = None;
+
+            // borrow each column from SQLite rather than copying it out
+            while let Some(row) = rows.next()? {
+                if Some(count) == limit {
+                    next_cursor = last_listed
+                        .take()
+                        .map(|(namespace, key)| encode_list_cursor(&namespace, &key));
+                    break;
+                }
+
+                out.write_all(row.get_ref(1)?.as_bytes()?)?;
+                if is_pattern || keys_only {
+                    out.write_all(b"@")?;
+                    out.write_all(row.get_ref(0)?.as_bytes()?)?;
+                }
+                if !keys_only {
+                    out.write_all(delimiter.as_bytes())?;
+                    write_value(&mut out, row.get_ref(2)?.as_bytes()?, is_terminal)?;
+                }
+                if long {
+                    out.write_all(delimiter.as_bytes())?;
+                    if let Some(note) = row.get_ref(3)?.as_bytes_or_null()? {
+                        out.write_all(note)?;
+                    }
+                }
+                out.write_all(b"\n")?;
+
+                count += 1;
+
+                if Some(count) == limit {
+                    last_listed = Some((row.get(0)?, row.get(1)?));
+                }
+            }
+
+            out.flush()?;
+
+            if let Some(next_cursor) = next_cursor {
+                eprintln!("{}", next_cursor);
+            }
+
+            debug!(rows = count, "listed entries");
+        }
+        Command::Keys { namespace, prefix } => {
+            let namespace = namespace.unwrap_or_else(|| default_namespace().to_string());
+
+            let is_pattern = namespace.contains('*');
+
+            let (namespace_filter, namespace) = if is_pattern {
+                ("namespace glob ?1", namespace_glob(&namespace))
+            } else {
+                ("namespace = ?1", namespace)
+            };
+
+            // everything here is in `entries_namespace_key_expires_at`,
+            // so SQLite never reads the values
+            let mut q = conn.prepare(&format!(
+                "
+            select
+                namespace,
+                key
+            from entries
+            where {}
+            and key >= ?2 and key < ?2 || x'ff'
+            and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+            order by namespace asc, key asc
+            ",
+                namespace_filter
+            ))?;
+
+            let mut rows = q.query([namespace, prefix])?;
+
+            let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+
+            while let Some(row) = rows.next()? {
+                out.write_all(row.get_ref(1)?.as_bytes()?)?;
+                if is_pattern {
+                    out.write_all(b"@")?;
+                    out.write_all(row.get_ref(0)?.as_bytes()?)?;
+                }
+                out.write_all(b"\n")?;
+            }
+
+            out.flush()?;
+        }
+        Command::Random { namespace, value } => {
+            let namespace = namespace.unwrap_or_else(|| default_namespace().to_string());
+
+            let key: Option<String> = conn
+                .query_row(
+                    "
+                select
+                    key
+                from entries
+                where namespace = ?1
+                and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+                order by random()
+                limit 1
+                ",
+                    [&namespace],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            let Some(key) = key else {
+                return Err(BladeError::NotFound(format!("no keys in {}", namespace)).into());
+            };
+
+            let mut out = std::io::stdout();
+
+            if !value {
+                writeln!(out, "{}", key)?;
+                return Ok(());
+            }
+
+            let namespaced_key = format!("{}@{}", key, namespace);
+
+            let mut accessed = AccessLog::default();
+
+            let value =
+                read_value(&conn, &config, &namespaced_key, &mut accessed)?.ok_or_else(|| {
+                    BladeError::NotFound(format!("key not found: {}", namespaced_key))
+                })?;
+
+            accessed.flush(&conn, &config);
+
+            let is_terminal = out.is_terminal();
+
+            if is_terminal {
+                check_not_binary(&namespaced_key, &value)?;
+            }
+
+            out.write_all(&value)?;
+
+            if is_terminal {
+                out.write_all(b"\n")?;
+            }
+        }
+        Command::Tree { namespace, prefix } => {
+            let namespace = namespace.unwrap_or_else(|| default_namespace().to_string());
+
+            let mut q = conn.prepare(
+                "
+            select
+                key
+            from entries
+            where namespace = ?1
+            and key >= ?2 and key < ?2 || x'ff'
+            and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+            order by key asc
+            ",
+            )?;
+
+            let rows = q.query_map([namespace, prefix], |row| row.get(0))?;
+
+            let mut out = std::io::stdout().lock();
+
+            let mut previous_dirs: Vec<String> = vec![];
+
+            for row in rows {
+                let key: String = row?;
+
+                let mut segments: Vec<&str> = key.split('/').collect();
+                let leaf = segments.pop().unwrap_or_default();
+
+                let shared = previous_dirs
+                    .iter()
+                    .zip(&segments)
+                    .take_while(|(previous, dir)| previous == *dir)
+                    .count();
+
+                for (depth, dir) in segments.iter().enumerate().skip(shared) {
+                    writeln!(out, "{}{}/", "  ".repeat(depth), dir)?;
+                }
+
+                // keys ending in `/` are just the directory
+                if !leaf.is_empty() {
+                    writeln!(out, "{}{}", "  ".repeat(segments.len()), leaf)?;
+                }
+
+                previous_dirs = segments.into_iter().map(String::from).collect();
+            }
+        }
+        // `--long` conflicts with `--tree`
+        Command::ListNamespaces { long: true, .. } => {
+            // namespaces can be described before they have any keys
+            let mut q = conn.prepare(&format!(
+                "
+            select
+                namespace,
+                keys,
+                {},
+                description
+            from (
+                select
+                    namespace,
+                    count(key) as keys,
+                    min(created_at) as created_at,
+                    max(description) as description
+                from (
+                    select namespace, key, inserted_at as created_at, null as description
+                    from entries
+                    union all
+                    select namespace, null, created_at, description
+                    from namespaces
+                )
+                group by namespace
+            )
+            order by namespace asc
+            ",
+                times.sql("created_at")
+            ))?;
+
+            let mut rows = q.query([])?;
+
+            let mut out = std::io::stdout().lock();
+
+            let mut count = 0;
+
+            while let Some(row) = rows.next()? {
+                let namespace: String = row.get(0)?;
+                let keys: i64 = row.get(1)?;
+                let description: Option<String> = row.get(3)?;
+
+                write!(out, "{}\t{}\t", namespace, keys)?;
+                times.write(&mut out, row.get_ref(2)?)?;
+                writeln!(out, "\t{}", description.unwrap_or_default())?;
+
+                count += 1;
+            }
+
+            debug!(rows = count, "listed namespaces");
+        }
+        Command::Direnv {
+            namespace: None, ..
+        } => {
+            let mut out = std::io::stdout();
+            out.write_all(DIRENV_STDLIB.as_bytes())?;
+        }
+        Command::Direnv {
+            namespace: Some(namespace),
+            ..
+        } => {
+            if namespace.contains('*') {
+                return Err(BladeError::Usage("direnv takes one namespace".to_string()).into());
+            }
+
+            let mut q = conn.prepare(
+                "
+            select
+                key,
+                value
+            from entries
+            where namespace = ?1
+            and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+            order by key
+            ",
+            )?;
+
+            let rows = q.query_map([&namespace], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+            // keys for each variable name
+            let mut variables: BTreeMap<String, String> = BTreeMap::new();
+
+            // printed once every key is checked, so that `eval` gets all or nothing
+            let mut exports = vec![];
+
+            for row in rows {
+                let (key, value): (String, Vec<u8>) = row?;
+                let variable = shell_variable_name(&key);
+
+                if let Some(other) = variables.get(&variable) {
+                    return Err(BladeError::Usage(format!(
+                        "{} and {} would both be exported as {}",
+                        other, key, variable
+                    ))
+                    .into());
+                }
+
+                exports.write_all(b"export ")?;
+                write_shell_assignment(
+                    &mut exports,
+                    &variable,
+                    &format!("{}@{}", key, namespace),
+                    &value,
+                )?;
+
+                variables.insert(variable, key);
+            }
+
+            let mut out = std::io::stdout().lock();
+
+            // direnv sets this while it evaluates an `.envrc`.
+            // writes land in the WAL until it's checkpointed, so it's watched too
+            if std::env::var_os("DIRENV_IN_ENVRC").is_some()
+                && let Some(path) = conn.path()
+                && !path.is_empty()
+            {
+                out.write_all(b"watch_file ")?;
+                write_shell_quoted(&mut out, path.as_bytes())?;
+                out.write_all(b" ")?;
+                write_shell_quoted(&mut out, format!("{}-wal", path).as_bytes())?;
+                out.write_all(b"\n")?;
+            }
+
+            out.write_all(&exports)?;
+        }
+        Command::ListNamespaces { tree, long: false } => {
+            let mut q = conn.prepare(
+                "
+            select namespace from entries
+            union
+            select namespace from namespaces
+            order by namespace asc
+            ",
+            )?;
+
+            let rows = q.query_map([], |row| row.get(0))?;
+
+            let mut out = std::io::stdout().lock();
+
+            let mut count = 0;
+
+            if tree {
+                let mut namespaces = rows.collect::<rusqlite::Result<Vec<String>>>()?;
+
+                // sort by segment, so `work-x` doesn't split up `work` and `work.a`
+                namespaces.sort_by(|a, b| a.split('.').cmp(b.split('.')));
+
+                let mut previous: Vec<&str> = vec![];
+
+                for namespace in &namespaces {
+                    let segments: Vec<&str> = namespace.split('.').collect();
+
+                    let shared = previous
+                        .iter()
+                        .zip(&segments)
+                        .take_while(|(previous, segment)| previous == segment)
+                        .count();
+
+                    for (depth, segment) in segments.iter().enumerate().skip(shared) {
+                        writeln!(out, "{}{}", "  ".repeat(depth), segment)?;
+                    }
+
+                    previous = segments;
+                    count += 1;
+                }
+            } else {
+                for row in rows {
+                    let row: String = row?;
+                    writeln!(out, "{}", row)?;
+
+                    count += 1;
+                }
+            }
+
+            debug!(rows = count, "listed namespaces");
+        }
+        Command::Dupes {
+            namespace,
+            delimiter,
+        } => {
+            let namespace = namespace.unwrap_or_else(|| default_namespace().to_string());
+
+            let mut q = conn.prepare(
+                "
+            select
+                key,
+                value
+            from entries
+            where namespace = ?1
+            and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+            and value in (
+                select value
+                from entries
+                where namespace = ?1
+                and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+                group by value
+                having count(*) > 1
+            )
+            order by value, key
+            ",
+            )?;
+
+            let progress = progress_spinner("finding duplicates");
+
+            let mut rows = q
+                .query_map([namespace], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .peekable();
+
+            // the first row is only available once the duplicates have been found
+            rows.peek();
+
+            progress.finish_and_clear();
+
+            let mut out = std::io::stdout().lock();
+
+            let mut previous_value: Option<Vec<u8>> = None;
+
+            for row in rows {
+                let (key, value): (String, Vec<u8>) = row?;
+
+                if previous_value.as_ref() == Some(&value) {
+                    out.write_all(delimiter.as_bytes())?;
+                } else {
+                    if previous_value.is_some() {
+                        out.write_all(b"\n")?;
+                    }
+                    previous_value = Some(value);
+                }
+
+                out.write_all(key.as_bytes())?;
+            }
+
+            if previous_value.is_some() {
+                out.write_all(b"\n")?;
+            }
+        }
+        Command::Diff {
+            namespaced_key1,
+            namespaced_key2,
+        } => {
+            let key1 = split_maybe_qualified_key(&namespaced_key1)?;
+            let key2 = split_maybe_qualified_key(&namespaced_key2)?;
+
+            let value1 = get_value(&conn, &key1)?.ok_or_else(|| {
+                BladeError::NotFound(format!("key not found: {}", namespaced_key1))
+            })?;
+            let value2 = get_value(&conn, &key2)?.ok_or_else(|| {
+                BladeError::NotFound(format!("key not found: {}", namespaced_key2))
+            })?;
+
+            if value1 == value2 {
+                return Ok(());
+            }
+
+            let mut out = std::io::stdout().lock();
+
+            match (std::str::from_utf8(&value1), std::str::from_utf8(&value2)) {
+                (Ok(text1), Ok(text2)) => {
+                    let diff = similar::TextDiff::from_lines(text1, text2);
+
+                    write!(
+                        out,
+                        "{}",
+                        diff.unified_diff()
+                            .missing_newline_hint(false)
+                            .header(&namespaced_key1, &namespaced_key2)
+                    )?;
+                }
+                _ => {
+                    writeln!(
+                        out,
+                        "binary values differ: {} ({} bytes), {} ({} bytes)",
+                        namespaced_key1,
+                        value1.len(),
+                        namespaced_key2,
+                        value2.len()
+                    )?;
+                }
+            }
+        }
+        Command::Render { template_file, key } => {
+            let template = if let Some(template_file) = template_file {
+                std::fs::read(&template_file)
+                    .with_context(|| format!("could not read {}", template_file.display()))?
+            } else if let Some(namespaced_key) = key {
+                let key = split_maybe_qualified_key(&namespaced_key)?;
+                let (namespace, name) = resolve_alias(&conn, &key)?;
+
+                get_value(
+                    &conn,
+                    &Key {
+                        namespace: &namespace,
+                        name: &name,
+                    },
+                )?
+                .ok_or_else(|| BladeError::NotFound(format!("key not found: {}", namespaced_key)))?
+            } else {
+                let mut template = vec![];
+                std::io::stdin().read_to_end(&mut template)?;
+                template
+            };
+
+            let template = String::from_utf8(template)
+                .map_err(|_| BladeError::Usage("template must be valid UTF-8".to_string()))?;
+
+            // render fully first, so a missing key doesn't leave half a file behind
+            let mut rendered = vec![];
+            render_template(&conn, &template, &mut rendered)?;
+
+            std::io::stdout().lock().write_all(&rendered)?;
+        }
+        Command::DbDiff {
+            other_db_location,
+            namespace,
+            format,
+        } => {
+            attach_db(&conn, &other_db_location, "other")?;
+
+            let mut q = conn.prepare(
+                "
+            select
+                'added',
+                o.namespace,
+                o.key
+            from other.entries o
+            where (?1 is null or o.namespace glob ?1)
+            and not exists (
+                select 1
+                from main.entries m
+                where m.namespace = o.namespace
+                and m.key = o.key
+            )
+            union all
+            select
+                'removed',
+                m.namespace,
+                m.key
+            from main.entries m
+            where (?1 is null or m.namespace glob ?1)
+            and not exists (
+                select 1
+                from other.entries o
+                where o.namespace = m.namespace
+                and o.key = m.key
+            )
+            union all
+            select
+                'changed',
+                m.namespace,
+                m.key
+            from main.entries m
+            join other.entries o
+            on o.namespace = m.namespace
+            and o.key = m.key
+            where (?1 is null or m.namespace glob ?1)
+            and o.value != m.value
+            order by 2, 3
+            ",
+            )?;
+
+            let rows = q.query_map([namespace.as_deref().map(namespace_glob)], |row| {
+                let change = match row.get_ref(0)?.as_str()? {
+                    "added" => Change::Added,
+                    "removed" => Change::Removed,
+                    _ => Change::Changed,
+                };
+
+                Ok(EntryChange {
+                    change,
+                    namespace: row.get(1)?,
+                    key: row.get(2)?,
+                })
+            })?;
+
+            let changes = rows.collect::<Result<Vec<_>, _>>()?;
+
+            let mut out = std::io::stdout().lock();
+
+            match format {
+                OutputFormat::Text => {
+                    for change in changes {
+                        writeln!(
+                            out,
+                            "{}\t{}@{}",
+                            change.change, change.key, change.namespace
+                        )?;
+                    }
+                }
+                OutputFormat::Json => {
+                    serde_json::to_writer_pretty(&mut out, &changes)?;
+                    out.write_all(b"\n")?;
+                }
+            }
+        }
+        Command::Merge {
+            other_db_location,
+            strategy,
+        } => {
+            // taking their side copies their metadata, so their columns have to be there
+            let namespaces = if other_db_location.exists() {
+                migrate_db(open_db_connection(&other_db_location, &config)?)?
+                    .prepare(
+                        "
+                    select distinct namespace
+                    from entries
+                    ",
+                    )?
+                    .query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()?
+            } else {
+                vec![]
+            };
+
+            // their keys are merged into the database their namespaces are kept in
+            if let Some(namespace_db_location) =
+                namespace_db_location(&conn, &config, &db_location, &namespaces)?
+            {
+                conn = open_namespace_db(&namespace_db_location, &config)?;
+            }
+
+            attach_db(&conn, &other_db_location, "other")?;
+
+            let tx = conn.transaction()?;
+
+            // our expired keys are as good as missing, so theirs are added in their place
+            let expired = tx
+                .prepare(
+                    "
+                delete from main.entries
+                where expires_at <= strftime('%Y-%m-%d %H:%M:%f', 'NOW')
+                returning namespace, key
+                ",
+                )?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<Vec<(String, String)>, _>>()?;
+
+            // their expired keys are left out, rather than coming back without an expiry
+            let mut written = tx
+                .prepare(
+                    "
+                insert into main.entries (
+                    namespace,
+                    key,
+                    value,
+                    inserted_at,
+                    updated_at,
+                    expires_at,
+                    content_type,
+                    source_url,
+                    source_headers,
+                    value_type,
+                    note
+                )
+                select
+                    o.namespace,
+                    o.key,
+                    o.value,
+                    strftime('%Y-%m-%d %H:%M:%f', o.inserted_at),
+                    strftime('%Y-%m-%d %H:%M:%f', o.updated_at),
+                    strftime('%Y-%m-%d %H:%M:%f', o.expires_at),
+                    o.content_type,
+                    o.source_url,
+                    o.source_headers,
+                    o.value_type,
+                    o.note
+                from other.entries o
+                where (o.expires_at is null or o.expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+                and not exists (
+                    select 1
+                    from main.entries m
+                    where m.namespace = o.namespace
+                    and m.key = o.key
+                )
+                returning namespace, key
+                ",
+                )?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<Vec<(String, String)>, _>>()?;
+
+            let added = written.len();
+
+            let conflicts = {
+                let mut q = tx.prepare(
+                    "
+                select
+                    m.namespace,
+                    m.key,
+                    m.value,
+                    o.value,
+                    julianday(o.updated_at) > julianday(m.updated_at)
+                from main.entries m
+                join other.entries o
+                on o.namespace = m.namespace
+                and o.key = m.key
+                where o.value != m.value
+                and (o.expires_at is null or o.expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+                order by m.namespace, m.key
+                ",
+                )?;
+
+                q.query_map([], |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                })?
+                .collect::<Result<Vec<(String, String, Vec<u8>, Vec<u8>, bool)>, _>>()?
+            };
+
+            let mut out = std::io::stdout().lock();
+
+            let mut updated = 0;
+
+            for (namespace, key, ours, theirs, theirs_is_newer) in &conflicts {
+                let take_theirs = match strategy {
+                    MergeStrategy::Newest => *theirs_is_newer,
+                    MergeStrategy::Ours => false,
+                    MergeStrategy::Theirs => true,
+                    MergeStrategy::Interactive => prompt_take_theirs(namespace, key, ours, theirs)?,
+                };
+
+                if take_theirs {
+                    // a replace doesn't fire the `updated_at` trigger, so their `updated_at`
+                    // is kept, and a later `--strategy newest` compares the right times
+                    tx.execute(
+                        "
+                        insert or replace into main.entries (
+                            namespace,
+                            key,
+                            value,
+                            inserted_at,
+                            updated_at,
+                            last_accessed_at,
+                            expires_at,
+                            content_type,
+                            source_url,
+                            source_headers,
+                            value_type,
+                            note
+                        )
+                        select
+                            o.namespace,
+                            o.key,
+                            o.value,
+                            m.inserted_at,
+                            strftime('%Y-%m-%d %H:%M:%f', o.updated_at),
+                            m.last_accessed_at,
+                            strftime('%Y-%m-%d %H:%M:%f', o.expires_at),
+                            o.content_type,
+                            o.source_url,
+                            o.source_headers,
+                            o.value_type,
+                            o.note
+                        from other.entries o
+                        join main.entries m
+                        on m.namespace = o.namespace
+                        and m.key = o.key
+                        where o.namespace = ?
+                        and o.key = ?
+                        ",
+                        params![namespace, key],
+                    )?;
+
+                    written.push((namespace.clone(), key.clone()));
+                    updated += 1;
+                }
+
+                writeln!(
+                    out,
+                    "conflict\t{}@{}\t{}",
+                    key,
+                    namespace,
+                    if take_theirs { "theirs" } else { "ours" }
+                )?;
+            }
+
+            tx.commit()?;
+
+            if send_hooks {
+                // an expired key that theirs replaced was deleted, then set
+                send_webhooks_for_each(&conn, &config, ChangeOp::Delete, &expired)?;
+                send_webhooks_for_each(&conn, &config, ChangeOp::Set, &written)?;
+            }
+
+            writeln!(
+                out,
+                "added {}, updated {}, conflicts {}",
+                added,
+                updated,
+                conflicts.len()
+            )?;
+        }
+        Command::Backup { to } => {
+            let to = backup_location(&to)?;
+
+            // remote locations are checked by the upload itself, which won't replace a backup
+            if let BackupLocation::File(path) = &to
+                && path.exists()
+            {
+                return Err(
+                    BladeError::Usage(format!("backup location already exists: {}", to)).into(),
+                );
+            }
+
+            let progress = progress_spinner("backing up");
+
+            match &to {
+                BackupLocation::File(path) => {
+                    // `vacuum into` can write to an empty file
+                    let temp = temp_file_for(path)?;
+                    conn.execute("vacuum into ?", [temp.path().to_string_lossy()])?;
+                    temp.persist_noclobber(path).map_err(|e| e.error)?;
+                }
+                // `vacuum into` writes a file, so remote snapshots are made locally first
+                remote => {
+                    let dir = tempfile::tempdir()?;
+                    let path = dir.path().join("blade.db");
+                    conn.execute("vacuum into ?", [path.to_string_lossy()])?;
+                    remote.upload(&conn, &path)?;
+                }
+            }
+
+            progress.finish_and_clear();
+        }
+        Command::Restore { from } => {
+            let from = backup_location(&from)?;
+
+            let progress = progress_spinner("restoring");
+
+            let dir = tempfile::tempdir()?;
+
+            let from = match from {
+                BackupLocation::File(path) if path.exists() => path,
+                BackupLocation::File(path) => {
+                    return Err(BladeError::Usage(format!(
+                        "backup does not exist: {}",
+                        path.display()
+                    ))
+                    .into());
+                }
+                remote => {
+                    let path = dir.path().join("blade.db");
+                    remote.download(&conn, &path)?;
+                    path
+                }
+            };
+
+            // the backup API copies the whole database, so expiry, notes, aliases,
+            // schemas, and namespace settings come back exactly as they were backed up
+            with_busy_retry(&config, || {
+                conn.restore(
+                    rusqlite::MAIN_DB,
+                    &from,
+                    None::<fn(rusqlite::backup::Progress)>,
+                )
+            })?;
+
+            // backups made by older versions get the same migrations as any other database
+            migrate_db(conn)?;
+
+            progress.finish_and_clear();
+        }
+        Command::Export {
+            namespace,
+            since,
+            format,
+            archive,
+            passphrase,
+            sign,
+            html,
+            consul,
+            etcd,
+            k8s_secret,
+            k8s_config_map,
+            k8s_namespace,
+            git_dir,
+        } => {
+            let since = since
+                .map(|since| since_timestamp(&conn, &since))
+                .transpose()?;
+
+            match (archive, html) {
+                _ if let Some(git_dir) = git_dir => {
+                    export_git_dir(&conn, namespace.as_deref(), since.as_deref(), &git_dir)?;
+                }
+                _ if let Some(name) = k8s_secret.as_ref().or(k8s_config_map.as_ref()) => {
+                    let mut out = std::io::stdout().lock();
+                    export_k8s_manifest(
+                        &conn,
+                        namespace.as_deref().unwrap(),
+                        since.as_deref(),
+                        k8s_config_map.is_some(),
+                        name,
+                        k8s_namespace.as_deref(),
+                        &mut out,
+                    )?;
+                }
+                _ if let Some((store, url)) =
+                    KvStore::from_flags(consul.as_deref(), etcd.as_deref()) =>
+                {
+                    export_to_kv_store(&conn, namespace.as_deref(), since.as_deref(), store, url)?;
+                }
+                (_, Some(html)) => {
+                    export_html(&conn, namespace.as_deref(), since.as_deref(), &html)?;
+                }
+                (Some(archive), None) => {
+                    write_archive(&archive, passphrase.as_deref(), |out| {
+                        export_entries(&conn, namespace.as_deref(), since.as_deref(), format, out)
+                    })?;
+
+                    if let Some(secret_key_path) = sign {
+                        sign_archive(&archive, &secret_key_path)?;
+                    }
+                }
+                (None, None) => {
+                    let mut out = std::io::stdout().lock();
+                    export_entries(
+                        &conn,
+                        namespace.as_deref(),
+                        since.as_deref(),
+                        format,
+                        &mut out,
+                    )?;
+                }
+            }
+        }
+        Command::Import {
+            format,
+            csv_options,
+            on_conflict,
+            dry_run,
+            archive,
+            passphrase,
+            verify,
+            redis,
+            redis_match,
+            consul,
+            etcd,
+            git_dir,
+        } => {
+            let summary = match (archive, redis) {
+                _ if let Some(git_dir) = git_dir => {
+                    let entries = read_git_dir_entries(&git_dir)?;
+                    import_entries(
+                        &mut conn,
+                        &config,
+                        &db_location,
+                        entries.into_iter().map(Ok),
+                        on_conflict,
+                        dry_run,
+                    )?
+                }
+                _ if let Some((store, url)) =
+                    KvStore::from_flags(consul.as_deref(), etcd.as_deref()) =>
+                {
+                    let entries =
+                        read_kv_store_entries(store, url, csv_options.namespace.as_deref())?;
+                    import_entries(
+                        &mut conn,
+                        &config,
+                        &db_location,
+                        entries.into_iter().map(Ok),
+                        on_conflict,
+                        dry_run,
+                    )?
+                }
+                (_, Some(url)) => {
+                    let namespace = csv_options
+                        .namespace
+                        .unwrap_or_else(|| default_namespace().to_string());
+                    let entries = read_redis_entries(
+                        &url,
+                        redis_match.as_deref().unwrap_or("*"),
+                        &namespace,
+                    )?;
+                    import_entries(
+                        &mut conn,
+                        &config,
+                        &db_location,
+                        entries.into_iter().map(Ok),
+                        on_conflict,
+                        dry_run,
+                    )?
+                }
+                (Some(archive), None) => {
+                    if let Some(public_key) = verify {
+                        let progress = progress_spinner("verifying signature");
+                        verify_archive(&archive, &public_key)?;
+                        progress.finish_and_clear();
+                    }
+
+                    let mut input = read_archive(&archive, passphrase.as_deref())?;
+                    import_from(
+                        &mut conn,
+                        &config,
+                        &db_location,
+                        &mut input,
+                        format,
+                        &csv_options,
+                        on_conflict,
+                        dry_run,
+                    )?
+                }
+                (None, None) => {
+                    let mut input = std::io::stdin().lock();
+                    import_from(
+                        &mut conn,
+                        &config,
+                        &db_location,
+                        &mut input,
+                        format,
+                        &csv_options,
+                        on_conflict,
+                        dry_run,
+                    )?
+                }
+            };
+
+            if send_hooks && !dry_run {
+                send_webhooks_for_each(&conn, &config, ChangeOp::Set, &summary.written)?;
+            }
+
+            let mut out = std::io::stdout();
+            writeln!(
+                out,
+                "{}created {}, updated {}, skipped {}",
+                if dry_run { "dry run: " } else { "" },
+                summary.created,
+                summary.updated,
+                summary.skipped
+            )?;
+        }
+        Command::GenerateSigningKey { secret_key_path } => {
+            let mut secret_key = [0u8; 32];
+            getrandom::fill(&mut secret_key).map_err(|e| anyhow!("{}", e))?;
+
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+            let mut f = options.open(&secret_key_path)?;
+            f.write_all((BASE64_STANDARD.encode(secret_key) + "\n").as_bytes())?;
+
+            let public_key = SigningKey::from_bytes(&secret_key).verifying_key();
+
+            let mut out = std::io::stdout();
+            writeln!(out, "{}", BASE64_STANDARD.encode(public_key.to_bytes()))?;
+        }
+        Command::Bench {
+            ops,
+            value_size,
+            concurrency,
+        } => {
+            if ops == 0 {
+                return Err(BladeError::Usage("ops must be greater than 0".to_string()).into());
+            }
+
+            if concurrency == 0 {
+                return Err(
+                    BladeError::Usage("concurrency must be greater than 0".to_string()).into(),
+                );
+            }
+
+            let dir = tempfile::tempdir()?;
+            let db_location = dir.path().join("bench.db");
+
+            migrate_db(open_or_create_db(&db_location, &config)?)?;
+
+            let value = vec![b'x'; value_size];
+
+            let key_name = |i: usize| format!("bench-{}", i);
+
+            let phases = [
+                (
+                    "set",
+                    run_bench_phase(&db_location, &config, ops, concurrency, |conn, i| {
+                        conn.prepare_cached(SET_QUERY)?
+                            .execute(params![DEFAULT_NAMESPACE, key_name(i), value, None::<f64>])
+                            .map(|_| ())
+                    })?,
+                ),
+                (
+                    "get",
+                    run_bench_phase(&db_location, &config, ops, concurrency, |conn, i| {
+                        let name = key_name(i);
+
+                        get_value(
+                            conn,
+                            &Key {
+                                namespace: DEFAULT_NAMESPACE,
+                                name: &name,
+                            },
+                        )
+                        .map(|_| ())
+                    })?,
+                ),
+                (
+                    "delete",
+                    run_bench_phase(&db_location, &config, ops, concurrency, |conn, i| {
+                        conn.prepare_cached(
+                            "
+                            delete from entries
+                            where namespace = ?
+                            and key = ?
+                            ",
+                        )?
+                        .execute(params![DEFAULT_NAMESPACE, key_name(i)])
+                        .map(|_| ())
+                    })?,
+                ),
+            ];
+
+            let mut out = std::io::stdout().lock();
+
+            writeln!(
+                out,
+                "ops: {}, value size: {} bytes, concurrency: {}, synchronous: {}, busy timeout: {}ms",
+                ops,
+                value_size,
+                concurrency,
+                config.sqlite_synchronous_mode,
+                config.sqlite_busy_timeout_ms
+            )?;
+
+            writeln!(
+                out,
+                "{:<8}{:>12}{:>12}{:>12}{:>12}{:>12}",
+                "op", "ops/s", "p50", "p90", "p99", "max"
+            )?;
+
+            for (name, phase) in phases {
+                writeln!(
+                    out,
+                    "{:<8}{:>12.0}{:>12}{:>12}{:>12}{:>12}",
+                    name,
+                    ops as f64 / phase.elapsed.as_secs_f64(),
+                    format!("{:.1?}", phase.percentile(0.5)),
+                    format!("{:.1?}", phase.percentile(0.9)),
+                    format!("{:.1?}", phase.percentile(0.99)),
+                    format!("{:.1?}", phase.percentile(1.0)),
+                )?;
+            }
+        }
+        Command::Query {
+            namespace,
+            wheres,
+            select,
+            delimiter,
+        } => {
+            let mut filters = vec![
+                "(expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))"
+                    .to_string(),
+            ];
+            let mut params: Vec<rusqlite::types::Value> = vec![];
+
+            if let Some(namespace) = namespace {
+                params.push(rusqlite::types::Value::Text(namespace_glob(&namespace)));
+                filters.push(format!("namespace glob ?{}", params.len()));
+            }
+
+            // only columns and operators from fixed lists are put in the query,
+            // and values are always parameters
+            for filter in wheres {
+                let value = match filter.value {
+                    QueryValue::Literal(value) => {
+                        params.push(value);
+                        format!("?{}", params.len())
+                    }
+                    QueryValue::Ago(duration) => {
+                        params.push(rusqlite::types::Value::Text(format!(
+                            "-{} seconds",
+                            duration.as_secs_f64()
+                        )));
+                        format!("strftime('%Y-%m-%d %H:%M:%f', 'NOW', ?{})", params.len())
+                    }
+                };
+
+                filters.push(format!(
+                    "{} {} {}",
+                    filter.field.column(),
+                    filter.operator,
+                    value
+                ));
+            }
+
+            let columns: Vec<String> = select
+                .iter()
+                .map(|field| {
+                    if field.is_time() {
+                        times.sql(field.column())
+                    } else {
+                        field.column().to_string()
+                    }
+                })
+                .collect();
+
+            let mut q = conn.prepare(&format!(
+                "
+            select
+                {}
+            from entries
+            where {}
+            order by namespace, key
+            ",
+                columns.join(", "),
+                filters.join("\n            and ")
+            ))?;
+
+            let is_terminal = std::io::stdout().is_terminal();
+
+            let mut out = std::io::stdout().lock();
+
+            let mut rows = q.query(rusqlite::params_from_iter(params))?;
+
+            let mut count = 0;
+
+            while let Some(row) = rows.next()? {
+                for (i, field) in select.iter().enumerate() {
+                    if i > 0 {
+                        out.write_all(delimiter.as_bytes())?;
+                    }
 
-        db_location.push("blade.db");
+                    if field.is_time() {
+                        times.write(&mut out, row.get_ref(i)?)?;
+                    } else {
+                        write_sql_value(&mut out, row.get_ref(i)?, is_terminal)?;
+                    }
+                }
 
-        Self {
-            db_location,
-            sqlite_synchronous_mode: SqliteSynchronousMode::default(),
-            sqlite_busy_timeout_ms: 5_000,
+                out.write_all(b"\n")?;
+
+                count += 1;
+            }
+
+            debug!(rows = count, "queried entries");
         }
-    }
-}
+        Command::Sql {
+            query,
+            delimiter,
+            write,
+        } => {
+            let conn = if write {
+                conn
+            } else {
+                open_read_only_db_connection(&db_location, &config)?
+            };
 
-#[derive(Clone, Copy, Default, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-enum SqliteSynchronousMode {
-    Extra,
-    Full,
-    #[default]
-    Normal,
-    Off,
-}
+            let mut q = conn.prepare(&query)?;
 
-impl Display for SqliteSynchronousMode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
-            SqliteSynchronousMode::Extra => "extra",
-            SqliteSynchronousMode::Full => "full",
-            SqliteSynchronousMode::Normal => "normal",
-            SqliteSynchronousMode::Off => "off",
-        };
+            if q.column_count() == 0 {
+                q.raw_execute()?;
+                return Ok(());
+            }
 
-        write!(f, "{}", s)
-    }
-}
+            let column_count = q.column_count();
 
-fn get_or_create_config_file() -> anyhow::Result<Config> {
-    let mut config_path = {
-        let mut config_path = directories::UserDirs::new()
-            .ok_or(anyhow!("could not retrieve home directory"))?
-            .home_dir()
-            .to_path_buf();
-        config_path.push(".config");
-        config_path.push("blade");
-        config_path
-    };
+            let is_terminal = std::io::stdout().is_terminal();
 
-    std::fs::create_dir_all(&config_path)?;
+            let mut out = std::io::stdout().lock();
 
-    config_path.push("config.toml");
+            out.write_all(q.column_names().join(&delimiter).as_bytes())?;
+            out.write_all(b"\n")?;
 
-    let config: Config = match std::fs::read_to_string(&config_path) {
-        Ok(f) => toml::from_str(&f)?,
-        Err(_) => {
-            let mut f = std::fs::File::create_new(&config_path)?;
+            let mut rows = q.query([])?;
 
-            let config = Config::default();
+            let mut count = 0;
 
-            let s = toml::to_string(&config)?;
+            while let Some(row) = rows.next()? {
+                for i in 0..column_count {
+                    if i > 0 {
+                        out.write_all(delimiter.as_bytes())?;
+                    }
 
-            f.write_all(s.as_bytes())?;
+                    write_sql_value(&mut out, row.get_ref(i)?, is_terminal)?;
+                }
 
-            config
+                out.write_all(b"\n")?;
+
+                count += 1;
+            }
+
+            debug!(rows = count, "ran query");
         }
-    };
+        Command::Namespace {
+            command:
+                NamespaceCommand::Describe {
+                    namespace,
+                    description,
+                },
+        } => {
+            if namespace.trim().is_empty() {
+                return Err(BladeError::Usage("namespace cannot be empty".to_string()).into());
+            }
 
-    Ok(config)
-}
+            if let Some(description) = description {
+                with_busy_retry(&config, || {
+                    conn.execute(
+                        "
+                    insert into namespaces (namespace, description)
+                    values (?1, ?2)
+                    on conflict do update
+                    set description = excluded.description
+                    ",
+                        [&namespace, &description],
+                    )
+                })?;
+            } else {
+                let description: Option<String> = conn
+                    .query_one(
+                        "
+                    select
+                        description
+                    from namespaces
+                    where namespace = ?
+                    ",
+                        [&namespace],
+                        |row| row.get(0),
+                    )
+                    .optional()?
+                    .flatten();
 
-fn open_or_create_db(
-    db_location: &Path,
-    sqlite_synchronous_mode: SqliteSynchronousMode,
-    sqlite_busy_timeout_ms: i32,
-) -> anyhow::Result<rusqlite::Connection> {
-    match open_db_connection(db_location, sqlite_synchronous_mode, sqlite_busy_timeout_ms) {
-        Ok(c) => Ok(c),
-        Err(rusqlite::Error::SqliteFailure(
-            rusqlite::ffi::Error {
-                code: rusqlite::ErrorCode::CannotOpen,
-                ..
-            },
-            _,
-        )) => {
-            let db_dir = db_location.parent().unwrap();
-            std::fs::create_dir_all(db_dir)?;
-            let conn =
-                open_db_connection(db_location, sqlite_synchronous_mode, sqlite_busy_timeout_ms)?;
-            Ok(conn)
+                if let Some(description) = description {
+                    writeln!(std::io::stdout().lock(), "{}", description)?;
+                }
+            }
         }
-        Err(e) => Err(e)?,
-    }
-}
+        Command::Namespace {
+            command:
+                NamespaceCommand::Copy {
+                    source,
+                    destination,
+                    key_prefix,
+                    filter,
+                    overwrite,
+                },
+        } => {
+            if destination.trim().is_empty() {
+                return Err(BladeError::Usage("namespace cannot be empty".to_string()).into());
+            }
 
-fn open_db_connection(
-    path: &Path,
-    sqlite_synchronous_mode: SqliteSynchronousMode,
-    sqlite_busy_timeout_ms: i32,
-) -> rusqlite::Result<rusqlite::Connection> {
-    let conn = rusqlite::Connection::open(path)?;
-    conn.pragma_update(None, "journal_mode", "wal")?;
-    conn.pragma_update(None, "synchronous", sqlite_synchronous_mode.to_string())?;
-    conn.pragma_update(None, "busy_timeout", sqlite_busy_timeout_ms)?;
+            if source == destination && key_prefix.is_empty() {
+                return Err(BladeError::Usage(
+                    "copying a namespace into itself needs a --key-prefix".to_string(),
+                )
+                .into());
+            }
 
-    #[cfg(target_os = "macos")]
-    conn.pragma_update(None, "fullfsync", true)?;
+            let copied = copy_namespace(
+                &mut conn,
+                &config,
+                send_hooks,
+                &source,
+                &destination,
+                &key_prefix,
+                &filter,
+                overwrite,
+            )?;
 
-    Ok(conn)
-}
+            writeln!(std::io::stdout(), "copied {} keys", copied)?;
+        }
+        Command::Namespace {
+            command:
+                NamespaceCommand::SetDefault {
+                    namespace,
+                    settings,
+                },
+        } => {
+            if namespace.trim().is_empty() {
+                return Err(BladeError::Usage("namespace cannot be empty".to_string()).into());
+            }
 
-fn migrate_db(conn: Connection) -> anyhow::Result<Connection> {
-    conn.execute_batch(
-        "
-    create table if not exists entries (
-        namespace text not null,
-        key text not null,
-        value blob not null,
-        inserted_at datetime not null default(strftime('%Y-%m-%d %H:%M:%f', 'NOW')),
-        updated_at datetime not null default(strftime('%Y-%m-%d %H:%M:%f', 'NOW')),
-        primary key (namespace, key)
-    ) without rowid;
-
-    create trigger if not exists entries_updated_at
-    after update on entries for each row
-    begin
-        update entries
-        set updated_at = current_timestamp
-        where namespace = old.namespace
-        and key = old.key;
-    end;
-    ",
-    )?;
-    Ok(conn)
-}
+            if settings.is_empty() {
+                let default_ttl_seconds: Option<i64> = conn
+                    .query_one(
+                        "
+                    select
+                        default_ttl_seconds
+                    from namespaces
+                    where namespace = ?
+                    ",
+                        [&namespace],
+                        |row| row.get(0),
+                    )
+                    .optional()?
+                    .flatten();
 
-struct Key<'input> {
-    namespace: &'input str,
-    name: &'input str,
-}
+                if let Some(default_ttl_seconds) = default_ttl_seconds {
+                    writeln!(
+                        std::io::stdout().lock(),
+                        "ttl={}",
+                        format_duration(default_ttl_seconds)
+                    )?;
+                }
 
-fn split_maybe_qualified_key(maybe_qualified_key: &str) -> anyhow::Result<Key<'_>> {
-    if maybe_qualified_key.trim().is_empty() {
-        return Err(anyhow!("key cannot be empty"));
-    }
+                return Ok(());
+            }
 
-    let mut split = maybe_qualified_key.split("@");
+            let mut default_ttl_seconds = None;
 
-    match (split.next(), split.next()) {
-        (Some(name), None) => Ok(Key {
-            namespace: DEFAULT_NAMESPACE,
-            name,
-        }),
-        (Some(name), Some(namespace)) => {
-            if name.trim().is_empty() {
-                Err(anyhow!("key cannot be empty"))
-            } else if namespace.trim().is_empty() {
-                Err(anyhow!("namespace cannot be empty"))
-            } else {
-                Ok(Key { namespace, name })
+            for setting in &settings {
+                let Some((name, value)) = setting.split_once('=') else {
+                    return Err(BladeError::Usage(format!(
+                        "settings must be like name=value: {}",
+                        setting
+                    ))
+                    .into());
+                };
+
+                match name {
+                    "ttl" => {
+                        default_ttl_seconds = match value {
+                            "none" => Some(None),
+                            _ => Some(Some(
+                                parse_ttl(value)
+                                    .map_err(|e| BladeError::Usage(e.to_string()))?
+                                    .as_secs() as i64,
+                            )),
+                        }
+                    }
+                    _ => {
+                        return Err(BladeError::Usage(format!(
+                            "unsupported namespace setting: {}",
+                            name
+                        ))
+                        .into());
+                    }
+                }
             }
-        }
-        _ => unreachable!(),
-    }
-}
 
-fn main() -> anyhow::Result<()> {
-    let options = Options::parse();
+            if let Some(default_ttl_seconds) = default_ttl_seconds {
+                with_busy_retry(&config, || {
+                    conn.execute(
+                        "
+                    insert into namespaces (namespace, default_ttl_seconds)
+                    values (?1, ?2)
+                    on conflict do update
+                    set default_ttl_seconds = excluded.default_ttl_seconds
+                    ",
+                        params![namespace, default_ttl_seconds],
+                    )
+                })?;
+            }
+        }
+        Command::Attach {
+            db_location: Some(attached_location),
+            namespace: Some(namespace),
+            ..
+        } => {
+            // relative paths would depend on where blade is run from
+            let attached_location = std::path::absolute(&attached_location)?;
 
-    let config = get_or_create_config_file()?;
+            // keys and aliases already in the namespace would be hidden by attaching it
+            let current_location = namespace_db_location(
+                &conn,
+                &config,
+                &db_location,
+                std::slice::from_ref(&namespace),
+            )?;
 
-    let conn = open_or_create_db(
-        options.db_location.as_ref().unwrap_or(&config.db_location),
-        config.sqlite_synchronous_mode,
-        config.sqlite_busy_timeout_ms,
-    )?;
+            let in_use = match &current_location {
+                Some(location) if *location == attached_location || !location.exists() => false,
+                Some(location) => {
+                    namespace_in_use(&open_namespace_db(location, &config)?, &namespace)?
+                }
+                None => namespace_in_use(&conn, &namespace)?,
+            };
 
-    let conn = migrate_db(conn)?;
+            if in_use {
+                return Err(BladeError::Usage(format!(
+                    "{} already has keys or aliases in {}, which attaching it would hide. \
+                    To move them, run `blade export --namespace {} > keys.jsonl` and \
+                    `blade prune --namespace {} --older-than 0s`, \
+                    then attach it and run `blade import < keys.jsonl`",
+                    namespace,
+                    current_location
+                        .as_deref()
+                        .unwrap_or(&db_location)
+                        .display(),
+                    namespace,
+                    namespace
+                ))
+                .into());
+            }
 
-    match options.command {
-        Command::Get { namespaced_key } => {
-            let key = split_maybe_qualified_key(&namespaced_key)?;
+            // create and migrate it now, so a bad path fails here rather than on first use
+            migrate_db(open_or_create_db(&attached_location, &config)?)?;
 
+            with_busy_retry(&config, || {
+                conn.execute(
+                    "
+                insert into attachments (namespace, db_location)
+                values (?, ?)
+                on conflict do update
+                set db_location = excluded.db_location
+                ",
+                    [&namespace, &attached_location.to_string_lossy().to_string()],
+                )
+            })?;
+        }
+        Command::Attach { .. } => {
             let mut q = conn.prepare(
                 "
             select
-                value
-            from entries
-            where namespace = ?
-            and key = ?
-            limit 1
+                namespace,
+                db_location
+            from attachments
+            order by namespace
             ",
             )?;
 
-            let value: Option<Vec<u8>> = q
-                .query_one([key.namespace, key.name], |row| row.get(0))
+            let mut rows = q.query([])?;
+
+            let mut out = std::io::stdout().lock();
+
+            while let Some(row) = rows.next()? {
+                let namespace: String = row.get(0)?;
+                let db_location: String = row.get(1)?;
+                writeln!(out, "{}\t{}", namespace, db_location)?;
+            }
+        }
+        Command::Detach { namespace } => {
+            let detached = with_busy_retry(&config, || {
+                conn.execute(
+                    "
+                delete from attachments
+                where namespace = ?
+                ",
+                    [&namespace],
+                )
+            })?;
+
+            if detached == 0 {
+                return Err(BladeError::NotFound(format!(
+                    "namespace is not attached: {}",
+                    namespace
+                ))
+                .into());
+            }
+        }
+        Command::Note {
+            command:
+                NoteCommand::Set {
+                    namespaced_key,
+                    note,
+                },
+        } => {
+            set_note(&conn, &config, send_hooks, &namespaced_key, Some(&note))?;
+        }
+        Command::Note {
+            command: NoteCommand::Delete { namespaced_key },
+        } => {
+            set_note(&conn, &config, send_hooks, &namespaced_key, None)?;
+        }
+        Command::Note {
+            command: NoteCommand::Get { namespaced_key },
+        } => {
+            let key = split_maybe_qualified_key(&namespaced_key)?;
+            let (namespace, name) = resolve_alias(&conn, &key)?;
+
+            let note: Option<Option<String>> = conn
+                .query_one(
+                    "
+                select
+                    note
+                from entries
+                where namespace = ?
+                and key = ?
+                and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+                ",
+                    [&namespace, &name],
+                    |row| row.get(0),
+                )
                 .optional()?;
 
-            if let Some(value) = value {
-                if std::io::stdin().is_terminal() && std::str::from_utf8(&value).is_err() {
-                    let mut out = std::io::stdout();
-                    out.write_all(format!("binary data ({} bytes)\n", value.len()).as_bytes())?;
-                } else {
-                    let mut out = std::io::stdout();
-                    out.write_all(&value)?;
-                    out.write_all(b"\n")?;
+            match note {
+                Some(Some(note)) => writeln!(std::io::stdout(), "{}", note)?,
+                Some(None) => {
+                    return Err(
+                        BladeError::NotFound(format!("no note on {}", namespaced_key)).into(),
+                    );
                 }
-            };
+                None => {
+                    return Err(
+                        BladeError::NotFound(format!("key not found: {}", namespaced_key)).into(),
+                    );
+                }
+            }
         }
-        Command::Set {
-            namespaced_key,
-            value,
+        Command::Alias {
+            command:
+                AliasCommand::Create {
+                    namespaced_alias,
+                    namespaced_target,
+                },
         } => {
-            let key = split_maybe_qualified_key(&namespaced_key)?;
+            let alias = split_maybe_qualified_key(&namespaced_alias)?;
+            let target = split_maybe_qualified_key(&namespaced_target)?;
 
-            const SET_QUERY: &str = "
-                    insert into entries (namespace, key, value)
-                    values (?, ?, ?)
-                    on conflict do update
-                    set value = excluded.value
-                    where namespace = excluded.namespace
-                    and key = excluded.key;
-                    ";
+            if get_value(&conn, &alias)?.is_some() {
+                return Err(BladeError::Usage(format!(
+                    "a key already exists at {}",
+                    namespaced_alias
+                ))
+                .into());
+            }
+
+            if alias_chain(&conn, &target)?
+                .contains(&(alias.namespace.to_string(), alias.name.to_string()))
+            {
+                return Err(BladeError::Usage(format!(
+                    "{} already points to {}, this would create a cycle",
+                    namespaced_target, namespaced_alias
+                ))
+                .into());
+            }
 
-            if let Some(value) = value {
+            with_busy_retry(&config, || {
                 conn.execute(
-                    SET_QUERY,
-                    params![key.namespace, key.name, value.as_bytes()],
-                )?;
-            } else {
-                let mut value = vec![];
+                    "
+                insert into aliases (namespace, alias, target_namespace, target_key)
+                values (?, ?, ?, ?)
+                on conflict do update
+                set target_namespace = excluded.target_namespace,
+                target_key = excluded.target_key
+                ",
+                    [alias.namespace, alias.name, target.namespace, target.name],
+                )
+            })?;
+        }
+        Command::Alias {
+            command: AliasCommand::Delete { namespaced_alias },
+        } => {
+            let alias = split_maybe_qualified_key(&namespaced_alias)?;
 
-                std::io::stdin().read_to_end(&mut value)?;
+            let deleted = with_busy_retry(&config, || {
+                conn.execute(
+                    "
+                delete from aliases
+                where namespace = ?
+                and alias = ?
+                ",
+                    [alias.namespace, alias.name],
+                )
+            })?;
 
-                conn.execute(SET_QUERY, params![key.namespace, key.name, value])?;
+            if deleted == 0 {
+                return Err(
+                    BladeError::NotFound(format!("alias not found: {}", namespaced_alias)).into(),
+                );
             }
         }
-        Command::Delete { namespaced_key } => {
-            let key = split_maybe_qualified_key(&namespaced_key)?;
+        Command::Alias {
+            command: AliasCommand::List { namespace },
+        } => {
+            let namespace = namespace.unwrap_or_else(|| default_namespace().to_string());
 
-            conn.execute(
+            let mut q = conn.prepare(
                 "
-                delete from entries
-                where namespace = ?
-                and key = ?
+            select
+                alias,
+                target_key,
+                target_namespace
+            from aliases
+            where namespace = ?
+            order by alias
             ",
-                [key.namespace, key.name],
             )?;
+
+            let rows = q.query_map([namespace], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+
+            let mut out = std::io::stdout().lock();
+
+            for row in rows {
+                let (alias, target_key, target_namespace): (String, String, String) = row?;
+                writeln!(out, "{}\t{}@{}", alias, target_key, target_namespace)?;
+            }
         }
-        Command::List {
-            namespace,
-            delimiter,
+        Command::Index {
+            command: IndexCommand::Create { namespace, path },
+        } => {
+            if let Err(e) = conn.query_one("select json_extract('{}', ?)", [&path], |_| Ok(())) {
+                return Err(BladeError::Usage(format!("invalid JSON path {}: {}", path, e)).into());
+            }
+
+            with_busy_retry(&config, || {
+                let tx = conn.transaction()?;
+
+                let id: i64 = tx.query_one(
+                    "
+                insert into json_indexes (namespace, path)
+                values (?, ?)
+                on conflict do nothing
+                returning id
+                ",
+                    [&namespace, &path],
+                    |row| row.get(0),
+                )?;
+
+                tx.execute_batch(&format!(
+                    "create index json_index_{} on entries ({}) where namespace = {}",
+                    id,
+                    json_path_expression(&path),
+                    sql_literal(&namespace)
+                ))?;
+
+                tx.commit()
+            })
+            .optional()?
+            .ok_or_else(|| {
+                BladeError::Conflict(format!("{} is already indexed in {}", path, namespace))
+            })?;
+        }
+        Command::Index {
+            command: IndexCommand::Drop { namespace, path },
         } => {
-            let namespace = namespace.unwrap_or_else(|| DEFAULT_NAMESPACE.to_string());
+            with_busy_retry(&config, || {
+                let tx = conn.transaction()?;
+
+                let id: i64 = tx.query_one(
+                    "
+                delete from json_indexes
+                where namespace = ?
+                and path = ?
+                returning id
+                ",
+                    [&namespace, &path],
+                    |row| row.get(0),
+                )?;
 
+                tx.execute_batch(&format!("drop index if exists json_index_{}", id))?;
+
+                tx.commit()
+            })
+            .optional()?
+            .ok_or_else(|| {
+                BladeError::NotFound(format!("{} is not indexed in {}", path, namespace))
+            })?;
+        }
+        Command::Index {
+            command: IndexCommand::List,
+        } => {
             let mut q = conn.prepare(
                 "
             select
-                key,
-                value
-            from entries
-            where namespace = ?
-            order by inserted_at desc
+                namespace,
+                path
+            from json_indexes
+            order by namespace, path
             ",
             )?;
 
-            let rows = q.query_map([namespace], |row| Ok((row.get(0)?, row.get(1)?)))?;
-
-            let is_terminal = std::io::stdin().is_terminal();
+            let rows = q.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
 
             let mut out = std::io::stdout().lock();
 
             for row in rows {
-                let (key, value): (String, Vec<u8>) = row?;
+                let (namespace, path): (String, String) = row?;
+                writeln!(out, "{}\t{}", namespace, path)?;
+            }
+        }
+        Command::Schema {
+            command:
+                SchemaCommand::Set {
+                    namespace,
+                    schema_file,
+                    prefix,
+                },
+        } => {
+            let schema = std::fs::read_to_string(&schema_file)
+                .with_context(|| format!("could not read {}", schema_file.display()))?;
 
-                if is_terminal && std::str::from_utf8(&value).is_err() {
-                    out.write_all(key.as_bytes())?;
-                    out.write_all(delimiter.as_bytes())?;
-                    out.write_all(format!("binary data ({} bytes)\n", value.len()).as_bytes())?;
-                } else {
-                    out.write_all(key.as_bytes())?;
-                    out.write_all(delimiter.as_bytes())?;
-                    out.write_all(&value)?;
-                    out.write_all(b"\n")?;
-                }
+            let parsed: serde_json::Value = serde_json::from_str(&schema).map_err(|e| {
+                BladeError::Usage(format!("{} is not JSON: {}", schema_file.display(), e))
+            })?;
+
+            jsonschema::validator_for(&parsed).map_err(|e| {
+                BladeError::Usage(format!(
+                    "{} is not a valid schema: {}",
+                    schema_file.display(),
+                    e
+                ))
+            })?;
+
+            with_busy_retry(&config, || {
+                conn.execute(
+                    "
+                insert into schemas (namespace, prefix, schema)
+                values (?, ?, ?)
+                on conflict do update
+                set schema = excluded.schema
+                ",
+                    [&namespace, &prefix, &schema],
+                )
+            })?;
+        }
+        Command::Schema {
+            command: SchemaCommand::Delete { namespace, prefix },
+        } => {
+            let deleted = with_busy_retry(&config, || {
+                conn.execute(
+                    "
+                delete from schemas
+                where namespace = ?
+                and prefix = ?
+                ",
+                    [&namespace, &prefix],
+                )
+            })?;
+
+            if deleted == 0 {
+                return Err(BladeError::NotFound(format!("no schema for {}", namespace)).into());
             }
         }
-        Command::ListNamespaces => {
+        Command::Schema {
+            command: SchemaCommand::Get { namespace, prefix },
+        } => {
+            let schema: String = conn
+                .query_one(
+                    "
+                select schema
+                from schemas
+                where namespace = ?
+                and prefix = ?
+                ",
+                    [&namespace, &prefix],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .ok_or_else(|| BladeError::NotFound(format!("no schema for {}", namespace)))?;
+
+            let mut out = std::io::stdout().lock();
+            out.write_all(schema.as_bytes())?;
+        }
+        Command::Schema {
+            command: SchemaCommand::List,
+        } => {
             let mut q = conn.prepare(
                 "
             select
-                distinct namespace
-            from entries
-            order by namespace asc
+                namespace,
+                prefix
+            from schemas
+            order by namespace, prefix
             ",
             )?;
 
-            let rows = q.query_map([], |row| row.get(0))?;
+            let rows = q.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
 
             let mut out = std::io::stdout().lock();
 
             for row in rows {
-                let row: String = row?;
-                writeln!(out, "{}", row)?;
+                let (namespace, prefix): (String, String) = row?;
+                writeln!(out, "{}\t{}", namespace, prefix)?;
             }
         }
         Command::DumpConfig => {
-            let s = toml::to_string_pretty(&config)?;
+            let s = toml::to_string_pretty(&config.effective(&conn)?)?;
             let mut out = std::io::stdout();
             writeln!(out, "{}", s)?;
         }
+        Command::Init { .. } | Command::Doctor => {
+            unreachable!("handled before loading the config")
+        }
     }
 
     Ok(())
@@ -0,0 +1,798 @@
+//! `blade mount`: namespaces as directories, and keys as files in them
+
+use crate::{
+    AccessLog, BladeError, ChangeOp, Config, ErrorKind, Key, SetOptions, delete_keys, read_value,
+    send_webhooks, set_key, with_busy_retry,
+};
+use blade::Backend;
+use fuser::{
+    Errno, FileAttr, FileHandle, FileType, Filesystem, FopenFlags, Generation, INodeNo, LockOwner,
+    MountOption, OpenFlags, RenameFlags, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory,
+    ReplyEmpty, ReplyEntry, ReplyOpen, ReplyWrite, Request, TimeOrNow, WriteFlags,
+};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// How long the kernel can cache names and attributes.
+/// Other blade commands can change keys while they're mounted, so not long
+const TTL: Duration = Duration::from_secs(1);
+
+/// Mounts the database at `mountpoint` until it's unmounted
+pub(crate) fn mount(
+    conn: Connection,
+    config: Config,
+    send_hooks: bool,
+    mountpoint: &Path,
+) -> anyhow::Result<()> {
+    let metadata = std::fs::metadata(mountpoint)?;
+
+    let fs = BladeFs {
+        conn: Mutex::new(conn),
+        config,
+        send_hooks,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        inodes: Mutex::new(Inodes::default()),
+        files: Mutex::new(HashMap::new()),
+        next_fh: AtomicU64::new(1),
+        new_namespaces: Mutex::new(HashSet::new()),
+    };
+
+    let mut options = fuser::Config::default();
+    options.mount_options.extend([
+        MountOption::FSName("blade".to_string()),
+        MountOption::DefaultPermissions,
+    ]);
+
+    fuser::mount(fs, mountpoint, &options)?;
+
+    Ok(())
+}
+
+/// Keys can have `/` in them, which file names can't
+fn file_name(name: &str) -> String {
+    name.replace('%', "%25").replace('/', "%2F")
+}
+
+fn key_name(file_name: &OsStr) -> Option<String> {
+    Some(file_name.to_str()?.replace("%2F", "/").replace("%25", "%"))
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Node {
+    Root,
+    Namespace(String),
+    Key { namespace: String, name: String },
+}
+
+/// Inode numbers for the nodes the kernel has seen, so they stay the same while mounted
+struct Inodes {
+    numbers: HashMap<Node, u64>,
+    nodes: Vec<Node>,
+}
+
+impl Default for Inodes {
+    fn default() -> Self {
+        Inodes {
+            numbers: HashMap::from([(Node::Root, INodeNo::ROOT.0)]),
+            nodes: vec![Node::Root],
+        }
+    }
+}
+
+impl Inodes {
+    fn number(&mut self, node: Node) -> INodeNo {
+        let next = self.nodes.len() as u64 + 1;
+
+        let number = *self.numbers.entry(node.clone()).or_insert_with(|| {
+            self.nodes.push(node);
+            next
+        });
+
+        INodeNo(number)
+    }
+
+    fn node(&self, number: INodeNo) -> Option<Node> {
+        self.nodes.get((number.0 as usize).checked_sub(1)?).cloned()
+    }
+}
+
+/// A key's value while it's open. Writes change the buffer,
+/// which is set when the file is flushed or closed
+struct OpenFile {
+    namespace: String,
+    name: String,
+    value: Vec<u8>,
+    dirty: bool,
+}
+
+struct BladeFs {
+    conn: Mutex<Connection>,
+    config: Config,
+    send_hooks: bool,
+    uid: u32,
+    gid: u32,
+    inodes: Mutex<Inodes>,
+    files: Mutex<HashMap<u64, OpenFile>>,
+    next_fh: AtomicU64,
+    /// Namespaces made with `mkdir` that don't have any keys yet
+    new_namespaces: Mutex<HashSet<String>>,
+}
+
+/// Tools look up files that don't exist all the time, so only other errors are logged
+fn errno(e: &anyhow::Error) -> Errno {
+    let kind = ErrorKind::of(e);
+
+    if !matches!(kind, ErrorKind::NotFound) {
+        warn!("{:#}", e);
+    }
+
+    match kind {
+        ErrorKind::NotFound => Errno::ENOENT,
+        ErrorKind::Usage | ErrorKind::Rejected => Errno::EINVAL,
+        ErrorKind::OverQuota => Errno::ENOSPC,
+        ErrorKind::Locked | ErrorKind::TimedOut => Errno::EBUSY,
+        _ => Errno::EIO,
+    }
+}
+
+impl BladeFs {
+    fn node(&self, number: INodeNo) -> Result<Node, Errno> {
+        self.inodes
+            .lock()
+            .unwrap()
+            .node(number)
+            .ok_or(Errno::ENOENT)
+    }
+
+    fn namespaces(&self, conn: &Connection) -> anyhow::Result<BTreeSet<String>> {
+        let mut namespaces: BTreeSet<String> = conn
+            .prepare(
+                "
+            select distinct
+                namespace
+            from entries
+            where expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW')
+            ",
+            )?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        namespaces.extend(self.new_namespaces.lock().unwrap().iter().cloned());
+
+        Ok(namespaces)
+    }
+
+    fn dir_attr(&self, number: INodeNo) -> FileAttr {
+        self.attr(number, FileType::Directory, 0, UNIX_EPOCH, UNIX_EPOCH)
+    }
+
+    fn attr(
+        &self,
+        number: INodeNo,
+        kind: FileType,
+        size: u64,
+        created: SystemTime,
+        updated: SystemTime,
+    ) -> FileAttr {
+        FileAttr {
+            ino: number,
+            size,
+            blocks: size.div_ceil(512),
+            atime: updated,
+            mtime: updated,
+            ctime: updated,
+            crtime: created,
+            kind,
+            perm: if kind == FileType::Directory {
+                0o755
+            } else {
+                0o644
+            },
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    /// The attributes of a key, or of an open file's unsaved value
+    fn key_attr(&self, number: INodeNo, namespace: &str, name: &str) -> anyhow::Result<FileAttr> {
+        let unsaved = self
+            .files
+            .lock()
+            .unwrap()
+            .values()
+            .find(|file| file.dirty && file.namespace == namespace && file.name == name)
+            .map(|file| file.value.len() as u64);
+
+        let conn = self.conn.lock().unwrap();
+
+        let times: Option<(i64, f64, f64)> = conn
+            .query_one(
+                "
+            select
+                length(value),
+                (julianday(inserted_at) - 2440587.5) * 86400,
+                (julianday(updated_at) - 2440587.5) * 86400
+            from entries
+            where namespace = ?
+            and key = ?
+            and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+            ",
+                [namespace, name],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+
+        let time = |seconds: f64| UNIX_EPOCH + Duration::from_secs_f64(seconds.max(0.0));
+
+        match (times, unsaved) {
+            (_, Some(size)) => {
+                let now = SystemTime::now();
+                let created = times.map_or(now, |(_, inserted_at, _)| time(inserted_at));
+                Ok(self.attr(number, FileType::RegularFile, size, created, now))
+            }
+            (Some((size, inserted_at, updated_at)), None) => Ok(self.attr(
+                number,
+                FileType::RegularFile,
+                size as u64,
+                time(inserted_at),
+                time(updated_at),
+            )),
+            (None, None) => Err(BladeError::NotFound(format!("key not found: {}", name)).into()),
+        }
+    }
+
+    fn node_attr(&self, number: INodeNo) -> Result<FileAttr, Errno> {
+        match self.node(number)? {
+            Node::Root | Node::Namespace(_) => Ok(self.dir_attr(number)),
+            Node::Key { namespace, name } => self
+                .key_attr(number, &namespace, &name)
+                .map_err(|e| errno(&e)),
+        }
+    }
+
+    fn set(&self, namespace: &str, name: &str, value: &[u8]) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+
+        set_key(
+            &mut conn,
+            &self.config,
+            self.send_hooks,
+            &format!("{}@{}", name, namespace),
+            &Key { namespace, name },
+            value,
+            &SetOptions::default(),
+        )?;
+
+        self.new_namespaces.lock().unwrap().remove(namespace);
+
+        Ok(())
+    }
+
+    fn save(&self, fh: FileHandle) -> Result<(), Errno> {
+        let mut files = self.files.lock().unwrap();
+        let file = files.get_mut(&fh.0).ok_or(Errno::EBADF)?;
+
+        if file.dirty {
+            self.set(&file.namespace, &file.name, &file.value)
+                .map_err(|e| errno(&e))?;
+            file.dirty = false;
+        }
+
+        Ok(())
+    }
+
+    fn open_file(
+        &self,
+        namespace: String,
+        name: String,
+        value: Vec<u8>,
+        dirty: bool,
+    ) -> FileHandle {
+        let fh = self.next_fh.fetch_add(1, Ordering::Relaxed);
+
+        self.files.lock().unwrap().insert(
+            fh,
+            OpenFile {
+                namespace,
+                name,
+                value,
+                dirty,
+            },
+        );
+
+        FileHandle(fh)
+    }
+
+    fn read_key(&self, namespace: &str, name: &str) -> anyhow::Result<Vec<u8>> {
+        let conn = self.conn.lock().unwrap();
+        let namespaced_key = format!("{}@{}", name, namespace);
+        let mut accessed = AccessLog::default();
+
+        let value = read_value(&conn, &self.config, &namespaced_key, &mut accessed)?
+            .ok_or_else(|| BladeError::NotFound(format!("key not found: {}", namespaced_key)))?;
+
+        accessed.flush(&conn, &self.config);
+
+        Ok(value)
+    }
+}
+
+impl Filesystem for BladeFs {
+    fn lookup(&self, _req: &Request, parent: INodeNo, name: &OsStr, reply: ReplyEntry) {
+        let result = (|| {
+            let child = match (self.node(parent)?, key_name(name)) {
+                (Node::Root, Some(namespace)) => {
+                    let conn = self.conn.lock().unwrap();
+                    let namespaces = self.namespaces(&conn).map_err(|e| errno(&e))?;
+
+                    if !namespaces.contains(&namespace) {
+                        return Err(Errno::ENOENT);
+                    }
+
+                    Node::Namespace(namespace)
+                }
+                (Node::Namespace(namespace), Some(name)) => Node::Key { namespace, name },
+                _ => return Err(Errno::ENOENT),
+            };
+
+            let number = self.inodes.lock().unwrap().number(child);
+            self.node_attr(number)
+        })();
+
+        match result {
+            Ok(attr) => reply.entry(&TTL, &attr, Generation(0)),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn getattr(&self, _req: &Request, ino: INodeNo, _fh: Option<FileHandle>, reply: ReplyAttr) {
+        match self.node_attr(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn setattr(
+        &self,
+        _req: &Request,
+        ino: INodeNo,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<TimeOrNow>,
+        _mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        fh: Option<FileHandle>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<fuser::BsdFileFlags>,
+        reply: ReplyAttr,
+    ) {
+        let result = (|| {
+            // only truncating means anything to a key
+            if let Some(size) = size
+                && let Node::Key { namespace, name } = self.node(ino)?
+            {
+                let open = fh.is_some_and(|fh| {
+                    self.files
+                        .lock()
+                        .unwrap()
+                        .get_mut(&fh.0)
+                        .is_some_and(|file| {
+                            file.value.resize(size as usize, 0);
+                            file.dirty = true;
+                            true
+                        })
+                });
+
+                if !open {
+                    let mut value = self.read_key(&namespace, &name).map_err(|e| errno(&e))?;
+                    value.resize(size as usize, 0);
+                    self.set(&namespace, &name, &value).map_err(|e| errno(&e))?;
+                }
+            }
+
+            self.node_attr(ino)
+        })();
+
+        match result {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn mkdir(
+        &self,
+        _req: &Request,
+        parent: INodeNo,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        reply: ReplyEntry,
+    ) {
+        let result = (|| {
+            let (Node::Root, Some(namespace)) = (self.node(parent)?, key_name(name)) else {
+                return Err(Errno::EPERM);
+            };
+
+            self.new_namespaces
+                .lock()
+                .unwrap()
+                .insert(namespace.clone());
+
+            let number = self
+                .inodes
+                .lock()
+                .unwrap()
+                .number(Node::Namespace(namespace));
+            Ok(self.dir_attr(number))
+        })();
+
+        match result {
+            Ok(attr) => reply.entry(&TTL, &attr, Generation(0)),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn rmdir(&self, _req: &Request, parent: INodeNo, name: &OsStr, reply: ReplyEmpty) {
+        let result = (|| {
+            let (Node::Root, Some(namespace)) = (self.node(parent)?, key_name(name)) else {
+                return Err(Errno::ENOENT);
+            };
+
+            let conn = self.conn.lock().unwrap();
+
+            if !conn
+                .list(&namespace)
+                .map_err(|e| errno(&e.into()))?
+                .is_empty()
+            {
+                return Err(Errno::ENOTEMPTY);
+            }
+
+            if !self.new_namespaces.lock().unwrap().remove(&namespace) {
+                return Err(Errno::ENOENT);
+            }
+
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn unlink(&self, _req: &Request, parent: INodeNo, name: &OsStr, reply: ReplyEmpty) {
+        let result = (|| {
+            let (Node::Namespace(namespace), Some(name)) = (self.node(parent)?, key_name(name))
+            else {
+                return Err(Errno::ENOENT);
+            };
+
+            let mut conn = self.conn.lock().unwrap();
+
+            let deleted = delete_keys(
+                &mut conn,
+                &self.config,
+                self.send_hooks,
+                &[Key {
+                    namespace: &namespace,
+                    name: &name,
+                }],
+                false,
+            )
+            .map_err(|e| errno(&e))?;
+
+            if deleted == 0 {
+                return Err(Errno::ENOENT);
+            }
+
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    /// Editors often save by writing a new file and renaming it over the old one
+    fn rename(
+        &self,
+        _req: &Request,
+        parent: INodeNo,
+        name: &OsStr,
+        newparent: INodeNo,
+        newname: &OsStr,
+        _flags: RenameFlags,
+        reply: ReplyEmpty,
+    ) {
+        let result = (|| {
+            let (Node::Namespace(namespace), Some(name)) = (self.node(parent)?, key_name(name))
+            else {
+                return Err(Errno::EPERM);
+            };
+
+            let (Node::Namespace(new_namespace), Some(new_name)) =
+                (self.node(newparent)?, key_name(newname))
+            else {
+                return Err(Errno::EPERM);
+            };
+
+            let mut conn = self.conn.lock().unwrap();
+
+            let renamed = with_busy_retry(&self.config, || {
+                let tx = conn.transaction()?;
+
+                tx.execute(
+                    "
+                delete from entries
+                where namespace = ?
+                and key = ?
+                ",
+                    [&new_namespace, &new_name],
+                )?;
+
+                let renamed = tx.execute(
+                    "
+                update entries
+                set namespace = ?3,
+                key = ?4
+                where namespace = ?1
+                and key = ?2
+                and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+                ",
+                    params![namespace, name, new_namespace, new_name],
+                )?;
+
+                tx.commit()?;
+
+                Ok(renamed)
+            })
+            .map_err(|e| errno(&e.into()))?;
+
+            if renamed == 0 {
+                return Err(Errno::ENOENT);
+            }
+
+            if self.send_hooks {
+                let renames = [
+                    (ChangeOp::Delete, &namespace, &name),
+                    (ChangeOp::Set, &new_namespace, &new_name),
+                ];
+
+                for (op, namespace, name) in renames {
+                    send_webhooks(&conn, &self.config, op, &Key { namespace, name })
+                        .map_err(|e| errno(&e))?;
+                }
+            }
+
+            self.new_namespaces.lock().unwrap().remove(&new_namespace);
+
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn open(&self, _req: &Request, ino: INodeNo, flags: OpenFlags, reply: ReplyOpen) {
+        let result = (|| {
+            let Node::Key { namespace, name } = self.node(ino)? else {
+                return Ok(FileHandle(0));
+            };
+
+            let truncate = flags.0 & rustix::fs::OFlags::TRUNC.bits() as i32 != 0;
+
+            let value = if truncate {
+                vec![]
+            } else {
+                self.read_key(&namespace, &name).map_err(|e| errno(&e))?
+            };
+
+            Ok(self.open_file(namespace, name, value, truncate))
+        })();
+
+        match result {
+            Ok(fh) => reply.opened(fh, FopenFlags::empty()),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn create(
+        &self,
+        _req: &Request,
+        parent: INodeNo,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        _flags: i32,
+        reply: ReplyCreate,
+    ) {
+        let result = (|| {
+            let (Node::Namespace(namespace), Some(name)) = (self.node(parent)?, key_name(name))
+            else {
+                return Err(Errno::EPERM);
+            };
+
+            let number = self.inodes.lock().unwrap().number(Node::Key {
+                namespace: namespace.clone(),
+                name: name.clone(),
+            });
+
+            // an empty file is an empty value, so it's set even if nothing is written
+            let fh = self.open_file(namespace, name, vec![], true);
+            let now = SystemTime::now();
+
+            Ok((self.attr(number, FileType::RegularFile, 0, now, now), fh))
+        })();
+
+        match result {
+            Ok((attr, fh)) => reply.created(&TTL, &attr, Generation(0), fh, FopenFlags::empty()),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn read(
+        &self,
+        _req: &Request,
+        _ino: INodeNo,
+        fh: FileHandle,
+        offset: u64,
+        size: u32,
+        _flags: OpenFlags,
+        _lock_owner: Option<LockOwner>,
+        reply: ReplyData,
+    ) {
+        let files = self.files.lock().unwrap();
+
+        match files.get(&fh.0) {
+            Some(file) => {
+                let start = (offset as usize).min(file.value.len());
+                let end = (start + size as usize).min(file.value.len());
+                reply.data(&file.value[start..end]);
+            }
+            None => reply.error(Errno::EBADF),
+        }
+    }
+
+    fn write(
+        &self,
+        _req: &Request,
+        _ino: INodeNo,
+        fh: FileHandle,
+        offset: u64,
+        data: &[u8],
+        _write_flags: WriteFlags,
+        _flags: OpenFlags,
+        _lock_owner: Option<LockOwner>,
+        reply: ReplyWrite,
+    ) {
+        let mut files = self.files.lock().unwrap();
+
+        match files.get_mut(&fh.0) {
+            Some(file) => {
+                let offset = offset as usize;
+                let end = offset + data.len();
+
+                if file.value.len() < end {
+                    file.value.resize(end, 0);
+                }
+
+                file.value[offset..end].copy_from_slice(data);
+                file.dirty = true;
+
+                reply.written(data.len() as u32);
+            }
+            None => reply.error(Errno::EBADF),
+        }
+    }
+
+    fn flush(
+        &self,
+        _req: &Request,
+        _ino: INodeNo,
+        fh: FileHandle,
+        _lock_owner: LockOwner,
+        reply: ReplyEmpty,
+    ) {
+        match self.save(fh) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn release(
+        &self,
+        _req: &Request,
+        _ino: INodeNo,
+        fh: FileHandle,
+        _flags: OpenFlags,
+        _lock_owner: Option<LockOwner>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        let saved = self.save(fh);
+        self.files.lock().unwrap().remove(&fh.0);
+
+        match saved {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn readdir(
+        &self,
+        _req: &Request,
+        ino: INodeNo,
+        _fh: FileHandle,
+        offset: u64,
+        mut reply: ReplyDirectory,
+    ) {
+        let result = (|| {
+            let node = self.node(ino)?;
+            let conn = self.conn.lock().unwrap();
+            let mut inodes = self.inodes.lock().unwrap();
+
+            let mut entries = vec![
+                (ino, FileType::Directory, ".".to_string()),
+                (INodeNo::ROOT, FileType::Directory, "..".to_string()),
+            ];
+
+            match node {
+                Node::Root => {
+                    for namespace in self.namespaces(&conn).map_err(|e| errno(&e))? {
+                        let number = inodes.number(Node::Namespace(namespace.clone()));
+                        entries.push((number, FileType::Directory, file_name(&namespace)));
+                    }
+                }
+                Node::Namespace(namespace) => {
+                    for name in conn.list(&namespace).map_err(|e| errno(&e.into()))? {
+                        let file_name = file_name(&name);
+                        let number = inodes.number(Node::Key {
+                            namespace: namespace.clone(),
+                            name,
+                        });
+                        entries.push((number, FileType::RegularFile, file_name));
+                    }
+                }
+                Node::Key { .. } => return Err(Errno::ENOTDIR),
+            }
+
+            Ok(entries)
+        })();
+
+        match result {
+            Ok(entries) => {
+                for (i, (number, kind, name)) in
+                    entries.into_iter().enumerate().skip(offset as usize)
+                {
+                    // the offset of an entry is where to carry on from after it
+                    if reply.add(number, i as u64 + 1, kind, name) {
+                        break;
+                    }
+                }
+
+                reply.ok();
+            }
+            Err(e) => reply.error(e),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! A [`Backend`] for a database hosted by Turso, or anything else that speaks libsql's protocol
+
+use crate::{Backend, MIGRATIONS, SET_QUERY};
+use libsql::{Value, params};
+use tracing::debug;
+
+/// libsql's client is async, so each call blocks on its own runtime
+pub struct LibsqlBackend {
+    runtime: tokio::runtime::Runtime,
+    conn: Option<libsql::Connection>,
+}
+
+impl LibsqlBackend {
+    /// Connects to a database at a URL like `libsql://my-db.turso.io`,
+    /// and migrates it like a local one
+    pub fn open(url: &str, auth_token: &str) -> anyhow::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let conn = runtime.block_on(async {
+            libsql::Builder::new_remote(url.to_string(), auth_token.to_string())
+                .build()
+                .await?
+                .connect()
+        })?;
+
+        let backend = LibsqlBackend {
+            runtime,
+            conn: Some(conn),
+        };
+        backend.migrate()?;
+
+        Ok(backend)
+    }
+
+    fn conn(&self) -> &libsql::Connection {
+        self.conn
+            .as_ref()
+            .expect("the connection is only taken on drop")
+    }
+
+    fn migrate(&self) -> anyhow::Result<()> {
+        self.runtime.block_on(async {
+            let user_version = async |conn: &libsql::Connection| -> libsql::Result<usize> {
+                let mut rows = conn.query("pragma user_version", ()).await?;
+
+                Ok(match rows.next().await? {
+                    Some(row) => row.get::<i64>(0)? as usize,
+                    None => 0,
+                })
+            };
+
+            // don't take the write lock unless there is something to do
+            if user_version(self.conn()).await? >= MIGRATIONS.len() {
+                return Ok(());
+            }
+
+            let tx = self
+                .conn()
+                .transaction_with_behavior(libsql::TransactionBehavior::Immediate)
+                .await?;
+
+            for (i, migration) in MIGRATIONS.iter().enumerate().skip(user_version(&tx).await?) {
+                let version = i + 1;
+                debug!(version, "running migration");
+                tx.execute_batch(migration).await?;
+                tx.execute(&format!("pragma user_version = {}", version), ())
+                    .await?;
+            }
+
+            tx.commit().await?;
+
+            Ok(())
+        })
+    }
+
+    /// Whether setting `key` would have to check its value against a schema or a type,
+    /// which only a local database does
+    pub fn has_checks(&self, namespace: &str, key: &str) -> libsql::Result<bool> {
+        self.runtime.block_on(async {
+            let checks = self
+                .rows(
+                    "
+                select
+                    exists (
+                        select 1
+                        from schemas
+                        where namespace = ?1
+                        and substr(?2, 1, length(prefix)) = prefix
+                    )
+                    or exists (
+                        select 1
+                        from entries
+                        where namespace = ?1
+                        and key = ?2
+                        and value_type is not null
+                        and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+                    )
+                ",
+                    params![namespace, key],
+                    |row| row.get::<bool>(0),
+                )
+                .await?;
+
+            Ok(checks.into_iter().next().unwrap_or(false))
+        })
+    }
+
+    async fn rows<T>(
+        &self,
+        sql: &str,
+        params: impl libsql::params::IntoParams,
+        f: impl Fn(&libsql::Row) -> libsql::Result<T>,
+    ) -> libsql::Result<Vec<T>> {
+        let mut rows = self.conn().query(sql, params).await?;
+        let mut all = vec![];
+
+        while let Some(row) = rows.next().await? {
+            all.push(f(&row)?);
+        }
+
+        Ok(all)
+    }
+}
+
+impl Drop for LibsqlBackend {
+    // closing the stream spawns a request, which needs the runtime
+    fn drop(&mut self) {
+        let _runtime = self.runtime.enter();
+        drop(self.conn.take());
+    }
+}
+
+impl Backend for LibsqlBackend {
+    type Error = libsql::Error;
+
+    fn get(&self, namespace: &str, key: &str) -> libsql::Result<Option<Vec<u8>>> {
+        self.runtime.block_on(async {
+            let values = self
+                .rows(
+                    "
+                select
+                    value
+                from entries
+                where namespace = ?
+                and key = ?
+                and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+                limit 1
+                ",
+                    params![namespace, key],
+                    |row| row.get(0),
+                )
+                .await?;
+
+            Ok(values.into_iter().next())
+        })
+    }
+
+    fn set(&self, namespace: &str, key: &str, value: &[u8]) -> libsql::Result<()> {
+        self.runtime.block_on(async {
+            self.conn()
+                .execute(SET_QUERY, params![namespace, key, value, Value::Null])
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn delete(&self, namespace: &str, key: &str) -> libsql::Result<bool> {
+        self.runtime.block_on(async {
+            // an expired row is deleted too, but it didn't exist as far as `get` is concerned.
+            // SQLite before 3.41 can get `is null` wrong in `returning`, so it's a `coalesce`
+            let existed = self
+                .rows(
+                    "
+                delete from entries
+                where namespace = ?
+                and key = ?
+                returning coalesce(expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'), true)
+                ",
+                    params![namespace, key],
+                    |row| row.get::<bool>(0),
+                )
+                .await?;
+
+            Ok(existed.into_iter().next().unwrap_or(false))
+        })
+    }
+
+    fn list(&self, namespace: &str) -> libsql::Result<Vec<String>> {
+        self.runtime.block_on(self.rows(
+            "
+        select
+            key
+        from entries
+        where namespace = ?
+        and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        order by key
+        ",
+            params![namespace],
+            |row| row.get(0),
+        ))
+    }
+
+    fn scan(&self, namespace: &str, prefix: &str) -> libsql::Result<Vec<(String, Vec<u8>)>> {
+        self.runtime.block_on(self.rows(
+            "
+        select
+            key,
+            value
+        from entries
+        where namespace = ?
+        and key glob ?
+        and (expires_at is null or expires_at > strftime('%Y-%m-%d %H:%M:%f', 'NOW'))
+        order by key
+        ",
+            params![namespace, crate::prefix_glob(prefix)],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ))
+    }
+}
@@ -1,17 +1,35 @@
+import base64
+import csv
+import ctypes
+import fnmatch
+import http.server
+import hashlib
+import io
+import json
 import os
+import pty
 import random
+import socketserver
+import sqlite3
 import string
 import subprocess
+import sys
 import tempfile
+import threading
+import time
 import typing
 import unittest
+import urllib.parse
 from contextlib import contextmanager
 
 
-def run(db, args):
+def run(db, args, env=None, input=None):
     my_env = os.environ.copy()
     my_env["DB_LOCATION"] = db
-    return subprocess.run(args, capture_output=True, text=True, env=my_env)
+    my_env.update(env or {})
+    return subprocess.run(
+        args, capture_output=True, text=True, env=my_env, input=input
+    )
 
 
 def generate_random_string(length):
@@ -21,12 +39,12 @@ def generate_random_string(length):
     return "".join(random.choices(characters, k=length))
 
 
-def get(db, key):
-    return run(db, ["blade", "get", key])
+def get(db, key, *args):
+    return run(db, ["blade", "get", key, *args])
 
 
-def set(db, key, value):
-    return run(db, ["blade", "set", key, value])
+def set(db, key, value, *args):
+    return run(db, ["blade", "set", key, value, *args])
 
 
 def set_from_stdin_str(db, key, value: str):
@@ -59,6 +77,14 @@ def delete(db, key):
     return run(db, ["blade", "delete", key])
 
 
+def touch(db, key, *args):
+    return run(db, ["blade", "touch", key, *args])
+
+
+def prune(db, *args, env=None):
+    return run(db, ["blade", "prune", *args], env=env)
+
+
 def list(db):
     return run(db, ["blade", "list"])
 
@@ -67,8 +93,66 @@ def list_with_namespace(db, ns):
     return run(db, ["blade", "list", ns])
 
 
-def dump_config(db):
-    return run(db, ["blade", "dump-config"])
+def dupes(db, ns=None):
+    return run(db, ["blade", "dupes"] + ([ns] if ns else []))
+
+
+def diff(db, key1, key2):
+    return run(db, ["blade", "diff", key1, key2])
+
+
+def db_diff(db, other_db, *args):
+    return run(db, ["blade", "db-diff", other_db, *args])
+
+
+def merge(db, other_db, *args, input=None):
+    my_env = os.environ.copy()
+    my_env["DB_LOCATION"] = db
+    return subprocess.run(
+        ["blade", "merge", other_db, *args],
+        capture_output=True,
+        text=True,
+        env=my_env,
+        input=input,
+    )
+
+
+def backup(db, to):
+    return run(db, ["blade", "backup", "--to", to])
+
+
+def restore(db, frm):
+    return run(db, ["blade", "restore", "--from", frm])
+
+
+def export(db, *args, env=None):
+    my_env = os.environ.copy()
+    my_env["DB_LOCATION"] = db
+    my_env.update(env or {})
+    return subprocess.run(
+        ["blade", "export", *args], capture_output=True, text=True, env=my_env
+    )
+
+
+def import_(db, *args, input=None, env=None):
+    my_env = os.environ.copy()
+    my_env["DB_LOCATION"] = db
+    my_env.update(env or {})
+    return subprocess.run(
+        ["blade", "import", *args],
+        capture_output=True,
+        text=True,
+        env=my_env,
+        input=input,
+    )
+
+
+def sql(db, query, *args):
+    return run(db, ["blade", "sql", query, *args])
+
+
+def dump_config(db, env=None):
+    return run(db, ["blade", "dump-config"], env=env)
 
 
 @contextmanager
@@ -83,6 +167,140 @@ def random_kv(ns=None):
     yield k, v
 
 
+@contextmanager
+def test_home(config):
+    """A HOME directory with the given contents in ~/.config/blade/config.toml"""
+    with tempfile.TemporaryDirectory() as home:
+        os.makedirs(home + "/.config/blade")
+        with open(home + "/.config/blade/config.toml", "w") as f:
+            f.write(config)
+        yield {"HOME": home}
+
+
+@contextmanager
+def test_server(handler):
+    """An HTTP server on localhost, calling `handler(request)` for each request"""
+
+    class Handler(http.server.BaseHTTPRequestHandler):
+        def do_GET(self):
+            handler(self)
+
+        def do_POST(self):
+            handler(self)
+
+        def do_PUT(self):
+            handler(self)
+
+        def do_HEAD(self):
+            handler(self)
+
+        def log_message(self, *args):
+            pass
+
+    server = http.server.HTTPServer(("127.0.0.1", 0), Handler)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    try:
+        yield f"http://127.0.0.1:{server.server_port}"
+    finally:
+        server.shutdown()
+        server.server_close()
+
+
+@contextmanager
+def test_redis_server(data, ttls):
+    """A Redis server on localhost that only knows the commands `import --redis` uses.
+    `data` maps keys to values, where lists stand in for other types"""
+
+    def reply(command, args):
+        if command == b"SCAN":
+            pattern = args[args.index(b"MATCH") + 1].decode()
+            keys = [k for k in data if fnmatch.fnmatchcase(k, pattern)]
+            return [b"0", [k.encode() for k in keys]]
+        key = args[0].decode() if args else None
+        if command == b"TYPE":
+            return "string" if isinstance(data[key], str) else "list"
+        if command == b"GET":
+            return data[key].encode()
+        if command == b"PTTL":
+            return ttls.get(key, -1)
+        return "OK"
+
+    def encode(value):
+        if isinstance(value, str):
+            return b"+" + value.encode() + b"\r\n"
+        if isinstance(value, int):
+            return b":%d\r\n" % value
+        if isinstance(value, bytes):
+            return b"$%d\r\n%s\r\n" % (len(value), value)
+        return b"*%d\r\n" % len(value) + b"".join(encode(v) for v in value)
+
+    class Handler(socketserver.StreamRequestHandler):
+        def handle(self):
+            while line := self.rfile.readline():
+                args = []
+                for _ in range(int(line[1:])):
+                    length = int(self.rfile.readline()[1:])
+                    args.append(self.rfile.read(length + 2)[:-2])
+                self.wfile.write(encode(reply(args[0].upper(), args[1:])))
+
+    server = socketserver.ThreadingTCPServer(("127.0.0.1", 0), Handler)
+    server.daemon_threads = True
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    try:
+        yield f"redis://127.0.0.1:{server.server_address[1]}/0"
+    finally:
+        server.shutdown()
+        server.server_close()
+
+
+def run_in_terminal(db, args, decode=True):
+    """Runs blade with stdout attached to a pseudo-terminal, returning what it wrote"""
+    my_env = os.environ.copy()
+    my_env["DB_LOCATION"] = db
+    primary, secondary = pty.openpty()
+    try:
+        subprocess.run(args, stdout=secondary, env=my_env, check=True)
+        os.close(secondary)
+        output = b""
+        while True:
+            try:
+                chunk = os.read(primary, 4096)
+            except OSError:
+                break
+            if not chunk:
+                break
+            output += chunk
+        if not decode:
+            return output
+        return output.decode().replace("\r\n", "\n")
+    finally:
+        os.close(primary)
+
+
+def run_interactive(db, args, keys, env=None):
+    """Runs blade on a pseudo-terminal, typing `keys` once it has started"""
+    my_env = os.environ.copy()
+    my_env["DB_LOCATION"] = db
+    my_env.update(env or {})
+    primary, secondary = pty.openpty()
+    try:
+        process = subprocess.Popen(
+            args, stdin=secondary, stdout=secondary, stderr=secondary, env=my_env
+        )
+        os.close(secondary)
+        time.sleep(0.3)
+        os.write(primary, keys)
+        while True:
+            try:
+                if not os.read(primary, 4096):
+                    break
+            except OSError:
+                break
+        return process.wait()
+    finally:
+        os.close(primary)
+
+
 @contextmanager
 def test_db():
     with tempfile.TemporaryDirectory() as tmpdirname:
@@ -100,7 +318,7 @@ class TestBlade(unittest.TestCase):
             get_out = get(db, key)
 
             self.assertEqual(get_out.returncode, 0)
-            self.assertEqual(get_out.stdout, value + "\n")
+            self.assertEqual(get_out.stdout, value)
 
     def test_get_and_set_from_stdin(self):
         with test_db() as db, random_kv() as (key, value):
@@ -111,7 +329,15 @@ class TestBlade(unittest.TestCase):
             get_out = get(db, key)
 
             self.assertEqual(get_out.returncode, 0)
-            self.assertEqual(get_out.stdout, value + "\n")
+            self.assertEqual(get_out.stdout, value)
+
+    def test_set_tee(self):
+        with test_db() as db:
+            value = "line 1\nline 2\n" * 10000
+            tee = run(db, ["blade", "set", "artifact", "--tee"], input=value)
+            self.assertEqual(tee.returncode, 0)
+            self.assertEqual(tee.stdout, value)
+            self.assertEqual(get(db, "artifact").stdout, value)
 
     def test_get_and_set_from_stdin_fd(self):
         with (
@@ -130,7 +356,7 @@ class TestBlade(unittest.TestCase):
             get_out = get(db, key)
 
             self.assertEqual(get_out.returncode, 0)
-            self.assertEqual(get_out.stdout, file_contents + "\n")
+            self.assertEqual(get_out.stdout, file_contents)
 
     def test_get_and_set_with_namespaces(self):
         with test_db() as db:
@@ -147,7 +373,7 @@ class TestBlade(unittest.TestCase):
             get_out1 = get(db, key1)
 
             self.assertEqual(get_out1.returncode, 0)
-            self.assertEqual(get_out1.stdout, value1 + "\n")
+            self.assertEqual(get_out1.stdout, value1)
 
             set_out2 = set(db, key2, value2)
 
@@ -156,7 +382,7 @@ class TestBlade(unittest.TestCase):
             get_out2 = get(db, key2)
 
             self.assertEqual(get_out2.returncode, 0)
-            self.assertEqual(get_out2.stdout, value2 + "\n")
+            self.assertEqual(get_out2.stdout, value2)
 
             self.assertNotEqual(get_out1.stdout, get_out2.stdout)
 
@@ -167,13 +393,195 @@ class TestBlade(unittest.TestCase):
 
             get_out = get(db, key)
             self.assertEqual(get_out.returncode, 0)
-            self.assertEqual(get_out.stdout, value + "\n")
+            self.assertEqual(get_out.stdout, value)
 
             delete_out = delete(db, key)
             self.assertEqual(delete_out.returncode, 0)
             self.assertEqual(delete_out.returncode, 0)
             self.assertEqual(delete_out.stdout, "")
 
+    def test_delete_many(self):
+        with test_db() as db:
+            set(db, "a", "1")
+            set(db, "b@other", "2")
+            set(db, "c", "3")
+
+            delete_out = run(db, ["blade", "delete", "a", "b@other", "missing"])
+            self.assertEqual(delete_out.returncode, 0)
+            self.assertIn("deleted 2 of 3 keys", delete_out.stderr)
+            self.assertEqual(get(db, "a").returncode, 1)
+            self.assertEqual(get(db, "b@other").returncode, 1)
+            self.assertEqual(get(db, "c").stdout, "3")
+
+    def test_find_value(self):
+        with test_db() as db:
+            set(db, "token@work", "Bearer abc123")
+            set(db, "token@home", "Bearer xyz")
+            set(db, "other", "abc")
+            with open(db + ".bin", "wb") as f:
+                f.write(b"\xff\xfeabc123")
+            with open(db + ".bin", "rb") as f:
+                set_from_file_redirection(db, "binary", f)
+
+            find_out = run(db, ["blade", "find-value", "abc123"])
+            self.assertEqual(find_out.stdout, "token@work\n")
+
+            find_out = run(db, ["blade", "find-value", "abc123", "--binary"])
+            self.assertEqual(find_out.stdout, "binary@default\ntoken@work\n")
+
+            find_args = ["blade", "find-value", "--regex", "^Bearer [a-z]+$"]
+            self.assertEqual(run(db, find_args).stdout, "token@home\n")
+
+            find_args = ["blade", "find-value", "abc", "--namespace", "default"]
+            self.assertEqual(run(db, find_args).stdout, "other@default\n")
+
+            self.assertEqual(run(db, ["blade", "find-value", "nope"]).returncode, 1)
+
+    def test_which(self):
+        with test_db() as db:
+            set(db, "token@work", "secret")
+            set(db, "copy", "secret")
+            set(db, "other", "secret2")
+
+            sha = hashlib.sha256(b"secret").hexdigest()
+            which_out = run(db, ["blade", "which", "--hash", sha.upper()])
+            self.assertEqual(which_out.stdout, "copy@default\ntoken@work\n")
+
+            with open(db + ".value", "w") as f:
+                f.write("secret")
+            which_args = ["blade", "which", "--value-file", db + ".value"]
+            which_out = run(db, which_args + ["--namespace", "work"])
+            self.assertEqual(which_out.stdout, "token@work\n")
+
+            self.assertEqual(run(db, ["blade", "which", "--hash", "00"]).returncode, 1)
+            self.assertEqual(run(db, ["blade", "which"]).returncode, 2)
+
+    def test_attach(self):
+        with test_db() as db:
+            media_db = db + ".media"
+            attach_out = run(db, ["blade", "attach", media_db, "as", "media"])
+            self.assertEqual(attach_out.returncode, 0)
+            attach_out = run(db, ["blade", "attach"])
+            self.assertEqual(attach_out.stdout, f"media\t{media_db}\n")
+
+            set(db, "a@media", "1")
+            set(db, "b", "2")
+            self.assertEqual(get(db, "a@media").stdout, "1")
+            self.assertEqual(list_with_namespace(db, "media").stdout, "a\t1\n")
+
+            conn = sqlite3.connect(media_db)
+            keys = conn.execute("select namespace, key from entries").fetchall()
+            self.assertEqual(keys, [("media", "a")])
+            conn.close()
+
+            cat_out = run(db, ["blade", "cat", "a@media", "b"])
+            self.assertEqual(cat_out.returncode, 2)
+
+            # imports and aliases use the attached database
+            imported = json.dumps(
+                {
+                    "namespace": "media",
+                    "key": "c",
+                    "value": "3",
+                    "inserted_at": "2020-01-01 00:00:00.000",
+                    "updated_at": "2020-01-01 00:00:00.000",
+                }
+            )
+            self.assertEqual(import_(db, input=imported).returncode, 0)
+            self.assertEqual(get(db, "c@media").stdout, "3")
+
+            alias = ["blade", "alias", "create"]
+            self.assertEqual(run(db, [*alias, "p", "a@media"]).returncode, 2)
+            self.assertEqual(run(db, [*alias, "p@media", "a@media"]).returncode, 0)
+            self.assertEqual(get(db, "p@media").stdout, "1")
+
+            export_out = run(db, ["blade", "export", "--namespace", "media"])
+            self.assertEqual(export_out.returncode, 0)
+            self.assertEqual(run(db, ["blade", "export"]).returncode, 2)
+
+            # attaching a namespace that has keys would hide them
+            set(db, "d@photos", "4")
+            photos_db = db + ".photos"
+            attach_out = run(db, ["blade", "attach", photos_db, "as", "photos"])
+            self.assertEqual(attach_out.returncode, 2)
+            self.assertIn("already has keys", attach_out.stderr)
+            self.assertEqual(get(db, "d@photos").stdout, "4")
+            attach_out = run(db, ["blade", "attach", media_db, "as", "media"])
+            self.assertEqual(attach_out.returncode, 0)
+
+            self.assertEqual(run(db, ["blade", "detach", "media"]).returncode, 0)
+            self.assertEqual(get(db, "a@media").returncode, 1)
+            self.assertEqual(run(db, ["blade", "detach", "media"]).returncode, 1)
+
+    def test_shard_by_namespace(self):
+        with test_db() as db, test_home("shard_by_namespace = true") as env:
+            def blade(*args):
+                return run(db, ["blade", *args], env=env)
+
+            self.assertEqual(blade("set", "a@work", "1").returncode, 0)
+            self.assertEqual(blade("set", "b", "2").returncode, 0)
+            self.assertEqual(blade("get", "a@work").stdout, "1")
+
+            self.assertTrue(os.path.exists(db + ".shards/work.db"))
+            self.assertTrue(os.path.exists(db + ".shards/default.db"))
+
+            conn = sqlite3.connect(db)
+            (count,) = conn.execute("select count(*) from entries").fetchone()
+            self.assertEqual(count, 0)
+            conn.close()
+
+            self.assertEqual(blade("cat", "a@work", "b").returncode, 2)
+
+            def entry(key, namespace, value):
+                return json.dumps(
+                    {
+                        "namespace": namespace,
+                        "key": key,
+                        "value": value,
+                        "inserted_at": "2020-01-01 00:00:00.000",
+                        "updated_at": "2020-01-01 00:00:00.000",
+                    }
+                )
+
+            entries = entry("c", "work", "3") + "\n" + entry("c", "home", "4")
+            import_out = import_(db, input=entries, env=env)
+            self.assertEqual(import_out.stdout, "created 2, updated 0, skipped 0\n")
+            self.assertEqual(blade("get", "c@work").stdout, "3")
+            self.assertEqual(blade("get", "c@home").stdout, "4")
+            self.assertTrue(os.path.exists(db + ".shards/home.db"))
+
+            # home's database is written first, and rolled back with work's
+            conflicting = entry("d", "home", "5") + "\n" + entry("c", "work", "5")
+            fail = ["--on-conflict", "fail"]
+            import_out = import_(db, *fail, input=conflicting, env=env)
+            self.assertEqual(import_out.returncode, 6)
+            self.assertEqual(blade("get", "d@home").returncode, 1)
+
+            get_stdin = ["blade", "get", "--stdin"]
+            get_out = run(db, get_stdin, env=env, input="a@work\nc@work")
+            self.assertEqual(get_out.stdout, "1\n3\n")
+            get_out = run(db, get_stdin, env=env, input="a@work\nc@home")
+            self.assertEqual(get_out.returncode, 2)
+
+            export_out = blade("export", "--namespace", "work")
+            exported = [json.loads(line) for line in export_out.stdout.splitlines()]
+            self.assertEqual([entry["key"] for entry in exported], ["a", "c"])
+            self.assertEqual(blade("export").returncode, 2)
+            self.assertEqual(blade("list-namespaces").returncode, 2)
+            self.assertEqual(blade("list", "work.*").returncode, 2)
+
+            blade("expire", "a@work", "0s")
+            blade("expire", "c@home", "0s")
+            gc_out = blade("gc")
+            self.assertEqual(gc_out.stdout, "deleted 2 expired entries\n")
+
+        with test_db() as db:
+            set(db, "a", "1")
+            with test_home("shard_by_namespace = true") as env:
+                get_out = run(db, ["blade", "get", "a"], env=env)
+            self.assertEqual(get_out.returncode, 2)
+            self.assertIn("before shard_by_namespace was turned on", get_out.stderr)
+
     def test_list(self):
         self.maxDiff = None
 
@@ -255,6 +663,1244 @@ class TestBlade(unittest.TestCase):
                 + "\n",
             )
 
+    def test_dupes(self):
+        with test_db() as db:
+            self.assertEqual(set(db, "b", "same").returncode, 0)
+            self.assertEqual(set(db, "a", "same").returncode, 0)
+            self.assertEqual(set(db, "c", "different").returncode, 0)
+            self.assertEqual(set(db, "d", "other").returncode, 0)
+            self.assertEqual(set(db, "e", "other").returncode, 0)
+            self.assertEqual(set(db, "f@ns1", "same").returncode, 0)
+
+            dupes_out = dupes(db)
+            self.assertEqual(dupes_out.returncode, 0)
+            self.assertEqual(dupes_out.stdout, "d\te\na\tb\n")
+
+            dupes_out2 = dupes(db, "ns1")
+            self.assertEqual(dupes_out2.returncode, 0)
+            self.assertEqual(dupes_out2.stdout, "")
+
+    def test_diff(self):
+        with test_db() as db:
+            self.assertEqual(set(db, "a", "one\ntwo\nthree\n").returncode, 0)
+            self.assertEqual(set(db, "b@ns1", "one\n2\nthree\n").returncode, 0)
+            self.assertEqual(set(db, "c", "one\ntwo\nthree\n").returncode, 0)
+
+            diff_out = diff(db, "a", "b@ns1")
+            self.assertEqual(diff_out.returncode, 0)
+            self.assertEqual(
+                diff_out.stdout,
+                "--- a\n+++ b@ns1\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n",
+            )
+
+            self.assertEqual(diff(db, "a", "c").stdout, "")
+            self.assertEqual(diff(db, "a", "missing").returncode, 1)
+
+    def test_db_diff(self):
+        with test_db() as db, test_db() as other_db:
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+            self.assertEqual(set(db, "b", "2").returncode, 0)
+            self.assertEqual(set(db, "c@ns1", "3").returncode, 0)
+
+            self.assertEqual(set(other_db, "b", "changed").returncode, 0)
+            self.assertEqual(set(other_db, "c@ns1", "3").returncode, 0)
+            self.assertEqual(set(other_db, "d@ns1", "4").returncode, 0)
+
+            db_diff_out = db_diff(db, other_db)
+            self.assertEqual(db_diff_out.returncode, 0)
+            self.assertEqual(
+                db_diff_out.stdout,
+                "removed\ta@default\nchanged\tb@default\nadded\td@ns1\n",
+            )
+
+            db_diff_out2 = db_diff(
+                db, other_db, "--namespace", "ns1", "--format", "json"
+            )
+            self.assertEqual(db_diff_out2.returncode, 0)
+            self.assertEqual(
+                json.loads(db_diff_out2.stdout),
+                [{"change": "added", "namespace": "ns1", "key": "d"}],
+            )
+
+            self.assertEqual(db_diff(db, other_db + ".missing").returncode, 2)
+
+    def test_merge(self):
+        with test_db() as db, test_db() as other_db:
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+            self.assertEqual(set(db, "b", "ours").returncode, 0)
+            self.assertEqual(set(other_db, "b", "theirs").returncode, 0)
+            self.assertEqual(set(other_db, "c@ns1", "3").returncode, 0)
+            run(other_db, ["blade", "note", "set", "b", "from the other machine"])
+
+            merge_out = merge(db, other_db, "--strategy", "ours")
+            self.assertEqual(merge_out.returncode, 0)
+            self.assertEqual(
+                merge_out.stdout,
+                "conflict\tb@default\tours\nadded 1, updated 0, conflicts 1\n",
+            )
+            self.assertEqual(get(db, "b").stdout, "ours")
+            self.assertEqual(get(db, "c@ns1").stdout, "3")
+
+            merge_out = merge(db, other_db, "--strategy", "interactive", input="t\n")
+            self.assertEqual(merge_out.returncode, 0)
+            self.assertEqual(
+                merge_out.stdout,
+                "conflict\tb@default\ttheirs\nadded 0, updated 1, conflicts 1\n",
+            )
+            self.assertEqual(get(db, "b").stdout, "theirs")
+
+            # taking theirs keeps their update time and metadata
+            def meta(db):
+                out = run(db, ["blade", "get", "b", "--meta"]).stdout
+                return dict(line.split("\t") for line in out.splitlines())
+
+            self.assertEqual(meta(db)["updated"], meta(other_db)["updated"])
+            self.assertEqual(meta(db)["note"], "from the other machine")
+
+    def test_merge_newest(self):
+        with test_db() as db, test_db() as other_db:
+            self.assertEqual(set(db, "a", "older").returncode, 0)
+            self.assertEqual(set(other_db, "a", "newer").returncode, 0)
+            self.assertEqual(set(other_db, "b", "older").returncode, 0)
+            self.assertEqual(set(db, "b", "newer").returncode, 0)
+
+            merge_out = merge(db, other_db)
+            self.assertEqual(merge_out.returncode, 0)
+            self.assertEqual(get(db, "a").stdout, "newer")
+            self.assertEqual(get(db, "b").stdout, "newer")
+
+    def test_merge_and_export_keep_expiry(self):
+        with test_db() as db, test_db() as other_db, test_db() as imported_db:
+
+            def ttl(db, key):
+                return run(db, ["blade", "ttl", key])
+
+            set(other_db, "session", "s", "--ttl", "1h")
+            set(other_db, "gone", "g", "--ttl", "1h")
+            run(other_db, ["blade", "expire", "gone", "0s"])
+            set(db, "taken", "ours")
+            set(other_db, "taken", "theirs", "--ttl", "1h")
+
+            merge_out = merge(db, other_db, "--strategy", "theirs")
+            self.assertEqual(
+                merge_out.stdout,
+                "conflict\ttaken@default\ttheirs\nadded 1, updated 1, conflicts 1\n",
+            )
+            self.assertNotEqual(ttl(db, "session").stdout, "none\n")
+            self.assertNotEqual(ttl(db, "taken").stdout, "none\n")
+            self.assertEqual(get(db, "gone").returncode, 1)
+
+            entries = [json.loads(line) for line in export(db).stdout.splitlines()]
+            self.assertIn("expires_at", entries[0])
+            entries.append(dict(entries[0], key="old", expires_at="2000-01-01"))
+            lines = "".join(json.dumps(entry) + "\n" for entry in entries)
+            import_out = run(imported_db, ["blade", "import"], input=lines)
+            self.assertEqual(import_out.stdout, "created 2, updated 0, skipped 1\n")
+            self.assertNotEqual(ttl(imported_db, "session").stdout, "none\n")
+            self.assertEqual(get(imported_db, "old").returncode, 1)
+
+    def test_backup_and_restore(self):
+        with test_db() as db, tempfile.TemporaryDirectory() as backup_dir:
+            backup_path = backup_dir + "/backup.db"
+
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+            self.assertEqual(set(db, "b@ns1", "2").returncode, 0)
+            self.assertEqual(set(db, "session", "s", "--ttl", "1h").returncode, 0)
+            run(db, ["blade", "note", "set", "a", "the first key"])
+            run(db, ["blade", "alias", "create", "first", "a"])
+
+            self.assertEqual(backup(db, "file://" + backup_path).returncode, 0)
+            self.assertEqual(backup(db, backup_path).returncode, 2)
+            self.assertEqual(backup(db, "ftp://host/backup.db").returncode, 2)
+
+            self.assertEqual(set(db, "a", "changed").returncode, 0)
+            self.assertEqual(set(db, "c", "3").returncode, 0)
+
+            self.assertEqual(restore(db, backup_path).returncode, 0)
+            self.assertEqual(get(db, "a").stdout, "1")
+            self.assertEqual(get(db, "b@ns1").stdout, "2")
+            self.assertEqual(get(db, "c").returncode, 1)
+            self.assertNotEqual(run(db, ["blade", "ttl", "session"]).stdout, "none\n")
+            note_out = run(db, ["blade", "note", "get", "a"])
+            self.assertEqual(note_out.stdout, "the first key\n")
+            self.assertEqual(get(db, "first").stdout, "1")
+
+            missing = restore(db, backup_dir + "/missing.db")
+            self.assertEqual(missing.returncode, 2)
+            self.assertFalse(os.path.exists(backup_dir + "/missing.db"))
+
+    def test_backup_and_restore_remote(self):
+        objects = {}
+        requests = []
+
+        def handler(request):
+            auth = request.headers.get("Authorization")
+            if_none_match = request.headers.get("If-None-Match")
+            requests.append((request.command, request.path, auth, if_none_match))
+            if request.command == "PUT" and request.path in objects:
+                request.send_response(412)
+                request.send_header("Content-Length", "0")
+                request.end_headers()
+            elif request.command == "PUT":
+                length = int(request.headers["Content-Length"])
+                objects[request.path] = request.rfile.read(length)
+                request.send_response(200)
+                request.end_headers()
+            elif request.path in objects:
+                body = objects[request.path]
+                request.send_response(200)
+                request.send_header("Content-Length", str(len(body)))
+                request.end_headers()
+                if request.command == "GET":
+                    request.wfile.write(body)
+            else:
+                request.send_response(404)
+                request.send_header("Content-Length", "0")
+                request.end_headers()
+
+        with test_db() as db, test_server(handler) as url:
+            set(db, "a", "1")
+
+            self.assertEqual(backup(db, url + "/blade.db").returncode, 0)
+            self.assertEqual(backup(db, url + "/blade.db").returncode, 2)
+            self.assertEqual([r[0] for r in requests], ["PUT", "PUT"])
+            self.assertEqual(requests[-1][3], "*")
+
+            env = {
+                "AWS_ACCESS_KEY_ID": "AKID",
+                "AWS_SECRET_ACCESS_KEY": "secret",
+                "AWS_REGION": "eu-west-1",
+                "AWS_ENDPOINT_URL": url,
+            }
+            s3_backup = ["blade", "backup", "--to", "s3://bucket/backups/blade.db"]
+            self.assertEqual(run(db, s3_backup, env=env).returncode, 0)
+            method, path, auth, if_none_match = requests[-1]
+            self.assertEqual((method, path), ("PUT", "/bucket/backups/blade.db"))
+            self.assertEqual(if_none_match, "*")
+            self.assertRegex(
+                auth,
+                r"^AWS4-HMAC-SHA256 Credential=AKID/\d{8}/eu-west-1/s3/aws4_request, "
+                r"SignedHeaders=host;if-none-match;x-amz-content-sha256;x-amz-date, "
+                r"Signature=[0-9a-f]{64}$",
+            )
+            self.assertEqual(run(db, s3_backup, env=env).returncode, 2)
+            self.assertEqual(requests[-1][0], "PUT")
+
+            odd_bucket = ["blade", "backup", "--to", "s3://my bucket/blade.db"]
+            self.assertEqual(run(db, odd_bucket, env=env).returncode, 0)
+            self.assertEqual(requests[-1][1], "/my%20bucket/blade.db")
+
+            set(db, "a", "changed")
+            restore_out = run(db, ["blade", "restore", "--from", url + "/blade.db"])
+            self.assertEqual(restore_out.returncode, 0)
+            self.assertEqual(get(db, "a").stdout, "1")
+
+            set(db, "a", "changed")
+            s3_restore = ["blade", "restore", "--from", "s3://bucket/backups/blade.db"]
+            self.assertEqual(run(db, s3_restore, env=env).returncode, 0)
+            self.assertEqual(get(db, "a").stdout, "1")
+
+            self.assertEqual(restore(db, url + "/missing.db").returncode, 2)
+            no_credentials = {"AWS_ACCESS_KEY_ID": "", "AWS_SECRET_ACCESS_KEY": ""}
+            s3_restore_out = run(db, s3_restore, env=no_credentials)
+            self.assertEqual(s3_restore_out.returncode, 2)
+
+    def test_export_and_import(self):
+        with test_db() as db, test_db() as other_db:
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+            self.assertEqual(set(db, "b@ns1", "2").returncode, 0)
+            with tempfile.TemporaryFile() as file:
+                file.write(b"\xff\x00\xfe")
+                file.seek(0)
+                set_from_file_redirection(db, "c", file)
+
+            export_out = export(db)
+            self.assertEqual(export_out.returncode, 0)
+            # progress is only shown when stderr is a terminal
+            self.assertEqual(export_out.stderr, "")
+
+            entries = [json.loads(line) for line in export_out.stdout.splitlines()]
+            self.assertEqual(
+                [(e["namespace"], e["key"]) for e in entries],
+                [("default", "a"), ("default", "c"), ("ns1", "b")],
+            )
+            self.assertEqual(entries[0]["value"], "1")
+            self.assertEqual(entries[1]["value_base64"], "/wD+")
+
+            self.assertEqual(
+                len(export(db, "--namespace", "ns1").stdout.splitlines()), 1
+            )
+
+            import_out = import_(other_db, input=export_out.stdout)
+            self.assertEqual(import_out.returncode, 0)
+            self.assertEqual(export(other_db).stdout, export_out.stdout)
+
+    def test_export_since(self):
+        with test_db() as db:
+            entries = [
+                {
+                    "namespace": "default",
+                    "key": "old",
+                    "value": "1",
+                    "inserted_at": "2020-01-01 00:00:00.000",
+                    "updated_at": "2020-06-01 00:00:00.000",
+                },
+                {
+                    "namespace": "default",
+                    "key": "new",
+                    "value": "2",
+                    "inserted_at": "2020-01-01 00:00:00.000",
+                    "updated_at": "2024-03-01 12:00:00",
+                },
+            ]
+            import_out = import_(
+                db, input="\n".join(json.dumps(entry) for entry in entries)
+            )
+            self.assertEqual(import_out.returncode, 0)
+
+            export_out = export(db, "--since", "2024-01-01")
+            self.assertEqual(export_out.returncode, 0)
+            self.assertEqual(
+                [json.loads(line)["key"] for line in export_out.stdout.splitlines()],
+                ["new"],
+            )
+
+            export_out = export(db, "--since", "2020-06-01")
+            self.assertEqual(len(export_out.stdout.splitlines()), 2)
+
+            self.assertEqual(export(db, "--since", "2025-01-01").stdout, "")
+            self.assertEqual(export(db, "--since", "yesterday").returncode, 2)
+
+    def test_touch(self):
+        with test_db() as db:
+            old = {
+                "namespace": "default",
+                "key": "a",
+                "value": "1",
+                "inserted_at": "2020-01-01 00:00:00.000",
+                "updated_at": "2020-01-01 00:00:00.000",
+            }
+            self.assertEqual(import_(db, input=json.dumps(old)).returncode, 0)
+
+            self.assertEqual(touch(db, "a").returncode, 0)
+            [entry] = [json.loads(line) for line in export(db).stdout.splitlines()]
+            self.assertEqual(entry["value"], "1")
+            self.assertEqual(entry["inserted_at"], old["inserted_at"])
+            self.assertGreater(entry["updated_at"], "2024")
+
+            self.assertEqual(touch(db, "a", "--created").returncode, 0)
+            [entry] = [json.loads(line) for line in export(db).stdout.splitlines()]
+            self.assertGreater(entry["inserted_at"], "2024")
+
+            self.assertEqual(touch(db, "missing").returncode, 1)
+
+    def test_recently_used_and_prune(self):
+        with test_db() as db, test_home("track_last_accessed = true") as env:
+            entries = [
+                {
+                    "namespace": "default",
+                    "key": key,
+                    "value": "1",
+                    "inserted_at": "2020-01-01 00:00:00.000",
+                    "updated_at": "2020-01-01 00:00:00.000",
+                }
+                for key in ["a", "b", "c"]
+            ]
+            import_out = import_(
+                db, input="\n".join(json.dumps(entry) for entry in entries)
+            )
+            self.assertEqual(import_out.returncode, 0)
+
+            self.assertEqual(run(db, ["blade", "get", "b"], env=env).returncode, 0)
+            self.assertEqual(run(db, ["blade", "get", "a"], env=env).returncode, 0)
+
+            list_out = run(db, ["blade", "list", "--recently-used"], env=env)
+            self.assertEqual(list_out.stdout, "a\t1\nb\t1\n")
+
+            # reads are not updates
+            [entry, *_] = [json.loads(line) for line in export(db).stdout.splitlines()]
+            self.assertEqual(entry["updated_at"], "2020-01-01 00:00:00.000")
+
+            self.assertEqual(
+                prune(db, "--not-used-in", "90d", "--dry-run").stdout,
+                "would prune 1 entries (1 bytes)\n",
+            )
+            self.assertEqual(len(list(db).stdout.splitlines()), 3)
+
+            self.assertEqual(
+                prune(db, "--not-used-in", "90d").stdout, "pruned 1 entries (1 bytes)\n"
+            )
+            self.assertEqual(get(db, "c").returncode, 1)
+            self.assertEqual(get(db, "a").stdout, "1")
+
+    def test_recently_used_get_stdin(self):
+        with test_db() as db, test_home("track_last_accessed = true") as env:
+            for key in ["a", "b", "c"]:
+                set(db, key, "1")
+
+            get_args = ["blade", "get", "--stdin"]
+            get_out = run(db, get_args, env=env, input="a\nmissing\nb\n")
+            self.assertEqual(get_out.returncode, 1)
+
+            list_out = run(db, ["blade", "list", "--recently-used"], env=env)
+            self.assertEqual(sorted(list_out.stdout.splitlines()), ["a\t1", "b\t1"])
+
+    def test_prune_by_age_and_size(self):
+        with test_db() as db:
+            entries = [
+                {
+                    "namespace": namespace,
+                    "key": key,
+                    "value": value,
+                    "inserted_at": "2020-01-01 00:00:00.000",
+                    "updated_at": updated_at,
+                }
+                for namespace, key, value, updated_at in [
+                    ("default", "old_small", "1", "2020-01-01 00:00:00.000"),
+                    ("default", "old_large", "x" * 2048, "2020-01-01 00:00:00.000"),
+                    ("default", "new_large", "x" * 2048, "2999-01-01 00:00:00.000"),
+                    ("scratch", "old_large", "x" * 2048, "2020-01-01 00:00:00.000"),
+                ]
+            ]
+            import_out = import_(
+                db, input="\n".join(json.dumps(entry) for entry in entries)
+            )
+            self.assertEqual(import_out.returncode, 0)
+
+            self.assertEqual(prune(db).returncode, 2)
+
+            prune_out = prune(
+                db,
+                "--namespace",
+                "default",
+                "--older-than",
+                "180d",
+                "--larger-than",
+                "1k",
+            )
+            self.assertEqual(prune_out.stdout, "pruned 1 entries (2048 bytes)\n")
+            self.assertEqual(get(db, "old_large").returncode, 1)
+            self.assertEqual(get(db, "old_small").returncode, 0)
+            self.assertEqual(get(db, "new_large").returncode, 0)
+            self.assertEqual(get(db, "old_large@scratch").returncode, 0)
+
+            self.assertEqual(
+                prune(db, "--larger-than", "1k").stdout,
+                "pruned 2 entries (4096 bytes)\n",
+            )
+
+    def test_namespace_quotas(self):
+        config = """
+[namespaces.limited]
+max_entries = 2
+
+[namespaces.cache]
+max_entries = 2
+max_bytes = 10
+eviction = "lru"
+"""
+
+        with test_db() as db, test_home(config) as env:
+
+            def set_(key, value):
+                return run(db, ["blade", "set", key, value], env=env)
+
+            self.assertEqual(set_("a@limited", "1").returncode, 0)
+            self.assertEqual(set_("b@limited", "1").returncode, 0)
+            set_out = set_("c@limited", "1")
+            self.assertEqual(set_out.returncode, 8)
+            self.assertIn("max_entries", set_out.stderr)
+            self.assertEqual(set_("a@limited", "2").returncode, 0)
+            self.assertEqual(get(db, "c@limited").returncode, 1)
+
+            # expired keys don't count
+            with sqlite3.connect(db) as conn:
+                conn.execute(
+                    "update entries set expires_at = '2000-01-01' where key = 'b'"
+                )
+            self.assertEqual(set_("c@limited", "1").returncode, 0)
+
+            self.assertEqual(set_("a@cache", "1").returncode, 0)
+            self.assertEqual(set_("b@cache", "1").returncode, 0)
+            self.assertEqual(set_("c@cache", "1").returncode, 0)
+            self.assertEqual(list_with_namespace(db, "cache").stdout, "c\t1\nb\t1\n")
+
+            self.assertEqual(set_("d@cache", "123456789").returncode, 0)
+            self.assertEqual(
+                list_with_namespace(db, "cache").stdout, "d\t123456789\nc\t1\n"
+            )
+
+            self.assertEqual(set_("e@cache", "12345678901").returncode, 8)
+            self.assertEqual(get(db, "d@cache").stdout, "123456789")
+
+    def test_cache_namespaces(self):
+        config = """
+[namespaces.lru]
+mode = "cache"
+max_entries = 2
+
+[namespaces.fifo]
+mode = "cache"
+max_entries = 2
+eviction = "fifo"
+"""
+
+        with test_db() as db, test_home(config) as env:
+            for namespace in ["lru", "fifo"]:
+                for key in ["a", "b"]:
+                    set_out = run(
+                        db, ["blade", "set", f"{key}@{namespace}", "1"], env=env
+                    )
+                    self.assertEqual(set_out.returncode, 0)
+
+                get_out = run(db, ["blade", "get", f"a@{namespace}"], env=env)
+                self.assertEqual(get_out.returncode, 0)
+
+                set_out = run(db, ["blade", "set", f"c@{namespace}", "1"], env=env)
+                self.assertEqual(set_out.returncode, 0)
+
+            self.assertEqual(list_with_namespace(db, "lru").stdout, "c\t1\na\t1\n")
+            self.assertEqual(list_with_namespace(db, "fifo").stdout, "c\t1\nb\t1\n")
+
+    def test_hierarchical_keys(self):
+        with test_db() as db:
+            for key in [
+                "deploy/prod/url",
+                "deploy/prod/token",
+                "deploy/staging/url",
+                "deployment",
+                "readme",
+            ]:
+                self.assertEqual(set(db, key, "1").returncode, 0)
+
+            tree_out = run(db, ["blade", "tree"])
+            self.assertEqual(
+                tree_out.stdout,
+                "deploy/\n"
+                "  prod/\n"
+                "    token\n"
+                "    url\n"
+                "  staging/\n"
+                "    url\n"
+                "deployment\n"
+                "readme\n",
+            )
+
+            tree_out = run(db, ["blade", "tree", "default", "deploy/prod/"])
+            self.assertEqual(tree_out.stdout, "deploy/\n  prod/\n    token\n    url\n")
+
+            list_out = run(db, ["blade", "list", "--prefix", "deploy/"])
+            self.assertEqual(
+                sorted(line.split("\t")[0] for line in list_out.stdout.splitlines()),
+                ["deploy/prod/token", "deploy/prod/url", "deploy/staging/url"],
+            )
+
+    def test_keys(self):
+        with test_db() as db:
+            for key in ["b", "a", "deploy/url", "deploy/token", "c@other"]:
+                self.assertEqual(set(db, key, "a value").returncode, 0)
+            self.assertEqual(set(db, "gone", "1").returncode, 0)
+            self.assertEqual(run(db, ["blade", "expire", "gone", "0s"]).returncode, 0)
+
+            keys_out = run(db, ["blade", "keys"])
+            self.assertEqual(keys_out.stdout, "a\nb\ndeploy/token\ndeploy/url\n")
+
+            keys_out = run(db, ["blade", "keys", "--prefix", "deploy/"])
+            self.assertEqual(keys_out.stdout, "deploy/token\ndeploy/url\n")
+
+            keys_out = run(db, ["blade", "keys", "*", "--prefix", "c"])
+            self.assertEqual(keys_out.stdout, "c@other\n")
+
+    def test_random(self):
+        with test_db() as db:
+            self.assertEqual(run(db, ["blade", "random", "notes"]).returncode, 1)
+
+            for key in ["a", "b", "c"]:
+                self.assertEqual(set(db, f"{key}@notes", key * 2).returncode, 0)
+
+            seen = {run(db, ["blade", "random", "notes"]).stdout for _ in range(30)}
+            self.assertEqual(seen, {"a\n", "b\n", "c\n"})
+
+            value = run(db, ["blade", "random", "notes", "--value"]).stdout
+            self.assertIn(value, ["aa", "bb", "cc"])
+
+    def test_list_with_cursor(self):
+        with test_db() as db:
+            for key in ["e", "c", "a", "d", "b", "f@other"]:
+                self.assertEqual(set(db, key, "1").returncode, 0)
+
+            def pages(*args):
+                listed, cursor = [], []
+                while True:
+                    args_and_cursor = [*args, "--limit", "2", *cursor]
+                    list_out = run(db, ["blade", "list", *args_and_cursor])
+                    self.assertEqual(list_out.returncode, 0)
+                    listed.append(list_out.stdout)
+                    if not list_out.stderr:
+                        return listed
+                    cursor = ["--cursor", list_out.stderr.strip()]
+
+            self.assertEqual(pages(), ["a\t1\nb\t1\n", "c\t1\nd\t1\n", "e\t1\n"])
+            self.assertEqual(
+                pages("*", "--keys-only"),
+                [
+                    "a@default\nb@default\n",
+                    "c@default\nd@default\n",
+                    "e@default\nf@other\n",
+                ],
+            )
+
+            list_out = run(db, ["blade", "list", "--cursor", "nonsense"])
+            self.assertEqual(list_out.returncode, 2)
+
+            cursor = run(db, ["blade", "list", "--limit", "1"]).stderr.strip()
+            list_out = run(db, ["blade", "list", "other", "--cursor", cursor])
+            self.assertEqual(list_out.returncode, 2)
+            self.assertIn("namespace default, not other", list_out.stderr)
+
+    def test_nested_namespaces(self):
+        with test_db() as db:
+            for key in ["a@work.projecta", "b@work.projectb.sub", "c@work-x", "d@home"]:
+                self.assertEqual(set(db, key, "1").returncode, 0)
+
+            tree_out = run(db, ["blade", "list-namespaces", "--tree"])
+            self.assertEqual(
+                tree_out.stdout,
+                "home\nwork\n  projecta\n  projectb\n    sub\nwork-x\n",
+            )
+
+            list_out = list_with_namespace(db, "work.*")
+            self.assertEqual(
+                list_out.stdout, "b@work.projectb.sub\t1\na@work.projecta\t1\n"
+            )
+
+            export_out = export(db, "--namespace", "work.*")
+            self.assertEqual(
+                [json.loads(line)["key"] for line in export_out.stdout.splitlines()],
+                ["a", "b"],
+            )
+
+    def test_namespace_descriptions(self):
+        with test_db() as db:
+            self.assertEqual(set(db, "a@clientx", "1").returncode, 0)
+            self.assertEqual(set(db, "b@clientx", "1").returncode, 0)
+
+            describe_out = run(
+                db,
+                ["blade", "namespace", "describe", "clientx", "client X credentials"],
+            )
+            self.assertEqual(describe_out.returncode, 0)
+            describe_out = run(db, ["blade", "namespace", "describe", "empty", "soon"])
+            self.assertEqual(describe_out.returncode, 0)
+
+            describe_out = run(db, ["blade", "namespace", "describe", "clientx"])
+            self.assertEqual(describe_out.stdout, "client X credentials\n")
+
+            self.assertEqual(
+                run(db, ["blade", "list-namespaces"]).stdout, "clientx\nempty\n"
+            )
+
+            long_out = run(db, ["blade", "list-namespaces", "--long"])
+            rows = [line.split("\t") for line in long_out.stdout.splitlines()]
+            self.assertEqual(
+                [(row[0], row[1], row[3]) for row in rows],
+                [("clientx", "2", "client X credentials"), ("empty", "0", "soon")],
+            )
+
+    def test_expire_ttl_and_persist(self):
+        with test_db() as db:
+            set(db, "a", "1")
+            self.assertEqual(run(db, ["blade", "ttl", "a"]).stdout, "none\n")
+
+            self.assertEqual(run(db, ["blade", "expire", "a", "1d"]).returncode, 0)
+            ttl_out = run(db, ["blade", "ttl", "a"])
+            self.assertIn(ttl_out.stdout, ["1d\n", "23h59m59s\n"])
+
+            self.assertEqual(run(db, ["blade", "persist", "a"]).returncode, 0)
+            self.assertEqual(run(db, ["blade", "ttl", "a"]).stdout, "none\n")
+
+            self.assertEqual(run(db, ["blade", "expire", "a", "0s"]).returncode, 0)
+            self.assertEqual(get(db, "a").returncode, 1)
+            self.assertEqual(run(db, ["blade", "ttl", "a"]).returncode, 1)
+            self.assertEqual(run(db, ["blade", "persist", "a"]).returncode, 1)
+
+    def test_gc(self):
+        def count(db):
+            conn = sqlite3.connect(db)
+            (n,) = conn.execute("select count(*) from entries").fetchone()
+            conn.close()
+            return n
+
+        with test_db() as db:
+            for key in ["a", "b", "c", "d"]:
+                set(db, key, "1")
+            run(db, ["blade", "expire", "a", "0s"])
+            run(db, ["blade", "expire", "b", "0s"])
+            run(db, ["blade", "expire", "c", "0s"])
+
+            gc_out = run(db, ["blade", "gc", "--max", "1"])
+            self.assertEqual(gc_out.stdout, "deleted 1 expired entries\n")
+            self.assertEqual(count(db), 3)
+
+            with test_home("expiry_sweep_limit = 1") as env:
+                self.assertEqual(run(db, ["blade", "get", "d"], env=env).returncode, 0)
+            self.assertEqual(count(db), 2)
+
+            gc_out = run(db, ["blade", "gc"])
+            self.assertEqual(gc_out.stdout, "deleted 1 expired entries\n")
+            self.assertEqual(count(db), 1)
+
+    def test_ttl_and_namespace_defaults(self):
+        with test_db() as db:
+            set_out = run(db, ["blade", "set", "a", "1", "--ttl", "1h"])
+            self.assertEqual(set_out.returncode, 0)
+            self.assertEqual(get(db, "a").stdout, "1")
+
+            for ttl in ["99999999999999999999999y", "9999999999y"]:
+                set_out = run(db, ["blade", "set", "a", "2", f"--ttl={ttl}"])
+                self.assertEqual(set_out.returncode, 2)
+            self.assertEqual(get(db, "a").stdout, "1")
+
+            def set_default(*args):
+                return run(db, ["blade", "namespace", "set-default", *args])
+
+            self.assertEqual(set_default("cache", "ttl=2d").returncode, 0)
+            self.assertEqual(set_default("cache").stdout, "ttl=2d\n")
+            self.assertEqual(set_default("cache", "compression=on").returncode, 2)
+            self.assertEqual(set_default("cache", "ttl=9999999999y").returncode, 2)
+
+            self.assertEqual(set(db, "b@cache", "1").returncode, 0)
+            self.assertEqual(set(db, "c", "1").returncode, 0)
+
+            conn = sqlite3.connect(db)
+            expires = dict(conn.execute("select key, expires_at from entries"))
+            self.assertIsNotNone(expires["a"])
+            self.assertIsNotNone(expires["b"])
+            self.assertIsNone(expires["c"])
+
+            conn.execute("update entries set expires_at = '2000-01-01' where key = 'a'")
+            conn.commit()
+            conn.close()
+
+            self.assertEqual(get(db, "a").returncode, 1)
+            self.assertEqual(list(db).stdout, "c\t1\n")
+
+            # an expired key is set as if it were new
+            self.assertEqual(set(db, "a", "2").returncode, 0)
+            self.assertEqual(get(db, "a").stdout, "2")
+
+            self.assertEqual(set_default("cache", "ttl=none").returncode, 0)
+            self.assertEqual(set_default("cache").stdout, "")
+
+    def test_aliases(self):
+        with test_db() as db:
+
+            def alias(*args):
+                return run(db, ["blade", "alias", *args])
+
+            self.assertEqual(set(db, "long/generated/key@ns", "1").returncode, 0)
+
+            create_out = alias("create", "short", "long/generated/key@ns")
+            self.assertEqual(create_out.returncode, 0)
+            self.assertEqual(alias("create", "shorter", "short").returncode, 0)
+            self.assertEqual(get(db, "short").stdout, "1")
+            self.assertEqual(get(db, "shorter").stdout, "1")
+
+            self.assertEqual(alias("create", "short", "shorter").returncode, 2)
+            self.assertEqual(alias("create", "short", "short").returncode, 2)
+
+            # setting an alias sets the key it points to
+            self.assertEqual(set(db, "shorter", "2").returncode, 0)
+            self.assertEqual(get(db, "long/generated/key@ns").stdout, "2")
+            self.assertEqual(get(db, "shorter").stdout, "2")
+            self.assertEqual(list(db).stdout, "")
+
+            self.assertEqual(set(db, "plain", "1").returncode, 0)
+            self.assertEqual(alias("create", "plain", "short").returncode, 2)
+
+            self.assertEqual(
+                alias("list").stdout,
+                "short\tlong/generated/key@ns\nshorter\tshort@default\n",
+            )
+
+            self.assertEqual(alias("delete", "short").returncode, 0)
+            self.assertEqual(alias("delete", "short").returncode, 1)
+            self.assertEqual(get(db, "shorter").returncode, 1)
+
+            # a cycle can only come from editing the database, so it's corruption
+            with sqlite3.connect(db) as conn:
+                cycle = ("default", "short", "default", "shorter")
+                conn.execute("insert into aliases values (?, ?, ?, ?)", cycle)
+            self.assertEqual(get(db, "short").returncode, 4)
+
+    def test_import_conflicts_and_dry_run(self):
+
+        with test_db() as db:
+            self.assertEqual(set(db, "a", "existing").returncode, 0)
+            self.assertEqual(set(db, "b", "same").returncode, 0)
+
+            entries = "\n".join(
+                json.dumps(entry)
+                for entry in [
+                    {
+                        "namespace": "default",
+                        "key": "a",
+                        "value": "imported",
+                        "inserted_at": "2020-01-01 00:00:00.000",
+                        "updated_at": "2020-01-01 00:00:00.000",
+                    },
+                    {
+                        "namespace": "default",
+                        "key": "b",
+                        "value": "same",
+                        "inserted_at": "2020-01-01 00:00:00.000",
+                        "updated_at": "2020-01-01 00:00:00.000",
+                    },
+                    {
+                        "namespace": "default",
+                        "key": "c",
+                        "value": "new",
+                        "inserted_at": "2020-01-01 00:00:00.000",
+                        "updated_at": "2020-01-01 00:00:00.000",
+                    },
+                ]
+            )
+
+            import_out = import_(db, "--dry-run", input=entries)
+            self.assertEqual(import_out.returncode, 0)
+            self.assertEqual(
+                import_out.stdout, "dry run: created 1, updated 1, skipped 1\n"
+            )
+            self.assertEqual(get(db, "a").stdout, "existing")
+            self.assertEqual(get(db, "c").stdout, "")
+
+            import_out = import_(db, "--on-conflict", "fail", input=entries)
+            self.assertEqual(import_out.returncode, 6)
+            self.assertEqual(get(db, "c").stdout, "")
+
+            import_out = import_(db, "--on-conflict", "newest", input=entries)
+            self.assertEqual(import_out.returncode, 0)
+            self.assertEqual(import_out.stdout, "created 1, updated 0, skipped 2\n")
+            self.assertEqual(get(db, "a").stdout, "existing")
+            self.assertEqual(get(db, "c").stdout, "new")
+
+            import_out = import_(db, "--on-conflict", "overwrite", input=entries)
+            self.assertEqual(import_out.stdout, "created 0, updated 1, skipped 2\n")
+            self.assertEqual(get(db, "a").stdout, "imported")
+
+            # a key that appears twice is counted the same with and without --dry-run
+            entry = {"namespace": "default", "key": "d"}
+            entry["inserted_at"] = entry["updated_at"] = "2020-01-01 00:00:00.000"
+            twice = "\n".join(
+                json.dumps({**entry, "value": value}) for value in ["1", "1", "2"]
+            )
+            dry_run_out = import_(
+                db, "--on-conflict", "overwrite", "--dry-run", input=twice
+            )
+            self.assertEqual(
+                dry_run_out.stdout, "dry run: created 1, updated 1, skipped 1\n"
+            )
+            self.assertEqual(get(db, "d").returncode, 1)
+            import_out = import_(db, "--on-conflict", "overwrite", input=twice)
+            self.assertEqual(import_out.stdout, "created 1, updated 1, skipped 1\n")
+
+    def test_export_and_import_csv(self):
+        with test_db() as db, test_db() as other_db:
+            self.assertEqual(set(db, "a", 'has "quotes", commas').returncode, 0)
+            self.assertEqual(set(db, "b@ns1", "base64:looks encoded").returncode, 0)
+            with tempfile.TemporaryFile() as file:
+                file.write(b"\xff\x00\xfe")
+                file.seek(0)
+                set_from_file_redirection(db, "c", file)
+
+            export_out = export(db, "--format", "csv")
+            self.assertEqual(export_out.returncode, 0)
+
+            rows = [row[:3] for row in csv.reader(io.StringIO(export_out.stdout))]
+            self.assertEqual(
+                rows,
+                [
+                    ["key", "value", "namespace"],
+                    ["a", 'has "quotes", commas', "default"],
+                    ["c", "base64:/wD+", "default"],
+                    ["b", "base64:YmFzZTY0Omxvb2tzIGVuY29kZWQ=", "ns1"],
+                ],
+            )
+
+            import_out = import_(
+                other_db,
+                "--format",
+                "csv",
+                "--namespace-column",
+                "2",
+                input=export_out.stdout,
+            )
+            self.assertEqual(import_out.returncode, 0)
+            self.assertEqual(get(other_db, "a").stdout, 'has "quotes", commas')
+            self.assertEqual(get(other_db, "b@ns1").stdout, "base64:looks encoded")
+
+    def test_import_tsv_columns(self):
+        with test_db() as db:
+            import_out = import_(
+                db,
+                "--format",
+                "tsv",
+                "--no-header",
+                "--key-column",
+                "1",
+                "--value-column",
+                "0",
+                "--namespace",
+                "sheet",
+                input="1\tone\n2\ttwo\n",
+            )
+            self.assertEqual(import_out.returncode, 0)
+            self.assertEqual(get(db, "one@sheet").stdout, "1")
+            self.assertEqual(get(db, "two@sheet").stdout, "2")
+
+            import_out = import_(
+                db, "--format", "tsv", "--value-column", "5", input="k\tv\na\tb\n"
+            )
+            self.assertEqual(import_out.returncode, 2)
+
+    def test_import_rejects_keys_get_cant_reach(self):
+        with test_db() as db:
+            for key, namespace in [("a@b", "default"), ("a", "b@c"), (" ", "default")]:
+                entry = {"namespace": namespace, "key": key, "value": "1"}
+                entry["inserted_at"] = entry["updated_at"] = "2020-01-01 00:00:00.000"
+                import_out = import_(db, input=json.dumps(entry))
+                self.assertEqual(import_out.returncode, 2)
+
+            import_out = import_(db, "--format", "csv", input="key,value\na@b,1\n")
+            self.assertEqual(import_out.returncode, 2)
+            self.assertEqual(list(db).stdout, "")
+
+    def test_import_redis(self):
+        data = {"app:a": "1", "app:b": "2", "app:queue": ["x"], "other": "3"}
+        with test_db() as db, test_redis_server(data, {"app:b": 60_000}) as url:
+            import_out = import_(
+                db, "--redis", url, "--match", "app:*", "--namespace", "cache"
+            )
+            self.assertEqual(import_out.returncode, 0)
+            self.assertIn("created 2,", import_out.stdout)
+            self.assertIn("app:queue", import_out.stderr)
+
+            self.assertEqual(get(db, "app:a@cache").stdout, "1")
+            self.assertEqual(get(db, "other@cache").returncode, 1)
+            ttl = run(db, ["blade", "ttl", "app:a@cache"])
+            self.assertEqual(ttl.stdout, "none\n")
+            ttl = run(db, ["blade", "ttl", "app:b@cache"])
+            self.assertRegex(ttl.stdout, "^(59|1m)")
+
+    def test_export_and_import_consul_and_etcd(self):
+        store = {}
+
+        def b64(s):
+            return base64.b64encode(s).decode()
+
+        def handler(request):
+            path = urllib.parse.unquote(request.path.partition("?")[0])
+            length = int(request.headers.get("Content-Length") or 0)
+            body = request.rfile.read(length)
+            status, reply = 200, {}
+            if request.command == "PUT":
+                store[path.removeprefix("/v1/kv/").encode()] = body
+            elif request.command == "GET":
+                prefix = path.removeprefix("/v1/kv/").encode()
+                reply = [
+                    {"Key": k.decode(), "Value": b64(v)}
+                    for k, v in sorted(store.items())
+                    if k.startswith(prefix)
+                ]
+                status = 200 if reply else 404
+            elif path == "/v3/kv/put":
+                body = json.loads(body)
+                store[base64.b64decode(body["key"])] = base64.b64decode(body["value"])
+            elif path == "/v3/kv/range":
+                body = json.loads(body)
+                start = base64.b64decode(body["key"])
+                end = base64.b64decode(body["range_end"])
+                reply = {
+                    "kvs": [
+                        {"key": b64(k), "value": b64(v)}
+                        for k, v in sorted(store.items())
+                        if start <= k < end
+                    ]
+                }
+            request.send_response(status)
+            request.end_headers()
+            request.wfile.write(json.dumps(reply).encode())
+
+        with test_db() as db, test_db() as other_db, test_server(handler) as url:
+            self.assertEqual(set(db, "a@work", "1").returncode, 0)
+            self.assertEqual(set(db, "dir/b@work", "2").returncode, 0)
+            self.assertEqual(set(db, "c@home", "3").returncode, 0)
+
+            consul = url + "/v1/kv/myapp"
+            self.assertEqual(export(db, "--consul", consul).returncode, 0)
+            self.assertEqual(store[b"myapp/work/dir/b"], b"2")
+
+            import_out = import_(other_db, "--consul", consul)
+            self.assertEqual(import_out.returncode, 0)
+            self.assertIn("created 3,", import_out.stdout)
+            self.assertEqual(get(other_db, "dir/b@work").stdout, "2")
+            self.assertEqual(get(other_db, "c@home").stdout, "3")
+
+            etcd = url + "/svc"
+            export_out = export(db, "--etcd", etcd, "--namespace", "work")
+            self.assertEqual(export_out.returncode, 0)
+            self.assertEqual(store[b"svc/a"], b"1")
+
+            import_out = import_(other_db, "--etcd", etcd, "--namespace", "copy")
+            self.assertEqual(import_out.returncode, 0)
+            self.assertIn("created 2,", import_out.stdout)
+            self.assertEqual(get(other_db, "dir/b@copy").stdout, "2")
+
+    def test_export_k8s_manifests(self):
+        with test_db() as db:
+            self.assertEqual(set(db, "db-pass@work", 'hunter"2').returncode, 0)
+            self.assertEqual(set(db, "other", "x").returncode, 0)
+            with tempfile.TemporaryFile() as file:
+                file.write(b"\xff\x00\xfe")
+                file.seek(0)
+                set_from_file_redirection(db, "bin@work", file)
+
+            args = ["--namespace", "work", "--k8s-namespace", "prod"]
+            export_out = export(db, "--k8s-secret", "app", *args)
+            self.assertEqual(export_out.returncode, 0)
+            self.assertEqual(
+                export_out.stdout,
+                "apiVersion: v1\n"
+                "kind: Secret\n"
+                "metadata:\n"
+                '  name: "app"\n'
+                '  namespace: "prod"\n'
+                "type: Opaque\n"
+                "data:\n"
+                '  "bin": "/wD+"\n'
+                '  "db-pass": "aHVudGVyIjI="\n',
+            )
+
+            export_out = export(db, "--k8s-config-map", "app", "--namespace", "work")
+            self.assertEqual(export_out.returncode, 0)
+            self.assertIn('data:\n  "db-pass": "hunter\\"2"\n', export_out.stdout)
+            self.assertIn('binaryData:\n  "bin": "/wD+"\n', export_out.stdout)
+
+            self.assertEqual(set(db, "a/b@work", "1").returncode, 0)
+            export_out = export(db, "--k8s-secret", "app", "--namespace", "work")
+            self.assertEqual(export_out.returncode, 2)
+
+    def test_export_and_import_git_dir(self):
+        with test_db() as db, test_db() as other_db, tempfile.TemporaryDirectory() as d:
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+            self.assertEqual(set(db, "dir/.b@work", "2").returncode, 0)
+            self.assertEqual(set(db, "c@work", "3").returncode, 0)
+
+            self.assertEqual(export(db, "--git-dir", d).returncode, 0)
+            with open(d + "/work/dir_2F.b") as f:
+                self.assertEqual(f.read(), "2")
+            with open(d + "/manifest.jsonl") as f:
+                manifest = [json.loads(line) for line in f]
+            self.assertEqual(
+                [(e["key"], e["file"]) for e in manifest],
+                [("a", "default/a"), ("c", "work/c"), ("dir/.b", "work/dir_2F.b")],
+            )
+
+            self.assertEqual(delete(db, "c@work").returncode, 0)
+            self.assertEqual(export(db, "--git-dir", d).returncode, 0)
+            self.assertFalse(os.path.exists(d + "/work/c"))
+
+            # exporting part of the database leaves the rest of the export alone
+            self.assertEqual(set(db, "e@work", "5").returncode, 0)
+            export_out = export(db, "--git-dir", d, "--namespace", "work")
+            self.assertEqual(export_out.returncode, 0)
+            self.assertTrue(os.path.exists(d + "/default/a"))
+            self.assertEqual(delete(db, "e@work").returncode, 0)
+            self.assertEqual(export(db, "--git-dir", d, "--since", "1m").returncode, 0)
+            self.assertTrue(os.path.exists(d + "/default/a"))
+            self.assertFalse(os.path.exists(d + "/work/e"))
+            with open(d + "/manifest.jsonl") as f:
+                manifest = [json.loads(line) for line in f]
+            self.assertEqual([e["key"] for e in manifest], ["a", "dir/.b"])
+
+            import_out = import_(other_db, "--git-dir", d)
+            self.assertEqual(import_out.returncode, 0)
+            self.assertIn("created 2,", import_out.stdout)
+            self.assertEqual(get(other_db, "dir/.b@work").stdout, "2")
+
+    def test_export_and_import_archive(self):
+        with (
+            test_db() as db,
+            test_db() as other_db,
+            tempfile.TemporaryDirectory() as archive_dir,
+        ):
+            archive = archive_dir + "/backup.blade"
+            encrypted_archive = archive_dir + "/encrypted.blade"
+            passphrase = {"BLADE_ARCHIVE_PASSPHRASE": "hunter2"}
+
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+            self.assertEqual(set(db, "b@ns1", "2").returncode, 0)
+
+            self.assertEqual(export(db, "--archive", archive).returncode, 0)
+            self.assertEqual(
+                export(db, "--archive", encrypted_archive, env=passphrase).returncode,
+                0,
+            )
+
+            with open(encrypted_archive, "rb") as f:
+                self.assertTrue(f.read().startswith(b"age-encryption.org/"))
+
+            self.assertEqual(import_(other_db, "--archive", archive).returncode, 0)
+            self.assertEqual(get(other_db, "b@ns1").stdout, "2")
+
+            self.assertEqual(
+                import_(other_db, "--archive", encrypted_archive).returncode, 2
+            )
+            self.assertEqual(
+                import_(
+                    other_db,
+                    "--archive",
+                    encrypted_archive,
+                    env={"BLADE_ARCHIVE_PASSPHRASE": "wrong"},
+                ).returncode,
+                9,
+            )
+
+            with open(encrypted_archive, "rb") as f:
+                encrypted = f.read()
+            mac = encrypted.index(b"\n--- ") + 5
+            corrupt_archive = archive_dir + "/corrupt.blade"
+            with open(corrupt_archive, "wb") as f:
+                flipped = b"A" if encrypted[mac : mac + 1] != b"A" else b"B"
+                f.write(encrypted[:mac] + flipped + encrypted[mac + 1 :])
+            self.assertEqual(
+                import_(
+                    other_db, "--archive", corrupt_archive, env=passphrase
+                ).returncode,
+                4,
+            )
+
+            self.assertEqual(set(other_db, "a", "changed").returncode, 0)
+            self.assertEqual(
+                import_(
+                    other_db, "--archive", encrypted_archive, env=passphrase
+                ).returncode,
+                0,
+            )
+            self.assertEqual(get(other_db, "a").stdout, "1")
+
+    def test_signed_archive(self):
+        with (
+            test_db() as db,
+            test_db() as other_db,
+            tempfile.TemporaryDirectory() as archive_dir,
+        ):
+            archive = archive_dir + "/backup.blade"
+            secret_key = archive_dir + "/secret.key"
+
+            keygen_out = run(db, ["blade", "generate-signing-key", secret_key])
+            self.assertEqual(keygen_out.returncode, 0)
+            public_key = keygen_out.stdout.strip()
+
+            other_keygen_out = run(
+                db, ["blade", "generate-signing-key", archive_dir + "/other.key"]
+            )
+            other_public_key = other_keygen_out.stdout.strip()
+
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+            self.assertEqual(
+                export(db, "--archive", archive, "--sign", secret_key).returncode, 0
+            )
+            self.assertTrue(os.path.exists(archive + ".sig"))
+
+            self.assertEqual(
+                import_(
+                    other_db, "--archive", archive, "--verify", other_public_key
+                ).returncode,
+                9,
+            )
+            self.assertEqual(get(other_db, "a").stdout, "")
+
+            self.assertEqual(
+                import_(
+                    other_db, "--archive", archive, "--verify", public_key
+                ).returncode,
+                0,
+            )
+            self.assertEqual(get(other_db, "a").stdout, "1")
+
+            with open(archive, "ab") as f:
+                f.write(b"tampered")
+
+            self.assertEqual(
+                import_(
+                    other_db, "--archive", archive, "--verify", public_key
+                ).returncode,
+                9,
+            )
+
+    def test_bench(self):
+        with test_db() as db:
+            bench_out = run(
+                db,
+                [
+                    "blade",
+                    "bench",
+                    "--ops",
+                    "50",
+                    "--value-size",
+                    "2k",
+                    "--concurrency",
+                    "2",
+                ],
+            )
+            self.assertEqual(bench_out.returncode, 0)
+
+            lines = bench_out.stdout.splitlines()
+            self.assertIn("value size: 2048 bytes", lines[0])
+            self.assertEqual(
+                [line.split()[0] for line in lines[1:]], ["op", "set", "get", "delete"]
+            )
+
+            # the benchmark runs against a temporary database
+            self.assertEqual(list(db).stdout, "")
+
+            self.assertEqual(
+                run(db, ["blade", "bench", "--value-size", "1q"]).returncode, 2
+            )
+
+    def test_sql(self):
+        with test_db() as db:
+            self.assertEqual(set(db, "a", "hello").returncode, 0)
+            self.assertEqual(set(db, "b@ns1", "hi").returncode, 0)
+
+            sql_out = sql(
+                db,
+                "select namespace, key, length(value) as size from entries order by key",
+            )
+            self.assertEqual(sql_out.returncode, 0)
+            self.assertEqual(
+                sql_out.stdout, "namespace\tkey\tsize\ndefault\ta\t5\nns1\tb\t2\n"
+            )
+
+            self.assertEqual(
+                sql(db, "select key, value from entries", ",").stdout,
+                "key,value\na,hello\nb,hi\n",
+            )
+
+    def test_sql_is_read_only_by_default(self):
+        with test_db() as db:
+            self.assertEqual(set(db, "a", "hello").returncode, 0)
+
+            sql_out = sql(db, "delete from entries")
+            self.assertEqual(sql_out.returncode, 7)
+            self.assertIn("readonly", sql_out.stderr)
+            self.assertEqual(get(db, "a").stdout, "hello")
+
+            sql_out = sql(db, "delete from entries", "--write")
+            self.assertEqual(sql_out.returncode, 0)
+            self.assertEqual(get(db, "a").stdout, "")
+
     def test_dump_config(self):
         with test_db() as db:
             dump_config_out = dump_config(db)
@@ -263,25 +1909,1241 @@ class TestBlade(unittest.TestCase):
             self.assertIn('sqlite_synchronous_mode = "normal"', dump_config_out.stdout)
             self.assertIn("sqlite_busy_timeout_ms = 5000", dump_config_out.stdout)
 
+    def test_init(self):
+        with test_db() as db, tempfile.TemporaryDirectory() as home:
+            env = {"HOME": home}
+            init_out = run(
+                db, ["blade", "init"], env=env, input=f"{db}\nwork\ny\n"
+            )
+            self.assertEqual(init_out.returncode, 0)
+            self.assertIn("created", init_out.stdout)
+            self.assertTrue(os.path.exists(db))
+
+            with open(home + "/.config/blade/config.toml") as f:
+                config = f.read()
+            self.assertIn('default_namespace = "work"', config)
+            self.assertIn('sqlite_synchronous_mode = "full"', config)
+
+            self.assertEqual(run(db, ["blade", "set", "a", "1"], env=env).returncode, 0)
+            self.assertEqual(get(db, "a@work").stdout, "1")
+
+            # doesn't replace the config without --force
+            self.assertEqual(run(db, ["blade", "init"], env=env).returncode, 6)
+            init_out = run(db, ["blade", "init", "--force"], env=env, input="\n\nn\n")
+            self.assertEqual(init_out.returncode, 0)
+            self.assertIn("using the existing database", init_out.stdout)
+
+            with open(home + "/.config/blade/config.toml") as f:
+                config = f.read()
+            self.assertIn('default_namespace = "work"', config)
+            self.assertIn('sqlite_synchronous_mode = "normal"', config)
+
+    def test_doctor(self):
+        with test_db() as db, test_home("") as env:
+            doctor_out = run(db, ["blade", "doctor"], env=env)
+            self.assertEqual(doctor_out.returncode, 0)
+            self.assertIn("no database yet", doctor_out.stdout)
+            # doesn't create the database
+            self.assertFalse(os.path.exists(db))
+
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+            open(db + "-journal", "w").close()
+
+            doctor_out = run(db, ["blade", "doctor"], env=env)
+            self.assertEqual(doctor_out.returncode, 0)
+            self.assertIn("schema is up to date", doctor_out.stdout)
+            self.assertIn("warning  a rollback journal", doctor_out.stdout)
+
+        with test_db() as db, test_home("sqlite_busy_timeout_ms = [") as env:
+            doctor_out = run(db, ["blade", "doctor"], env=env)
+            self.assertEqual(doctor_out.returncode, 7)
+            self.assertIn("error    config file", doctor_out.stdout)
+            self.assertIn("fix: ", doctor_out.stdout)
+
+    def test_sqlite_tuning_config(self):
+        config = "\n".join(
+            [
+                'db_location = "/nonexistent/blade.db"',
+                "sqlite_page_size = 8192",
+                "sqlite_cache_size = -4000",
+                'sqlite_temp_store = "memory"',
+                'sqlite_auto_vacuum = "incremental"',
+            ]
+        )
+
+        with test_db() as db, test_home(config) as env:
+            dump_config_out = dump_config(db, env=env)
+            self.assertEqual(dump_config_out.returncode, 0)
+            self.assertIn('sqlite_synchronous_mode = "normal"', dump_config_out.stdout)
+            self.assertIn("sqlite_page_size = 8192", dump_config_out.stdout)
+            self.assertIn("sqlite_cache_size = -4000", dump_config_out.stdout)
+            self.assertIn("sqlite_wal_autocheckpoint = 1000", dump_config_out.stdout)
+            self.assertIn('sqlite_temp_store = "memory"', dump_config_out.stdout)
+            self.assertIn('sqlite_auto_vacuum = "incremental"', dump_config_out.stdout)
+
+            sql_out = run(db, ["blade", "sql", "pragma cache_size"], env=env)
+            self.assertEqual(sql_out.stdout, "cache_size\n-4000\n")
+
+    def test_busy_retry(self):
+        no_retry_config = "\n".join(
+            [
+                'db_location = "/nonexistent/blade.db"',
+                "sqlite_busy_timeout_ms = 0",
+            ]
+        )
+        retry_config = "\n".join(
+            [
+                no_retry_config,
+                "[busy_retry]",
+                "max_attempts = 20",
+                "initial_backoff_ms = 10",
+                "max_backoff_ms = 100",
+            ]
+        )
+
+        with (
+            test_db() as db,
+            test_home(no_retry_config) as no_retry_env,
+            test_home(retry_config) as retry_env,
+        ):
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+
+            lock = sqlite3.connect(db, isolation_level=None, check_same_thread=False)
+
+            lock.execute("begin immediate")
+            try:
+                set_out = run(db, ["blade", "set", "a", "2"], env=no_retry_env)
+                self.assertEqual(set_out.returncode, 3)
+                self.assertIn("database is locked", set_out.stderr)
+            finally:
+                lock.rollback()
+
+            lock.execute("begin immediate")
+            threading.Timer(0.3, lock.rollback).start()
+
+            set_out = run(db, ["blade", "set", "a", "3"], env=retry_env)
+            self.assertEqual(set_out.returncode, 0)
+            self.assertIn("database is busy, retrying", set_out.stderr)
+            self.assertEqual(get(db, "a").stdout, "3")
+
+            lock.execute("begin immediate")
+            threading.Timer(0.3, lock.rollback).start()
+
+            entry = {"namespace": "default", "key": "b", "value": "imported"}
+            entry |= {"inserted_at": "2024-01-01", "updated_at": "2024-01-01"}
+            import_args = ["blade", "import"]
+            import_out = run(db, import_args, env=retry_env, input=json.dumps(entry))
+            self.assertEqual(import_out.returncode, 0)
+            self.assertIn("database is busy, retrying", import_out.stderr)
+            self.assertEqual(get(db, "b").stdout, "imported")
+
+    def test_timeout(self):
+        with test_db() as db:
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+
+            lock = sqlite3.connect(db, isolation_level=None)
+            lock.execute("begin immediate")
+            try:
+                start = time.monotonic()
+                set_args = ["blade", "--timeout", "500ms", "set", "a", "2"]
+                set_out = run(db, set_args)
+                self.assertLess(time.monotonic() - start, 3)
+                self.assertEqual(set_out.returncode, 10)
+                self.assertIn("timed out after 500ms", set_out.stderr)
+            finally:
+                lock.rollback()
+                lock.close()
+
+            self.assertEqual(get(db, "a").stdout, "1")
+            self.assertEqual(get(db, "a", "--timeout", "5s").stdout, "1")
+
+            # a command waiting for stdin, not the database, times out too
+            with subprocess.Popen(
+                ["blade", "--timeout", "500ms", "set", "b"],
+                stdin=subprocess.PIPE,
+                stderr=subprocess.PIPE,
+                env=os.environ | {"DB_LOCATION": db},
+            ) as set_process:
+                self.assertEqual(set_process.wait(timeout=5), 10)
+                self.assertIn(b"timed out after 500ms", set_process.stderr.read())
+
+    def test_verbose_logging(self):
+        with test_db() as db:
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+
+            self.assertEqual(list(db).stderr, "")
+
+            list_out = run(db, ["blade", "-v", "list"])
+            self.assertEqual(list_out.returncode, 0)
+            self.assertEqual(list_out.stdout, "a\t1\n")
+            self.assertIn("loaded config", list_out.stderr)
+            self.assertIn("opened database", list_out.stderr)
+            self.assertIn("executed statement", list_out.stderr)
+            self.assertIn("listed entries rows=1", list_out.stderr)
+
+            list_out = run(
+                db,
+                ["blade", "list"],
+                env={"BLADE_LOG": "debug", "BLADE_LOG_FORMAT": "json"},
+            )
+            self.assertEqual(list_out.returncode, 0)
+            logs = [json.loads(line) for line in list_out.stderr.splitlines()]
+            self.assertIn(
+                {"message": "listed entries", "rows": 1},
+                [log["fields"] for log in logs],
+            )
+
+    def test_get_newline_and_binary(self):
+        with test_db() as db:
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+            with tempfile.TemporaryFile() as file:
+                file.write(b"\xff\x00\xfe")
+                file.seek(0)
+                set_from_file_redirection(db, "bin", file)
+
+            # piped values are written exactly as they were set
+            self.assertEqual(get(db, "a").stdout, "1")
+            self.assertEqual(get(db, "a", "--newline").stdout, "1\n")
+            self.assertEqual(run_in_terminal(db, ["blade", "get", "a"]), "1\n")
+
+            with self.assertRaises(subprocess.CalledProcessError):
+                run_in_terminal(db, ["blade", "get", "bin"])
+            args = ["blade", "get", "bin", "--binary-ok"]
+            output = run_in_terminal(db, args, decode=False)
+            self.assertTrue(output.startswith(b"\xff\x00\xfe"))
+
+    def test_get_default(self):
+        with test_db() as db, tempfile.NamedTemporaryFile("w") as default_file:
+            default_file.write("from file")
+            default_file.flush()
+
+            self.assertEqual(set(db, "a", "1").returncode, 0)
+
+            get_out = run(db, ["blade", "get", "a", "--default", "fallback"])
+            self.assertEqual(get_out.stdout, "1")
+
+            get_out = run(db, ["blade", "get", "missing", "--default", "fallback"])
+            self.assertEqual(get_out.returncode, 0)
+            self.assertEqual(get_out.stdout, "fallback")
+
+            get_out = run(
+                db, ["blade", "get", "missing", "--default-file", default_file.name]
+            )
+            self.assertEqual(get_out.returncode, 0)
+            self.assertEqual(get_out.stdout, "from file")
+
+    def test_render(self):
+        with test_db() as db, tempfile.NamedTemporaryFile("w") as template:
+            template.write("user={{ user@acme }}\npassword={{password@acme}}\n")
+            template.flush()
+
+            self.assertEqual(set(db, "user@acme", "admin").returncode, 0)
+            self.assertEqual(set(db, "password@acme", "hunter2").returncode, 0)
+
+            render_out = run(db, ["blade", "render", template.name])
+            self.assertEqual(render_out.stdout, "user=admin\npassword=hunter2\n")
+
+            self.assertEqual(set(db, "tmpl", "u={{ user@acme }}").returncode, 0)
+            render_out = run(db, ["blade", "render", "--key", "tmpl"])
+            self.assertEqual(render_out.stdout, "u=admin")
+
+            self.assertEqual(delete(db, "password@acme").returncode, 0)
+            render_out = run(db, ["blade", "render", template.name])
+            self.assertEqual(render_out.returncode, 1)
+            self.assertEqual(render_out.stdout, "")
+
+    def test_cat(self):
+        with test_db() as db:
+            self.assertEqual(set(db, "part1", "hello ").returncode, 0)
+            self.assertEqual(set(db, "part2@ns", "world").returncode, 0)
+
+            cat_out = run(db, ["blade", "cat", "part1", "part2@ns"])
+            self.assertEqual(cat_out.stdout, "hello world")
+
+            cat_out = run(
+                db, ["blade", "cat", "part1", "part2@ns", "part1", "--delimiter", "\\n"]
+            )
+            self.assertEqual(cat_out.stdout, "hello \nworld\nhello ")
+
+            cat_out = run(db, ["blade", "cat", "missing", "part1", "part2@ns"])
+            self.assertEqual(cat_out.returncode, 1)
+            self.assertEqual(cat_out.stdout, "hello world")
+            self.assertIn("missing", cat_out.stderr)
+
+    def test_get_stdin(self):
+        with test_db() as db:
+            set(db, "a@ns", "1")
+            set(db, "b@ns", "2")
+            set(db, "c", "3")
+
+            keys_out = run(db, ["blade", "list", "ns", "--keys-only"])
+            self.assertEqual(keys_out.stdout, "b@ns\na@ns\n")
+
+            get_args = ["blade", "get", "--stdin"]
+            get_out = run(db, get_args, input=keys_out.stdout + "c\n")
+            self.assertEqual(get_out.returncode, 0)
+            self.assertEqual(get_out.stdout, "2\n1\n3\n")
+
+            get_args = ["blade", "get", "--stdin", "-0", "--delimiter", "\\0"]
+            get_out = run(db, get_args, input="a@ns\0missing\0c")
+            self.assertEqual(get_out.returncode, 1)
+            self.assertEqual(get_out.stdout, "1\x003\x00")
+            self.assertIn("keys not found: missing", get_out.stderr)
+
+            get_args = ["blade", "get", "--stdin", "--default", "none"]
+            get_out = run(db, get_args, input="missing\nc\n")
+            self.assertEqual(get_out.stdout, "none\n3\n")
+
+    def test_get_shell(self):
+        with test_db() as db:
+            set(db, "motd", "it's\na \"test\" $HOME")
+            set(db, "db-host@db", "localhost")
+            set(db, "port@db", "5432")
+
+            get_out = get(db, "motd", "--shell", "MOTD")
+            self.assertEqual(get_out.stdout, "MOTD='it'\\''s\na \"test\" $HOME'\n")
+            script = get_out.stdout + 'printf %s "$MOTD"'
+            eval_out = subprocess.run(
+                ["sh", "-c", script], capture_output=True, text=True
+            )
+            self.assertEqual(eval_out.stdout, "it's\na \"test\" $HOME")
+
+            get_args = ["blade", "get", "--stdin", "--shell", "DB_"]
+            get_out = run(db, get_args, input="db-host@db\nport@db\n")
+            expected = "DB_DB_HOST='localhost'\nDB_PORT='5432'\n"
+            self.assertEqual(get_out.stdout, expected)
+
+            self.assertEqual(get(db, "motd", "--shell", "1x").returncode, 2)
+
+    def test_direnv(self):
+        with test_db() as db:
+            set(db, "db-host@proj", "it's")
+            set(db, "port@proj", "5432")
+
+            direnv_out = run(db, ["blade", "direnv", "proj"])
+            self.assertEqual(direnv_out.returncode, 0)
+            script = direnv_out.stdout + 'printf "%s %s" "$DB_HOST" "$PORT"'
+            eval_out = subprocess.run(
+                ["sh", "-c", script], capture_output=True, text=True
+            )
+            self.assertEqual(eval_out.stdout, "it's 5432")
+
+            direnv_out = run(
+                db, ["blade", "direnv", "proj"], env={"DIRENV_IN_ENVRC": "1"}
+            )
+            self.assertTrue(direnv_out.stdout.startswith(f"watch_file '{db}' "))
+
+            stdlib_out = run(db, ["blade", "direnv", "--stdlib"])
+            self.assertIn("use_blade() {", stdlib_out.stdout)
+
+            set(db, "db_host@proj", "x")
+            direnv_out = run(db, ["blade", "direnv", "proj"])
+            self.assertEqual(direnv_out.returncode, 2)
+            self.assertEqual(direnv_out.stdout, "")
+
+    def test_set_many(self):
+        with test_db() as db:
+            set_out = run(db, ["blade", "set", "host=localhost", "port=5432", "a@b=c"])
+            self.assertEqual(set_out.returncode, 0)
+            self.assertEqual(get(db, "host").stdout, "localhost")
+            self.assertEqual(get(db, "port").stdout, "5432")
+            self.assertEqual(get(db, "a@b").stdout, "c")
+
+            set_args = ["blade", "set", "user=me", "--namespace", "db"]
+            self.assertEqual(run(db, set_args).returncode, 0)
+            self.assertEqual(get(db, "user@db").stdout, "me")
+
+            set_args = ["blade", "set", "x=1", "y=two", "--namespace", "n"]
+            self.assertEqual(run(db, set_args + ["--type", "int"]).returncode, 9)
+            self.assertEqual(get(db, "x@n").returncode, 1)
+
+            set_out = run(db, ["blade", "set", "x=1", "y", "z=3"])
+            self.assertEqual(set_out.returncode, 2)
+            self.assertIn("expected key=value, not: y", set_out.stderr)
+
+    def test_set_from_url(self):
+        responses = [b'{"version": 1}', b'{"version": 2}']
+
+        def handler(request):
+            if request.headers["Authorization"] != "Bearer abc":
+                request.send_response(401)
+                request.end_headers()
+                return
+
+            body = responses.pop(0)
+            request.send_response(200)
+            request.send_header("Content-Type", "application/json")
+            request.send_header("Content-Length", str(len(body)))
+            request.end_headers()
+            request.wfile.write(body)
+
+        with test_db() as db, test_server(handler) as url:
+            set_out = run(
+                db,
+                [
+                    "blade",
+                    "set",
+                    "api",
+                    "--from-url",
+                    url + "/data.json",
+                    "--header",
+                    "Authorization: Bearer abc",
+                ],
+            )
+            self.assertEqual(set_out.returncode, 0)
+            self.assertEqual(get(db, "api").stdout, '{"version": 1}')
+
+            [entry] = [json.loads(line) for line in export(db).stdout.splitlines()]
+            self.assertEqual(entry["content_type"], "application/json")
+
+            # credentials are stored as only their names
+            with sqlite3.connect(db) as conn:
+                [(headers,)] = conn.execute("select source_headers from entries")
+            self.assertEqual(headers, "Authorization")
+
+            refresh_out = run(db, ["blade", "set", "api", "--refresh"])
+            self.assertEqual(refresh_out.returncode, 2)
+            self.assertIn("BLADE_HEADER_AUTHORIZATION", refresh_out.stderr)
+
+            env = {"BLADE_HEADER_AUTHORIZATION": "Bearer abc"}
+            refresh_out = run(db, ["blade", "set", "api", "--refresh"], env=env)
+            self.assertEqual(refresh_out.returncode, 0)
+            self.assertEqual(get(db, "api").stdout, '{"version": 2}')
+
+            self.assertEqual(set(db, "api", "plain").returncode, 0)
+            [entry] = [json.loads(line) for line in export(db).stdout.splitlines()]
+            self.assertNotIn("content_type", entry)
+            refresh_out = run(db, ["blade", "set", "api", "--refresh"])
+            self.assertEqual(refresh_out.returncode, 2)
+
+    def test_webhooks(self):
+        events = []
+        failures = [1]
+
+        def handler(request):
+            body = request.rfile.read(int(request.headers["Content-Length"]))
+            if failures:
+                failures.pop()
+                request.send_response(500)
+            else:
+                events.append(json.loads(body))
+                request.send_response(204)
+            request.end_headers()
+
+        with test_db() as db, test_server(handler) as url:
+            config = f"""
+[namespaces.watched]
+webhooks = ["{url}/hook"]
+"""
+            with test_home(config) as env:
+
+                def blade(*args):
+                    return run(db, ["blade", *args], env=env)
+
+                set_out = blade("set", "a@watched", "1")
+                self.assertEqual(set_out.returncode, 0)
+                self.assertIn("retrying", set_out.stderr)
+                self.assertEqual(blade("touch", "a@watched").returncode, 0)
+                self.assertEqual(blade("delete", "a@watched").returncode, 0)
+                self.assertEqual(blade("delete", "a@watched").returncode, 0)
+                self.assertEqual(blade("set", "b", "1").returncode, 0)
+                no_hooks_out = blade("set", "c@watched", "1", "--no-hooks")
+                self.assertEqual(no_hooks_out.returncode, 0)
+
+        self.assertEqual([event["op"] for event in events], ["set", "touch", "delete"])
+        self.assertEqual(events[0]["namespace"], "watched")
+        self.assertEqual(events[0]["key"], "a")
+        self.assertIn("timestamp", events[0])
+
+    def test_webhooks_for_bulk_changes(self):
+        events = []
+
+        def handler(request):
+            body = request.rfile.read(int(request.headers["Content-Length"]))
+            events.append(json.loads(body))
+            request.send_response(204)
+            request.end_headers()
+
+        with test_db() as db, test_server(handler) as url:
+            config = f"""
+[namespaces.watched]
+webhooks = ["{url}/hook"]
+max_entries = 2
+eviction = "fifo"
+"""
+            with test_home(config) as env:
+
+                def blade(*args, input=None):
+                    return run(db, ["blade", *args], env=env, input=input)
+
+                def ops():
+                    ops = [(event["op"], event["key"]) for event in events]
+                    events.clear()
+                    return ops
+
+                entries = [
+                    {"namespace": "watched", "key": key, "value": "1"}
+                    | {"inserted_at": inserted_at, "updated_at": inserted_at}
+                    for key, inserted_at in [("a", "2024-01-01"), ("b", "2024-01-02")]
+                ]
+                jsonl = "\n".join(json.dumps(entry) for entry in entries)
+                import_out = blade("import", "--dry-run", input=jsonl)
+                self.assertEqual(import_out.returncode, 0)
+                self.assertEqual(ops(), [])
+                self.assertEqual(blade("import", input=jsonl).returncode, 0)
+                self.assertEqual(ops(), [("set", "a"), ("set", "b")])
+
+                self.assertEqual(blade("expire", "a@watched", "1h").returncode, 0)
+                self.assertEqual(blade("note", "set", "b@watched", "hi").returncode, 0)
+                self.assertEqual(ops(), [("touch", "a"), ("touch", "b")])
+
+                self.assertEqual(blade("set", "c@watched", "123").returncode, 0)
+                self.assertEqual(ops(), [("set", "c"), ("delete", "a")])
+
+                prune_args = ["prune", "--larger-than", "2"]
+                self.assertEqual(blade(*prune_args, "--dry-run").returncode, 0)
+                self.assertEqual(ops(), [])
+                self.assertEqual(blade(*prune_args).returncode, 0)
+                self.assertEqual(ops(), [("delete", "c")])
+
+                self.assertEqual(blade("expire", "b@watched", "1ms").returncode, 0)
+                time.sleep(0.1)
+                self.assertEqual(blade("gc").returncode, 0)
+                self.assertEqual(ops(), [("touch", "b"), ("delete", "b")])
+
+                # an expired key is already gone, so deleting it isn't a change
+                set_out = blade("set", "d@watched", "1", "--ttl", "1ms")
+                self.assertEqual(set_out.returncode, 0)
+                time.sleep(0.1)
+                delete_out = blade("delete", "d@watched", "e@watched")
+                self.assertIn("deleted 0 of 2 keys", delete_out.stderr)
+                self.assertEqual(ops(), [("set", "d")])
+
+    def test_hooks(self):
+        with test_db() as db, tempfile.TemporaryDirectory() as tmp:
+            log = tmp + "/log"
+            config = f"""
+[hooks]
+pre-set = "! grep -q bad"
+post-set = 'echo "$BLADE_HOOK $BLADE_KEY@$BLADE_NAMESPACE $(cat)" >> {log}'
+post-delete = 'echo "$BLADE_HOOK $BLADE_KEY@$BLADE_NAMESPACE" >> {log}'
+"""
+            with test_home(config) as env:
+
+                def blade(*args):
+                    return run(db, ["blade", *args], env=env)
+
+                self.assertEqual(blade("set", "a@ns", "good").returncode, 0)
+                set_out = blade("set", "a@ns", "bad")
+                self.assertEqual(set_out.returncode, 9)
+                self.assertIn("pre-set hook failed", set_out.stderr)
+                self.assertEqual(get(db, "a@ns").stdout, "good")
+                no_hooks_out = blade("set", "a@ns", "bad", "--no-hooks")
+                self.assertEqual(no_hooks_out.returncode, 0)
+                self.assertEqual(blade("delete", "a@ns").returncode, 0)
+
+            with open(log) as f:
+                self.assertEqual(f.read(), "post-set a@ns good\npost-delete a@ns\n")
+
+    def test_schemas(self):
+        with test_db() as db, tempfile.TemporaryDirectory() as tmp:
+            schema = {
+                "type": "object",
+                "properties": {"port": {"type": "integer"}},
+                "required": ["port"],
+            }
+            with open(tmp + "/schema.json", "w") as f:
+                json.dump(schema, f)
+
+            schema_args = ["blade", "schema", "set", "app", tmp + "/schema.json"]
+            schema_out = run(db, [*schema_args, "--prefix", "web/"])
+            self.assertEqual(schema_out.returncode, 0)
+            self.assertEqual(run(db, ["blade", "schema", "list"]).stdout, "app\tweb/\n")
+
+            self.assertEqual(set(db, "web/a@app", '{"port": 80}').returncode, 0)
+            set_out = set(db, "web/a@app", '{"port": "80"}')
+            self.assertEqual(set_out.returncode, 9)
+            self.assertIn('at /port: "80" is not of type "integer"', set_out.stderr)
+            self.assertEqual(set(db, "web/a@app", "port=80").returncode, 9)
+            self.assertEqual(get(db, "web/a@app").stdout, '{"port": 80}')
+            self.assertEqual(set(db, "db@app", "anything").returncode, 0)
+
+            delete_args = ["blade", "schema", "delete", "app", "--prefix", "web/"]
+            self.assertEqual(run(db, delete_args).returncode, 0)
+            self.assertEqual(set(db, "web/a@app", "port=80").returncode, 0)
+
+    def test_typed_values(self):
+        with test_db() as db:
+            self.assertEqual(set(db, "flag", "true", "--type", "bool").returncode, 0)
+            set_out = set(db, "flag", "ture")
+            self.assertEqual(set_out.returncode, 9)
+            self.assertIn("flag must be a bool, not: ture", set_out.stderr)
+            self.assertEqual(get(db, "flag").stdout, "true")
+
+            self.assertEqual(set(db, "flag", "Yes").returncode, 0)
+            self.assertEqual(get(db, "flag").stdout, "Yes")
+            self.assertEqual(get(db, "flag", "--type", "bool").stdout, "true")
+
+            self.assertEqual(set(db, "flag", "1.5", "--type", "float").returncode, 0)
+            self.assertEqual(get(db, "flag", "--type", "int").returncode, 9)
+            self.assertEqual(get(db, "flag", "--type", "float").stdout, "1.5")
+
+            self.assertEqual(set(db, "config", "{", "--type", "json").returncode, 9)
+            self.assertEqual(get(db, "config").returncode, 1)
+
+    def test_json_indexes_and_where(self):
+        with test_db() as db:
+            set(db, "api@work", '{"env": "prod", "replicas": 3}')
+            set(db, "web@work", '{"env": "dev", "replicas": 1}')
+            set(db, "notes@work", "not json")
+
+            def list_where(*wheres):
+                args = [arg for where in wheres for arg in ["--where", where]]
+                return run(db, ["blade", "list", "work", *args])
+
+            prod = 'api\t{"env": "prod", "replicas": 3}\n'
+            self.assertEqual(list_where("$.env = prod").stdout, prod)
+
+            index_args = ["blade", "index", "create", "work", "$.env"]
+            self.assertEqual(run(db, index_args).returncode, 0)
+            self.assertEqual(run(db, index_args).returncode, 6)
+            index_list_out = run(db, ["blade", "index", "list"])
+            self.assertEqual(index_list_out.stdout, "work\t$.env\n")
+
+            self.assertEqual(list_where("$.env = prod").stdout, prod)
+            not_prod = list_where("$.replicas >= 1", "$.env != prod")
+            self.assertTrue(not_prod.stdout.startswith("web\t"))
+            self.assertEqual(list_where("$.replicas > 3").stdout, "")
+            self.assertEqual(list_where("env = prod").returncode, 2)
+
+            drop_args = ["blade", "index", "drop", "work", "$.env"]
+            self.assertEqual(run(db, drop_args).returncode, 0)
+            self.assertEqual(run(db, drop_args).returncode, 1)
+
+    def test_query(self):
+        with test_db() as db:
+            set(db, "small@work", "1")
+            set(db, "big@work", "x" * 2000)
+            set(db, "big@home", "x" * 2000)
+
+            def query(*args):
+                return run(db, ["blade", "query", *args])
+
+            query_out = query(
+                "--namespace", "work", "--where", "size > 1KB", "--select", "key,size"
+            )
+            self.assertEqual(query_out.stdout, "big\t2000\n")
+
+            recent_out = query("--where", "updated > 1h ago", "--where", "key ~ b*")
+            self.assertEqual(
+                [line.split("\t")[:2] for line in recent_out.stdout.splitlines()],
+                [["home", "big"], ["work", "big"]],
+            )
+            self.assertEqual(query("--where", "created < 1h ago").stdout, "")
+            self.assertEqual(query("--where", "owner = me").returncode, 2)
+
+    def test_time_formats(self):
+        with test_db() as db:
+            set(db, "a", "1")
+            set(db, "a", "2")
+            conn = sqlite3.connect(db)
+            conn.execute("update entries set inserted_at = '2020-01-01 00:00:00.000'")
+            conn.commit()
+            # updates through the trigger are stored like every other time
+            (updated_at,) = conn.execute("select updated_at from entries").fetchone()
+            conn.close()
+            self.assertRegex(
+                updated_at, r"^\d{4}-\d\d-\d\d \d\d:\d\d:\d\d\.\d{3}$"
+            )
+
+            def created(*args, env=None):
+                args = ["blade", "query", "--select", "created", *args]
+                return run(db, args, env=env).stdout
+
+            self.assertEqual(created(), "2020-01-01T00:00:00.000Z\n")
+            self.assertEqual(created("--time-format", "unix"), "1577836800\n")
+            self.assertRegex(created("--time-format", "relative"), r"^\d+ years ago\n$")
+            self.assertEqual(
+                created("--local", env={"TZ": "Asia/Kolkata"}),
+                "2020-01-01T05:30:00.000+05:30\n",
+            )
+            self.assertEqual(
+                created("--local", "--utc", env={"TZ": "Asia/Kolkata"}),
+                "2020-01-01T00:00:00.000Z\n",
+            )
+
+            long_out = run(
+                db, ["blade", "list-namespaces", "--long", "--time-format", "unix"]
+            )
+            self.assertEqual(long_out.stdout, "default\t1\t1577836800\t\n")
+
+    def test_relative_time_filters(self):
+        with test_db() as db:
+            hundred_days_ago = time.gmtime(time.time() - 100 * 86400)
+            old = {
+                "namespace": "default",
+                "key": "old",
+                "value": "1",
+                "inserted_at": time.strftime("%Y-%m-%d %H:%M:%S", hundred_days_ago),
+                "updated_at": time.strftime("%Y-%m-%d %H:%M:%S", hundred_days_ago),
+            }
+            self.assertEqual(import_(db, input=json.dumps(old)).returncode, 0)
+            set(db, "new", "1")
+
+            list_out = run(db, ["blade", "list", "--updated-within", "2w"])
+            self.assertEqual(list_out.stdout, "new\t1\n")
+
+            export_out = export(db, "--since", "3mo")
+            self.assertEqual(
+                [json.loads(line)["key"] for line in export_out.stdout.splitlines()],
+                ["new"],
+            )
+            self.assertEqual(len(export(db, "--since", "1y").stdout.splitlines()), 2)
+
+            prune_out = run(db, ["blade", "prune", "--older-than", "3mo"])
+            self.assertEqual(prune_out.returncode, 0)
+            self.assertEqual(list(db).stdout, "new\t1\n")
+
+            list_out = run(db, ["blade", "list", "--updated-within", "7x"])
+            self.assertEqual(list_out.returncode, 2)
+            self.assertIn("Units are ms, s, m, h, d, w, mo, and y", list_out.stderr)
+            self.assertIn("like `2w`", export(db, "--since", "yesterday").stderr)
+
+    def test_notes(self):
+        with test_db() as db:
+            set(db, "k8s_tok@work", "abc")
+            set(db, "other@work", "def")
+
+            def note(*args):
+                return run(db, ["blade", "note", *args])
+
+            self.assertEqual(note("set", "missing", "x").returncode, 1)
+            self.assertEqual(note("get", "k8s_tok@work").returncode, 1)
+            self.assertEqual(note("set", "k8s_tok@work", "staging token").returncode, 0)
+            self.assertEqual(note("get", "k8s_tok@work").stdout, "staging token\n")
+
+            meta = get(db, "k8s_tok@work", "--meta").stdout.splitlines()
+            self.assertIn("size\t3", meta)
+            self.assertIn("note\tstaging token", meta)
+
+            list_out = run(db, ["blade", "list", "work", "--long"])
+            self.assertEqual(
+                sorted(list_out.stdout.splitlines()),
+                ["k8s_tok\tabc\tstaging token", "other\tdef\t"],
+            )
+
+            search_out = run(db, ["blade", "search", "--notes", "staging"])
+            self.assertEqual(search_out.stdout, "k8s_tok@work\n")
+
+            # notes go wherever the entry goes
+            with test_db() as other_db:
+                import_(other_db, input=export(db).stdout)
+                other_note = run(other_db, ["blade", "note", "get", "k8s_tok@work"])
+                self.assertEqual(other_note.stdout, "staging token\n")
+
+            self.assertEqual(note("delete", "k8s_tok@work").returncode, 0)
+            self.assertEqual(note("get", "k8s_tok@work").returncode, 1)
+
+    def test_rename(self):
+        with test_db() as db:
+            for key in ["old/a", "old/b/c", "older", "a.json", "b.json", "b.yaml"]:
+                set(db, key, key)
+            run(db, ["blade", "alias", "create", "current", "old/a"])
+
+            def rename(*args):
+                return run(db, ["blade", "rename", *args])
+
+            dry_run = rename("--glob", "old/*", "--replace", "new/", "--dry-run")
+            self.assertEqual(dry_run.stdout, "old/a\tnew/a\nold/b/c\tnew/b/c\n")
+            self.assertEqual(get(db, "old/a").stdout, "old/a")
+
+            rename_out = rename("--glob", "old/*", "--replace", "new/")
+            self.assertEqual(rename_out.stdout, "renamed 2 keys\n")
+            self.assertEqual(get(db, "new/b/c").stdout, "old/b/c")
+            self.assertEqual(get(db, "old/b/c").returncode, 1)
+            self.assertEqual(get(db, "current").stdout, "old/a")
+
+            # b.yaml is taken, so nothing is renamed
+            conflict = rename("--glob", "*.json", "--replace", "*.yaml")
+            self.assertEqual(conflict.returncode, 6)
+            self.assertEqual(get(db, "a.json").stdout, "a.json")
+            set(db, "x.ab", "1")
+            set(db, "xa.b", "2")
+            collision = rename("--glob", "x*.*", "--replace", "k/")
+            self.assertEqual(collision.returncode, 6)
+            self.assertIn("both x.ab and xa.b would be renamed", collision.stderr)
+
+            # a key can take the name of another key being renamed
+            set(db, "v", "1")
+            set(db, "vv", "2")
+            self.assertEqual(rename("--glob", "v*", "--replace", "vv*").returncode, 0)
+            self.assertEqual(get(db, "vv").stdout, "1")
+            self.assertEqual(get(db, "vvv").stdout, "2")
+
+            self.assertEqual(rename("--glob", "z*", "--replace", "y").returncode, 1)
+            self.assertEqual(rename("--glob", "*", "--replace", "**").returncode, 2)
+
+    def test_move(self):
+        with test_db() as db:
+            set(db, "proj-b@work", "old")
+            for key in ["proj-a", "proj-b", "other"]:
+                set(db, key, "new")
+            run(db, ["blade", "alias", "create", "current", "proj-a"])
+
+            def move(*args):
+                args = ["--glob", "proj-*", "--to", "work", *args]
+                return run(db, ["blade", "move", *args])
+
+            conflict = move()
+            self.assertEqual(conflict.returncode, 6)
+            self.assertEqual(get(db, "proj-a").stdout, "new")
+
+            move_out = move("--on-conflict", "skip")
+            self.assertEqual(move_out.stdout, "moved 1, skipped 1\n")
+            self.assertEqual(get(db, "proj-a@work").stdout, "new")
+            self.assertEqual(get(db, "proj-b@work").stdout, "old")
+            self.assertEqual(get(db, "current").stdout, "new")
+
+            move_out = move("--on-conflict", "newest")
+            self.assertEqual(move_out.stdout, "moved 1, skipped 0\n")
+            self.assertEqual(get(db, "proj-b@work").stdout, "new")
+            self.assertEqual(list(db).stdout, "other\tnew\n")
+
+            self.assertEqual(move().returncode, 1)
+
+    def test_namespace_copy(self):
+        with test_db() as db:
+            set(db, "url@config", "prod.example.com")
+            set(db, "port@config", "80")
+            set(db, "name@config", "x")
+            run(db, ["blade", "note", "set", "url@config", "the host"])
+            set(db, "v2/port@staging", "8080")
+
+            def copy(*args):
+                return run(db, ["blade", "namespace", "copy", "config", *args])
+
+            args = ["staging", "--key-prefix", "v2/", "--filter", "*r*"]
+            self.assertEqual(copy(*args).returncode, 6)
+            staging = list_with_namespace(db, "staging").stdout
+            self.assertEqual(staging, "v2/port\t8080\n")
+
+            self.assertEqual(copy(*args, "--overwrite").stdout, "copied 2 keys\n")
+            self.assertEqual(get(db, "v2/port@staging").stdout, "80")
+            self.assertEqual(get(db, "v2/name@staging").returncode, 1)
+            note_out = run(db, ["blade", "note", "get", "v2/url@staging"])
+            self.assertEqual(note_out.stdout, "the host\n")
+            self.assertEqual(get(db, "url@config").stdout, "prod.example.com")
+
+            self.assertEqual(copy("config").returncode, 2)
+
+    def test_capture_env(self):
+        with test_db() as db:
+            env = {"MYAPP_DB_HOST": "db.local", "MYAPP_PORT": "5432", "OTHER": "x"}
+
+            args = ["blade", "set-from-env", "host@ns", "MYAPP_DB_HOST"]
+            self.assertEqual(run(db, args, env=env).returncode, 0)
+            self.assertEqual(get(db, "host@ns").stdout, "db.local")
+            missing = run(db, ["blade", "set-from-env", "host", "NOT_SET_ANYWHERE"])
+            self.assertEqual(missing.returncode, 1)
+
+            args = ["blade", "capture-env", "--prefix", "MYAPP_"]
+            args += ["--namespace", "myapp"]
+            self.assertEqual(run(db, args, env=env).stdout, "captured 2 variables\n")
+            self.assertEqual(get(db, "db_host@myapp").stdout, "db.local")
+            self.assertEqual(get(db, "other@myapp").returncode, 1)
+
+            args = ["blade", "get", "--stdin", "--shell", "MYAPP_"]
+            shell = run(db, args, input="port@myapp\n").stdout
+            self.assertEqual(shell, "MYAPP_PORT='5432'\n")
+
+    def test_push_and_lrange(self):
+        with test_db() as db:
+
+            def lrange(*args):
+                return run(db, ["blade", "lrange", "log", *args]).stdout
+
+            push = run(db, ["blade", "push", "log", "started", "working"])
+            self.assertEqual(push.stdout, "2\n")
+            push = run(db, ["blade", "push", "log", "--json", "3", '{"done":true}'])
+            self.assertEqual(push.stdout, "4\n")
+
+            self.assertEqual(lrange(), 'started\nworking\n3\n{"done":true}\n')
+            self.assertEqual(lrange("1", "-2"), "working\n3\n")
+            self.assertEqual(lrange("-100", "0"), "started\n")
+            self.assertEqual(lrange("3", "1"), "")
+            stored = '["started","working",3,{"done":true}]'
+            self.assertEqual(get(db, "log").stdout, stored)
+
+            set(db, "plain", "text")
+            self.assertEqual(run(db, ["blade", "push", "plain", "x"]).returncode, 2)
+            self.assertEqual(run(db, ["blade", "lrange", "missing"]).returncode, 1)
+
+            run(db, ["blade", "alias", "create", "latest", "log"])
+            push = run(db, ["blade", "push", "latest", "again"])
+            self.assertEqual(push.stdout, "5\n")
+            self.assertEqual(lrange("-1"), "again\n")
+            self.assertEqual(list_with_namespace(db, "default").stdout.count("\n"), 2)
+
+    def test_mount(self):
+        with test_db() as db, tempfile.TemporaryDirectory() as mountpoint:
+            if "\n  mount " not in run(db, ["blade", "--help"]).stdout:
+                self.skipTest("blade was built without the fuser feature")
+
+            set(db, "a", "1")
+            set(db, "todo/today@notes", "ship it")
+
+            env = {**os.environ, "DB_LOCATION": db}
+            mount = subprocess.Popen(["blade", "mount", mountpoint], env=env)
+            try:
+                for _ in range(50):
+                    if os.path.ismount(mountpoint) or mount.poll() is not None:
+                        break
+                    time.sleep(0.1)
+                if not os.path.ismount(mountpoint):
+                    self.skipTest("FUSE filesystems can't be mounted here")
+
+                self.assertEqual(sorted(os.listdir(mountpoint)), ["default", "notes"])
+                self.assertEqual(os.listdir(f"{mountpoint}/notes"), ["todo%2Ftoday"])
+                with open(f"{mountpoint}/notes/todo%2Ftoday") as f:
+                    self.assertEqual(f.read(), "ship it")
+
+                with open(f"{mountpoint}/default/a", "w") as f:
+                    f.write("2")
+                with open(f"{mountpoint}/default/b", "w") as f:
+                    f.write("new")
+                self.assertEqual(get(db, "a").stdout, "2")
+                self.assertEqual(get(db, "b").stdout, "new")
+
+                os.remove(f"{mountpoint}/default/b")
+                self.assertEqual(get(db, "b").returncode, 1)
+            finally:
+                subprocess.run(["umount", mountpoint])
+                mount.wait()
+
+    def test_libsql_url(self):
+        # nothing listens on port 1, so this only gets as far as connecting
+        get = run("libsql://localhost:1", ["blade", "get", "a"])
+        self.assertNotEqual(get.returncode, 0)
+        if "libsql feature" in get.stderr:
+            self.assertEqual(get.returncode, 2)
+
+        # changes that would skip hooks or quotas are refused before connecting
+        config = """
+[hooks]
+post-set = "true"
+
+[namespaces.limited]
+max_entries = 1
+"""
+        with test_home(config) as env:
+            url = "libsql://localhost:1"
+            set_out = run(url, ["blade", "set", "a", "1"], env=env)
+            self.assertEqual(set_out.returncode, 2)
+            set_args = ["blade", "set", "a@limited", "1", "--no-hooks"]
+            quota_out = run(url, set_args, env=env)
+            self.assertEqual(quota_out.returncode, 2)
+            if "libsql feature" not in set_out.stderr:
+                self.assertIn("doesn't run hooks", set_out.stderr)
+                self.assertIn("doesn't enforce quotas", quota_out.stderr)
+
+    def test_c_api(self):
+        name = "libblade.dylib" if sys.platform == "darwin" else "libblade.so"
+        path = os.path.join(os.path.dirname(__file__) or ".", "target/debug", name)
+        if not os.path.exists(path):
+            self.skipTest(f"{path} hasn't been built")
+
+        lib = ctypes.CDLL(path)
+        lib.blade_open.restype = ctypes.c_void_p
+        lib.blade_open.argtypes = [ctypes.c_char_p]
+        lib.blade_close.argtypes = [ctypes.c_void_p]
+        lib.blade_last_error.restype = ctypes.c_char_p
+        lib.blade_set.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p]
+        lib.blade_set.argtypes += [ctypes.c_char_p, ctypes.c_size_t]
+        lib.blade_delete.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p]
+
+        value = ctypes.POINTER(ctypes.c_uint8)()
+        keys = ctypes.POINTER(ctypes.c_char_p)()
+        size = ctypes.c_size_t()
+        lib.blade_get.argtypes = lib.blade_delete.argtypes + [
+            ctypes.POINTER(type(value)),
+            ctypes.POINTER(ctypes.c_size_t),
+        ]
+        lib.blade_list.argtypes = [ctypes.c_void_p, ctypes.c_char_p]
+        lib.blade_list.argtypes += [
+            ctypes.POINTER(type(keys)),
+            ctypes.POINTER(ctypes.c_size_t),
+        ]
+
+        with test_db() as db:
+            blade = lib.blade_open(db.encode())
+            self.assertIsNotNone(blade)
+            try:
+                self.assertEqual(lib.blade_set(blade, b"default", b"a", b"1\0", 2), 0)
+                self.assertEqual(lib.blade_set(blade, b"ns", b"b", b"2", 1), 0)
+
+                got = lib.blade_get(blade, b"default", b"a", value, size)
+                self.assertEqual(got, 0)
+                self.assertEqual(ctypes.string_at(value, size.value), b"1\0")
+                lib.blade_free_value(value, size)
+
+                self.assertEqual(lib.blade_list(blade, b"ns", keys, size), 0)
+                self.assertEqual([keys[i] for i in range(size.value)], [b"b"])
+                lib.blade_free_list(keys, size)
+
+                self.assertEqual(lib.blade_delete(blade, b"ns", b"b"), 0)
+                self.assertEqual(lib.blade_delete(blade, b"ns", b"b"), 1)
+                self.assertEqual(lib.blade_last_error(), b"key not found: b")
+                self.assertEqual(lib.blade_delete(blade, None, b"b"), 2)
+                self.assertEqual(lib.blade_get(None, b"ns", b"b", value, size), 2)
+                self.assertEqual(lib.blade_last_error(), b"db is null")
+                self.assertEqual(lib.blade_get(blade, b"ns", b"b", None, size), 2)
+                self.assertEqual(lib.blade_list(None, b"ns", keys, size), 2)
+
+                # threads take turns with the connection
+                def set_keys(i):
+                    for j in range(20):
+                        key = f"{i}-{j}".encode()
+                        self.assertEqual(lib.blade_set(blade, b"t", key, b"1", 1), 0)
+
+                threads = [
+                    threading.Thread(target=set_keys, args=(i,)) for i in range(4)
+                ]
+                for thread in threads:
+                    thread.start()
+                for thread in threads:
+                    thread.join()
+                self.assertEqual(lib.blade_list(blade, b"t", keys, size), 0)
+                self.assertEqual(size.value, 80)
+                lib.blade_free_list(keys, size)
+            finally:
+                lib.blade_close(blade)
+
+            self.assertEqual(get(db, "a").stdout, "1\0")
+
+    def test_history_and_rollback(self):
+        with test_db() as db:
+            for value in ["1", "2", "3"]:
+                set(db, "a", value)
+
+            rollback = run(db, ["blade", "rollback", "a", "--to-version", "1"])
+            self.assertEqual(rollback.returncode, 0)
+            self.assertEqual(get(db, "a").stdout, "1")
+
+            history = run(db, ["blade", "history", "a"]).stdout.splitlines()
+            changes = [line.split("\t")[2] for line in history]
+            self.assertEqual(changes, ["set", "set", "set", "rollback to 1"])
+
+            run(db, ["blade", "delete", "a"])
+            rollback = run(db, ["blade", "rollback", "a", "--to", "2099-01-01"])
+            self.assertEqual(rollback.returncode, 1)
+
+            second_recorded_at = history[1].split("\t")[1]
+            rollback = run(db, ["blade", "rollback", "a", "--to", second_recorded_at])
+            self.assertEqual(rollback.returncode, 0)
+            self.assertEqual(get(db, "a").stdout, "2")
+
+            run(db, ["blade", "rename", "--glob", "a", "--replace", "b"])
+            history = run(db, ["blade", "history", "b"]).stdout.splitlines()
+            self.assertEqual(history[-1].split("\t")[2], "rollback to 2")
+
+            rollback = run(db, ["blade", "rollback", "b", "--to-version", "99"])
+            self.assertEqual(rollback.returncode, 1)
+            self.assertEqual(run(db, ["blade", "rollback", "b"]).returncode, 2)
+
+    def test_history_retention(self):
+        def history_values(db):
+            conn = sqlite3.connect(db)
+            rows = conn.execute("select value from entry_history order by key, version")
+            values = [value for (value,) in rows]
+            conn.close()
+            return values
+
+        with test_db() as db:
+            for value in ["1", "2", "3"]:
+                set(db, "a", value)
+            set(db, "secret", "hunter2")
+            delete(db, "secret")
+
+            gc_out = run(db, ["blade", "gc"])
+            self.assertEqual(gc_out.stdout, "deleted 0 expired entries\n")
+            self.assertIn(b"hunter2", history_values(db))
+
+            with test_home("[history]\nmax_versions = 1") as env:
+                gc_out = run(db, ["blade", "gc"], env=env)
+            self.assertEqual(
+                gc_out.stdout,
+                "deleted 0 expired entries\ntrimmed 3 versions from history\n",
+            )
+            self.assertEqual(history_values(db), [b"3", None])
+
+            set(db, "b", "1")
+            set(db, "b", "2")
+            with test_home('[history]\nmax_age = "0s"') as env:
+                gc_out = run(db, ["blade", "gc"], env=env)
+            self.assertIn("trimmed 2 versions from history", gc_out.stdout)
+            self.assertEqual(history_values(db), [b"3", b"2"])
+
+            with test_home('[history]\nmax_age = "soon"') as env:
+                self.assertEqual(run(db, ["blade", "gc"], env=env).returncode, 2)
+
+            set(db, "c", "1")
+            set(db, "c", "2")
+            purge_out = run(db, ["blade", "history", "purge", "c"])
+            self.assertEqual(purge_out.stdout, "purged 1 versions\n")
+            history = run(db, ["blade", "history", "c"]).stdout.splitlines()
+            self.assertEqual(len(history), 1)
+            self.assertEqual(get(db, "c").stdout, "2")
+
+            delete_out = run(db, ["blade", "delete", "--purge-history", "c"])
+            self.assertEqual(delete_out.returncode, 0)
+            self.assertEqual(run(db, ["blade", "history", "c"]).returncode, 1)
+
+    def test_export_html(self):
+        with test_db() as db, tempfile.TemporaryDirectory() as site:
+            set(db, "todo/today@notes", "# Today\n\n- **ship** <it>")
+            set(db, "other@work", "x")
+
+            export_out = export(db, "--html", site, "--namespace", "notes")
+            self.assertEqual(export_out.returncode, 0)
+            self.assertEqual(
+                sorted(os.listdir(site)), ["index.html", "todo_2Ftoday@notes.html"]
+            )
+
+            with open(site + "/index.html") as f:
+                index = f.read()
+            self.assertIn('<a href="todo_2Ftoday@notes.html">todo/today</a>', index)
+            self.assertNotIn("other", index)
+
+            with open(site + "/todo_2Ftoday@notes.html") as f:
+                page = f.read()
+            self.assertIn("<h1>Today</h1>", page)
+            self.assertIn("<strong>ship</strong> &lt;it&gt;", page)
+
+    def test_get_render(self):
+        with test_db() as db:
+            set(db, "config", '{"port":80}')
+            set(db, "notes", "# Title\n\n- **one**\n- two\n")
+
+            self.assertEqual(get(db, "config", "--render").stdout, '{"port":80}')
+
+            rendered = run_in_terminal(db, ["blade", "get", "config", "--render"])
+            self.assertIn("\x1b[", rendered)
+            self.assertIn('"port"', rendered)
+            self.assertIn("\n}", rendered)
+
+            rendered = run_in_terminal(db, ["blade", "get", "notes", "--render"])
+            self.assertNotIn("#", rendered)
+            self.assertIn("• ", rendered)
+            self.assertIn("two\n", rendered)
+
+    def test_get_qr(self):
+        with test_db() as db:
+            set(db, "wifi", "WIFI:S:home;T:WPA;P:hunter2;;")
+            qr_out = get(db, "wifi", "--qr")
+            self.assertEqual(qr_out.returncode, 0)
+            self.assertIn("▀", qr_out.stdout)
+            self.assertNotIn("hunter2", qr_out.stdout)
+
+            set(db, "big", "x" * 2000)
+            qr_out = get(db, "big", "--qr")
+            self.assertEqual(qr_out.returncode, 2)
+            self.assertIn("too large for a QR code", qr_out.stderr)
+
+    def test_base64_and_hex(self):
+        with test_db() as db:
+            self.assertEqual(set(db, "greeting", "aGVsbG8=", "--base64").returncode, 0)
+            self.assertEqual(get(db, "greeting").stdout, "hello")
+            self.assertEqual(get(db, "greeting", "--hex").stdout, "68656c6c6f")
+
+            self.assertEqual(set(db, "bytes", "ff00 FE\n", "--hex").returncode, 0)
+            self.assertEqual(get(db, "bytes", "--base64").stdout, "/wD+")
+            self.assertEqual(get(db, "bytes", "--hex").stdout, "ff00fe")
+
+            self.assertEqual(set(db, "bytes", "zz", "--hex").returncode, 2)
+            self.assertEqual(set(db, "bytes", "!!", "--base64").returncode, 2)
+            self.assertEqual(get(db, "bytes", "--hex").stdout, "ff00fe")
+
+    def test_pick(self):
+        with test_db() as db:
+            for key in ["alpha", "beta-two", "gamma"]:
+                set(db, key, "value of " + key)
+
+            pick_args = ["blade", "pick", "--then", "edit"]
+            env = {"EDITOR": "printf edited >"}
+            self.assertEqual(run_interactive(db, pick_args, b"bt\r", env=env), 0)
+            self.assertEqual(get(db, "beta-two").stdout, "edited")
+
+            pick_args = ["blade", "pick", "--then", "delete"]
+            self.assertEqual(run_interactive(db, pick_args, b"gam\r"), 0)
+            self.assertEqual(get(db, "gamma").returncode, 1)
+
+            self.assertEqual(run_interactive(db, pick_args, b"\x1b"), 0)
+            self.assertEqual(get(db, "alpha").returncode, 0)
+
+            self.assertEqual(run(db, ["blade", "pick", "empty"]).returncode, 1)
+
+    def test_delete_interactive(self):
+        with test_db() as db:
+            for key in ["alpha", "beta", "gamma"]:
+                set(db, key, "value of " + key)
+
+            delete_args = ["blade", "delete", "--interactive"]
+            self.assertEqual(run_interactive(db, delete_args, b"\t\t\rn\n"), 0)
+            self.assertEqual(get(db, "alpha").returncode, 0)
+
+            self.assertEqual(run_interactive(db, delete_args, b"\t\t\ry\n"), 0)
+            self.assertEqual(get(db, "alpha").returncode, 1)
+            self.assertEqual(get(db, "beta").returncode, 1)
+            self.assertEqual(get(db, "gamma").stdout, "value of gamma")
+
+            self.assertEqual(run(db, ["blade", "delete"]).returncode, 2)
+
+    def test_error_exit_codes(self):
+        with test_db() as db:
+            get_out = get(db, "missing")
+            self.assertEqual(get_out.returncode, 1)
+            self.assertEqual(get_out.stdout, "")
+            self.assertIn("key not found: missing", get_out.stderr)
+
+            get_out = run(db, ["blade", "--errors", "json", "get", "missing"])
+            self.assertEqual(get_out.returncode, 1)
+            self.assertEqual(
+                json.loads(get_out.stderr),
+                {
+                    "error": {
+                        "kind": "not_found",
+                        "code": 1,
+                        "message": "key not found: missing",
+                        "causes": [],
+                    }
+                },
+            )
+
+            set_out = run(
+                db, ["blade", "set", "", "value"], env={"BLADE_ERRORS": "json"}
+            )
+            self.assertEqual(set_out.returncode, 2)
+            self.assertEqual(json.loads(set_out.stderr)["error"]["kind"], "usage")
+
     def test_errors_if_key_is_empty(self):
         with test_db() as db, random_kv() as (_key, value):
-            self.assertEqual(set(db, "", value).returncode, 1)
-            self.assertEqual(set(db, " ", value).returncode, 1)
-            self.assertEqual(set(db, "@namespace", value).returncode, 1)
-            self.assertEqual(set(db, "  @namespace", value).returncode, 1)
+            self.assertEqual(set(db, "", value).returncode, 2)
+            self.assertEqual(set(db, " ", value).returncode, 2)
+            self.assertEqual(set(db, "@namespace", value).returncode, 2)
+            self.assertEqual(set(db, "  @namespace", value).returncode, 2)
 
-            self.assertEqual(get(db, "").returncode, 1)
-            self.assertEqual(get(db, " ").returncode, 1)
-            self.assertEqual(get(db, "@namespace").returncode, 1)
-            self.assertEqual(get(db, "  @namespace").returncode, 1)
+            self.assertEqual(get(db, "").returncode, 2)
+            self.assertEqual(get(db, " ").returncode, 2)
+            self.assertEqual(get(db, "@namespace").returncode, 2)
+            self.assertEqual(get(db, "  @namespace").returncode, 2)
 
     def test_errors_if_namespace_is_empty(self):
         with test_db() as db, random_kv() as (_key, value):
-            self.assertEqual(set(db, "abc@", value).returncode, 1)
-            self.assertEqual(set(db, "abc@   ", value).returncode, 1)
+            self.assertEqual(set(db, "abc@", value).returncode, 2)
+            self.assertEqual(set(db, "abc@   ", value).returncode, 2)
 
-            self.assertEqual(get(db, "abc@").returncode, 1)
-            self.assertEqual(get(db, "abc@    ").returncode, 1)
+            self.assertEqual(get(db, "abc@").returncode, 2)
+            self.assertEqual(get(db, "abc@    ").returncode, 2)
 
 
 if __name__ == "__main__":